@@ -1,28 +1,60 @@
-use chess::{Color, Game};
+use chess::{Color, Engine, Game, OpeningBook, SearchLimits};
 use dioxus::prelude::*;
 use dioxus_router::prelude::*;
 
 use super::super::{
+    helpers::with_event_log,
     router::Route,
-    shared_states::{Analyze, BoardSize, GameId, Perspective, Settings},
+    shared_states::{Analyze, BoardSize, Book, BotEngine, GameId, Perspective, Settings, Syzygy},
     stockfish::Eval,
 };
+use crate::common::tablebase::open_tablebase;
 
 const WIDGET_HEIGHT: u32 = 800;
 
+// About a second of thinking in the browser
+fn get_bot_limits() -> SearchLimits {
+    SearchLimits::nodes(50_000)
+}
+
 pub(crate) fn App(cx: Scope) -> Element {
     log::info!("app launched");
 
     use_shared_state_provider(cx, || Eval::Centipawns(0));
     use_shared_state_provider(cx, || GameId(None));
-    use_shared_state_provider(cx, Game::new);
+    use_shared_state_provider(cx, || with_event_log(Game::new()));
     use_shared_state_provider(cx, || BoardSize(WIDGET_HEIGHT));
     use_shared_state_provider(cx, || Perspective(Color::White));
     use_shared_state_provider(cx, || Analyze(false));
     use_shared_state_provider(cx, Settings::new);
+    use_shared_state_provider(cx, || Book(None));
+    use_shared_state_provider(cx, || BotEngine(Some(Engine::new(get_bot_limits()))));
+    use_shared_state_provider(cx, || Syzygy(open_tablebase()));
+
+    let book = use_shared_state::<Book>(cx)?;
+    use_future(cx, (), |_| load_opening_book(book.to_owned()));
 
     cx.render(rsx! {
         style { {include_str!("../../../styles/output.css")} }
         Router::<Route> {}
     })
 }
+
+async fn load_opening_book(book: UseSharedState<Book>) {
+    #[cfg(feature = "desktop")]
+    use crate::common::opening_book::get_opening_book;
+    #[cfg(feature = "web")]
+    use crate::server::server_functions::get_opening_book;
+
+    let bytes = match get_opening_book().await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            log::info!("Playing without an opening book: {e}");
+            return;
+        }
+    };
+    match OpeningBook::from_bytes(&bytes) {
+        Ok(opening_book) => **book.write() = Some(opening_book),
+        Err(e) => log::error!("Failed to read the opening book: {e}"),
+    }
+}
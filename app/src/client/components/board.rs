@@ -1,6 +1,9 @@
-use std::collections::HashSet;
+use std::{collections::HashSet, time::Duration};
 
-use async_std::channel::{unbounded, Receiver, Sender};
+use async_std::{
+    channel::{unbounded, Receiver, Sender},
+    task::sleep,
+};
 use chess::{Color, Game, Move, Piece, PlayerKind, Position};
 use dioxus::{
     html::{
@@ -20,7 +23,7 @@ use super::super::{
     components::{Arrow, BoardSquare, Piece},
     game_socket::create_game_socket,
     mouse_click::MouseClick,
-    shared_states::{Analyze, BoardSize, GameId, Perspective},
+    shared_states::{Analyze, BoardSize, Book, BotEngine, GameId, Perspective, Syzygy},
     stockfish::{
         core::{on_game_changed, toggle_stockfish},
         interface::Process,
@@ -35,6 +38,8 @@ pub(crate) type Channel<T> = (Sender<T>, Receiver<T>);
 static MOVE_CHANNEL: Lazy<Channel<Move>> = Lazy::new(unbounded);
 // Channel for telling dragged pieces how far they have been dragged
 static DRAG_CHANNEL: Lazy<Channel<ElementPoint>> = Lazy::new(unbounded);
+// About 40 ms of the bot's thinking in the browser, between redraws
+const BOT_PAUSE_NODES: u64 = 2_000;
 
 #[derive(Props, PartialEq)]
 pub(crate) struct BoardProps {
@@ -57,6 +62,7 @@ pub(crate) struct BoardHooks<'a> {
     pub(crate) board_size: u32,
     pub(crate) perspective: Color,
     pub(crate) selected_squares: &'a UseRef<HashSet<Position>>,
+    pub(crate) pending_promotion: &'a UseState<Option<Move>>,
 }
 
 pub(crate) fn Board(cx: Scope<BoardProps>) -> Element {
@@ -77,6 +83,7 @@ pub(crate) fn Board(cx: Scope<BoardProps>) -> Element {
         board_size: **use_shared_state::<BoardSize>(cx)?.read(),
         perspective: **use_shared_state::<Perspective>(cx)?.read(),
         selected_squares: use_ref::<HashSet<Position>>(cx, HashSet::new),
+        pending_promotion: use_state::<Option<Move>>(cx, || None),
     };
 
     use_effect(cx, use_shared_state::<Analyze>(cx).unwrap(), |analyze| {
@@ -91,10 +98,32 @@ pub(crate) fn Board(cx: Scope<BoardProps>) -> Element {
     use_effect(cx, hooks.game, |game| {
         on_game_changed(
             game.read().get_fen_str(),
+            game.read().is_chess960(),
             hooks.stockfish_process.to_owned(),
             hooks.analysis_arrows.to_owned(),
         )
     });
+    let engine = use_shared_state::<BotEngine>(cx)?;
+    let book = use_shared_state::<Book>(cx)?;
+    let syzygy = use_shared_state::<Syzygy>(cx)?;
+    use_effect(
+        cx,
+        (
+            hooks.game,
+            &cx.props.white_player_kind,
+            &cx.props.black_player_kind,
+        ),
+        |(game, white_player_kind, black_player_kind)| {
+            play_bot_move(
+                game,
+                engine.to_owned(),
+                book.to_owned(),
+                syzygy.to_owned(),
+                white_player_kind,
+                black_player_kind,
+            )
+        },
+    );
     use_future(cx, use_shared_state::<GameId>(cx).unwrap(), |game_id| {
         create_game_socket(hooks.game.to_owned(), game_id, &MOVE_CHANNEL.1)
     });
@@ -137,6 +166,21 @@ pub(crate) fn Board(cx: Scope<BoardProps>) -> Element {
                     }),
                 }
             },
+            // promotion choices
+            for (piece, pos) in get_promotion_choices(&hooks) {
+                BoardSquare {
+                    class: "promotion-square".to_string(),
+                    position: pos,
+                    hovered: false,
+                }
+                img {
+                    src: "{get_piece_image_file(&hooks.settings.read().piece_theme, piece)}",
+                    class: "images promotion-piece",
+                    style: "left: {_to_point(&hooks, &pos).x}px; top: {_to_point(&hooks, &pos).y}px;",
+                    width: "{hooks.board_size / 8}",
+                    height: "{hooks.board_size / 8}",
+                }
+            },
             // arrows
             for data in hooks.arrows.read().get().into_iter()
                 .chain(hooks.drawing_arrow.read().into_iter())
@@ -247,23 +291,131 @@ fn drop_piece(
 ) {
     let from = _to_position(hooks, point);
     let to = _to_position(hooks, &event.element_coordinates());
+    let mv = Move::new(from, to);
+    if can_move(props, hooks) && hooks.game.read().is_move_valid(&mv).is_ok() {
+        if hooks.game.read().is_promotion_move(&mv) {
+            hooks.pending_promotion.set(Some(mv));
+        } else {
+            make_move(props, hooks, mv);
+        }
+    }
+}
+
+fn make_move(props: &BoardProps, hooks: &BoardHooks, mv: Move) {
     let opponent_player_kind = match hooks.game.read().get_current_player() {
         Color::White => props.black_player_kind,
         Color::Black => props.white_player_kind,
     };
-    let mv = Move::new(from, to);
-    if can_move(props, hooks) && hooks.game.read().is_move_valid(&mv).is_ok() {
-        hooks.game.write().move_piece(from, to).ok();
-        if opponent_player_kind == PlayerKind::Remote {
-            spawn(async move {
-                if let Err(e) = MOVE_CHANNEL.0.send(mv).await {
-                    log::error!("Failed to send move: {e}")
-                }
-            })
+    hooks.game.write().make_move(mv).ok();
+    if opponent_player_kind == PlayerKind::Remote {
+        spawn(async move {
+            if let Err(e) = MOVE_CHANNEL.0.send(mv).await {
+                log::error!("Failed to send move: {e}")
+            }
+        })
+    }
+}
+
+// Answers with the built-in engine when it's a bot's turn in the game itself
+async fn play_bot_move(
+    game: UseSharedState<Game>,
+    engine: UseSharedState<BotEngine>,
+    book: UseSharedState<Book>,
+    syzygy: UseSharedState<Syzygy>,
+    white_player_kind: PlayerKind,
+    black_player_kind: PlayerKind,
+) {
+    let bot_to_move = match game.read().get_current_player() {
+        Color::White => white_player_kind == PlayerKind::Bot,
+        Color::Black => black_player_kind == PlayerKind::Bot,
+    };
+    if !bot_to_move || game.read().is_replaying() || game.read().game_over() {
+        return;
+    }
+    // the bot plays from the book for as long as it can
+    let book_move = book
+        .read()
+        .as_ref()
+        .and_then(|book| book.choose_move(&game.read(), rand::random()));
+    // and plays endgames perfectly once few enough pieces are left
+    let best_move = book_move.or_else(|| get_tablebase_move(&game.read(), &syzygy.read()));
+    let best_move = match best_move {
+        Some(mv) => Some(mv),
+        None => search_bot_move(&engine, &game).await,
+    };
+    if let Some(mv) = best_move {
+        game.write().make_move(mv).ok();
+    }
+}
+
+// Pauses the search now and then to let the board redraw, since a whole
+// search would freeze it, most of all in the browser
+async fn search_bot_move(
+    engine: &UseSharedState<BotEngine>,
+    game: &UseSharedState<Game>,
+) -> Option<Move> {
+    // no borrow of the engine is held while waiting, and an earlier search
+    // that is still going has it
+    let mut searching = engine.write().take()?;
+    let position = game.read().clone();
+    let mut best_move = None;
+    let mut moved_on = false;
+    for result in searching.iterate(&position).pausing_every(BOT_PAUSE_NODES) {
+        best_move = Some(result.best_move);
+        sleep(Duration::ZERO).await;
+        // the game may have moved on while the engine was thinking
+        let game = game.read();
+        moved_on = game.get_zobrist_key() != position.get_zobrist_key() || game.is_replaying();
+        if moved_on {
+            break;
+        }
+    }
+    // even to a new game, which couldn't clear the engine while it was out
+    if moved_on {
+        searching.clear();
+    }
+    **engine.write() = Some(searching);
+    best_move.filter(|_| !moved_on)
+}
+
+fn get_tablebase_move(game: &Game, syzygy: &Syzygy) -> Option<Move> {
+    let tablebase = syzygy.as_ref().filter(|tablebase| tablebase.can_probe(game))?;
+    match tablebase.get_best_move(game) {
+        Ok(mv) => mv,
+        Err(e) => {
+            log::warn!("Failed to probe the tablebase: {e}");
+            None
         }
     }
 }
 
+// The pieces a pawn can promote to, stacked from the promotion square towards the center
+fn get_promotion_choices(hooks: &BoardHooks) -> Vec<(Piece, Position)> {
+    hooks.pending_promotion.get().map_or(vec![], |mv| {
+        let player = hooks.game.read().get_current_player();
+        Piece::get_promotion_options(player)
+            .into_iter()
+            .enumerate()
+            .map(|(i, piece)| {
+                let y = if mv.to.y == 7 { 7 - i } else { i };
+                (piece, Position::new(mv.to.x, y))
+            })
+            .collect()
+    })
+}
+
+fn select_promotion(props: &BoardProps, hooks: &BoardHooks, event: &Event<MouseData>) {
+    let pos = _to_position(hooks, &event.element_coordinates());
+    if let Some(mv) = *hooks.pending_promotion.get()
+        && let Some((piece, _)) = get_promotion_choices(hooks)
+            .into_iter()
+            .find(|(_, choice)| *choice == pos)
+    {
+        make_move(props, hooks, Move::with_promotion(mv.from, mv.to, piece));
+    }
+    hooks.pending_promotion.set(None);
+}
+
 fn complete_arrow(hooks: &BoardHooks) {
     if let Some(arrow_data) = *hooks.drawing_arrow.read() {
         if arrow_data.has_length() {
@@ -303,7 +455,11 @@ fn handle_on_mouse_down_event(hooks: &BoardHooks, event: Event<MouseData>) {
 fn handle_on_mouse_up_event(props: &BoardProps, hooks: &BoardHooks, event: Event<MouseData>) {
     if let Some(mouse_down) = hooks.mouse_down_state.get() {
         if mouse_down.kind.contains(MouseButton::Primary) {
-            drop_piece(props, hooks, &event, &mouse_down.point);
+            if hooks.pending_promotion.is_some() {
+                select_promotion(props, hooks, &event);
+            } else {
+                drop_piece(props, hooks, &event, &mouse_down.point);
+            }
             hooks.arrows.write().clear();
             hooks.selected_squares.write().clear();
         } else if mouse_down.kind.contains(MouseButton::Secondary) {
@@ -1,8 +1,12 @@
-use chess::{Color, Game, Player, PlayerKind};
+use chess::{Color, Game, Player, PlayerKind, TimeControl, Variant, CHESS960_POSITIONS};
 use dioxus::prelude::*;
+use rand::Rng;
 
 use crate::{
-    client::shared_states::{Analyze, BoardSize, GameId, Perspective},
+    client::{
+        helpers::with_event_log,
+        shared_states::{Analyze, BoardSize, BotEngine, GameId, Perspective, Settings},
+    },
     server::server_functions::setup_remote_game,
 };
 
@@ -16,18 +20,41 @@ pub(crate) fn BoardButtons(
     let board_size = **use_shared_state::<BoardSize>(cx)?.read();
     let perspective = use_shared_state::<Perspective>(cx)?;
     let game = use_shared_state::<Game>(cx)?;
+    let engine = use_shared_state::<BotEngine>(cx)?;
     let game_id = use_shared_state::<GameId>(cx)?;
+    let settings = use_shared_state::<Settings>(cx)?;
 
     cx.render(rsx! {
         div { class: "board-buttons-container", style: "width: {board_size}px",
+            button { class: "button",
+                hidden: white_player.read().kind == PlayerKind::Remote
+                    || black_player.read().kind == PlayerKind::Remote,
+                onclick: |_| {
+                    let settings = settings.read();
+                    let chess960_index = settings
+                        .chess960
+                        .then(|| rand::thread_rng().gen_range(0..CHESS960_POSITIONS));
+                    *game.write() = new_game(settings.variant, chess960_index, settings.time_control.clone());
+                    // nothing the bot learned carries over to the new game
+                    if let Some(engine) = engine.write().as_mut() {
+                        engine.clear();
+                    }
+                    **analyze.write() = false;
+                },
+                "New Game"
+            }
             button { class: "button",
                 onclick: |_| {
                     to_owned![analyze, white_player, black_player, perspective, game, game_id];
+                    let (variant, chess960, time_control) = {
+                        let settings = settings.read();
+                        (settings.variant, settings.chess960, settings.time_control.clone())
+                    };
                     cx.spawn(async move {
-                        match setup_remote_game().await {
+                        match setup_remote_game(variant, chess960, time_control).await {
                             Ok(info) => {
                                 log::info!("Setting up remote game: {info:?}");
-                                game.write().reset();
+                                *game.write() = new_game(info.variant, info.chess960_index, info.time_control);
                                 **game_id.write() = Some(info.game_id);
                                 let player = match info.local_color {
                                     Color::White => black_player.to_owned(),
@@ -43,14 +70,39 @@ pub(crate) fn BoardButtons(
                 },
                 "Play Remote"
             }
+            button { class: "button",
+                hidden: white_player.read().kind == PlayerKind::Remote
+                    || black_player.read().kind == PlayerKind::Remote,
+                onclick: |_| {
+                    let settings = settings.read();
+                    let chess960_index = settings
+                        .chess960
+                        .then(|| rand::thread_rng().gen_range(0..CHESS960_POSITIONS));
+                    *game.write() = new_game(settings.variant, chess960_index, settings.time_control.clone());
+                    if let Some(engine) = engine.write().as_mut() {
+                        engine.clear();
+                    }
+                    // the bot takes the side at the top of the board
+                    let (local_player, bot_player) = match **perspective.read() {
+                        Color::White => (white_player, black_player),
+                        Color::Black => (black_player, white_player),
+                    };
+                    local_player.write().kind = PlayerKind::Local;
+                    bot_player.write().kind = PlayerKind::Bot;
+                    **analyze.write() = false;
+                },
+                "Play Bot"
+            }
             button { class: "button",
                 onclick: |_| perspective.with_mut(|perspective| **perspective = !**perspective),
                 "Flip Board"
             }
             button { class: "button",
-                hidden: !game.read().game_over()
-                    && (white_player.read().kind != PlayerKind::Local
-                        || black_player.read().kind != PlayerKind::Local),
+                // Stockfish only knows the standard rules
+                hidden: game.read().get_variant() != Variant::Standard
+                    || (!game.read().game_over()
+                        && (white_player.read().kind != PlayerKind::Local
+                            || black_player.read().kind != PlayerKind::Local)),
                 onclick: |_| analyze.with_mut(|analyze| **analyze = !**analyze),
                 if **analyze.read() { "Stop analyzing" } else { "Analyze" }
             }
@@ -71,6 +123,14 @@ pub(crate) fn BoardButtons(
     })
 }
 
+fn new_game(variant: Variant, chess960_index: Option<usize>, time_control: TimeControl) -> Game {
+    let game = match chess960_index.and_then(|index| Game::chess960(index).ok()) {
+        Some(game) => game,
+        None => Game::with_variant(variant),
+    };
+    with_event_log(game.with_time_control(time_control))
+}
+
 fn get_default_perspective(
     white_player: &UseLock<Player>,
     black_player: &UseLock<Player>,
@@ -0,0 +1,41 @@
+use chess::{Game, Variant};
+use dioxus::prelude::*;
+
+use crate::client::shared_states::{Book, Settings};
+
+#[component]
+pub(crate) fn BookMoves(cx: Scope) -> Element {
+    let book = use_shared_state::<Book>(cx)?.read();
+    let game = use_shared_state::<Game>(cx)?.read();
+    let notation = use_shared_state::<Settings>(cx)?.read().notation;
+    let Some(book) = book.as_ref() else {
+        return None;
+    };
+    if game.get_variant() != Variant::Standard {
+        return None;
+    }
+    let moves = book.get_moves(&game);
+    let total: u32 = moves.iter().map(|book_move| book_move.weight as u32).sum();
+    // each move with its share of the games the book plays from here
+    let rows = moves.into_iter().filter_map(|book_move| {
+        let san = game.get_san_str(&book_move.mv, notation)?;
+        Some((san, 100 * book_move.weight as u32 / total))
+    });
+
+    cx.render(rsx! {
+        p { "Book:" }
+        div { class: "book-moves-container",
+            for number in book.get_book_exit(&game) {
+                p { class: "text-gray-400", "Left the book on move {number}" }
+            }
+            table { class: "place-content-center",
+                for (san, share) in rows {
+                    tr {
+                        td { "{san}" }
+                        td { class: "text-right", "{share}%" }
+                    }
+                }
+            }
+        }
+    })
+}
@@ -1,7 +1,11 @@
-use chess::Color;
+use chess::{Color, Game, Wdl};
 use dioxus::prelude::*;
 
-use super::super::{helpers::sigmoid, shared_states::Perspective, stockfish::Eval};
+use super::super::{
+    helpers::sigmoid,
+    shared_states::{Perspective, Syzygy},
+    stockfish::Eval,
+};
 
 const EVAL_SENSITIVITY: f64 = 1.0 / 800.0;
 
@@ -9,10 +13,18 @@ const EVAL_SENSITIVITY: f64 = 1.0 / 800.0;
 pub(crate) fn EvalBar(cx: Scope) -> Element {
     let eval = *use_shared_state::<Eval>(cx)?.read();
     let perspective = **use_shared_state::<Perspective>(cx)?.read();
-    let winning_player = eval.get_winning_player();
-    let percent = match eval {
-        Eval::Centipawns(cp) => 100.0 * sigmoid(EVAL_SENSITIVITY * cp as f64),
-        Eval::Mate(mate) => 100.0 * (mate > 0) as u64 as f64,
+    let game = use_shared_state::<Game>(cx)?.read();
+    let syzygy = use_shared_state::<Syzygy>(cx)?.read();
+    // the tablebase knows the result for sure, so it replaces the evaluation
+    let (percent, winning_player, text) = match get_tablebase_result(&game, &syzygy) {
+        Some(result) => result,
+        None => {
+            let percent = match eval {
+                Eval::Centipawns(cp) => 100.0 * sigmoid(EVAL_SENSITIVITY * cp as f64),
+                Eval::Mate(mate) => 100.0 * (mate > 0) as u64 as f64,
+            };
+            (percent, eval.get_winning_player(), eval.to_string())
+        }
     };
     let direction = match perspective {
         Color::White => "top",
@@ -37,7 +49,43 @@ pub(crate) fn EvalBar(cx: Scope) -> Element {
                 color: {text_color};
                 justify-content: {justify};
             ",
-            "{eval}"
+            "{text}"
         }
     })
 }
+
+// How full the bar is for White, who is ahead, and the plies to the next
+// capture or pawn move, for positions in the tablebase
+fn get_tablebase_result(game: &Game, syzygy: &Syzygy) -> Option<(f64, Color, String)> {
+    let tablebase = syzygy
+        .as_ref()
+        .filter(|tablebase| tablebase.can_probe(game))?;
+    let (wdl, dtz) = match (tablebase.probe_wdl(game), tablebase.probe_dtz(game)) {
+        (Ok(wdl), Ok(dtz)) => (wdl, dtz),
+        (Err(e), _) | (_, Err(e)) => {
+            log::warn!("Failed to probe the tablebase: {e}");
+            return None;
+        }
+    };
+    let wdl = match game.get_current_player() {
+        Color::White => wdl,
+        Color::Black => -wdl,
+    };
+    let percent = match wdl {
+        Wdl::Win => 100.0,
+        Wdl::CursedWin => 75.0,
+        Wdl::Draw => 50.0,
+        Wdl::BlessedLoss => 25.0,
+        Wdl::Loss => 0.0,
+    };
+    let winning_player = if wdl >= Wdl::Draw {
+        Color::White
+    } else {
+        Color::Black
+    };
+    let text = match wdl {
+        Wdl::Draw => "0.0".to_string(),
+        _ => format!("DTZ {}", dtz.abs()),
+    };
+    Some((percent, winning_player, text))
+}
@@ -1,6 +1,6 @@
 use dioxus::prelude::*;
 
-use super::{RoundList, Timer};
+use super::{BookMoves, RoundList, Timer};
 
 #[component]
 pub(crate) fn InfoBar(cx: Scope, is_local_game: bool) -> Element {
@@ -10,6 +10,7 @@ pub(crate) fn InfoBar(cx: Scope, is_local_game: bool) -> Element {
                 Timer {}
             }
             RoundList {}
+            BookMoves {}
         }
     })
 }
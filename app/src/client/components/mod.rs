@@ -4,6 +4,7 @@ mod arrow;
 mod board;
 mod board_buttons;
 mod board_square;
+mod book_moves;
 mod eval_bar;
 mod info_bar;
 pub(super) mod nav_bar;
@@ -18,6 +19,7 @@ pub(super) use arrow::Arrow;
 pub(super) use board::{get_center, Board};
 pub(super) use board_buttons::BoardButtons;
 pub(super) use board_square::BoardSquare;
+pub(super) use book_moves::BookMoves;
 pub(super) use eval_bar::EvalBar;
 pub(super) use info_bar::InfoBar;
 pub(super) use piece::Piece;
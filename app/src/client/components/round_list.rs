@@ -1,16 +1,19 @@
 use chess::Game;
 use dioxus::prelude::*;
 
+use crate::client::shared_states::Settings;
+
 #[component]
 pub(crate) fn RoundList(cx: Scope) -> Element {
     let game = use_shared_state::<Game>(cx)?.read();
+    let notation = use_shared_state::<Settings>(cx)?.read().notation;
     let current_round = game.get_current_round();
 
     cx.render(rsx! {
         p { "Moves:" }
         div { class: "rounds-container",
             table { class: "place-content-center",
-                for (i, info) in game.get_rounds_info().into_iter().enumerate() {
+                for (i, info) in game.get_rounds_info(notation).into_iter().enumerate() {
                     tr {
                         class: if i + 1 == current_round {
                             "mb-4 bg-gray-600/75"
@@ -18,7 +21,7 @@ pub(crate) fn RoundList(cx: Scope) -> Element {
                             "mb-4"
                         },
                         td {
-                            "{i + 1}."
+                            "{info.number}."
                         }
                         td {
                             "{info.white_string}"
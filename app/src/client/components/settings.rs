@@ -1,3 +1,6 @@
+use std::time::Duration;
+
+use chess::{Notation, TimeBonus, TimeControl, TimePeriod, Variant};
 use dioxus::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -10,6 +13,32 @@ const APP_NAME: &str = "oxide-chess";
 #[cfg(feature = "desktop")]
 const CONFIG_NAME: &str = "themes";
 
+fn minutes(minutes: u64) -> Duration {
+    Duration::from_secs(minutes * 60)
+}
+
+fn get_time_controls() -> Vec<TimeControl> {
+    let seconds = Duration::from_secs;
+    vec![
+        TimeControl::fischer(minutes(3), seconds(2)),
+        TimeControl::fischer(minutes(5), seconds(3)),
+        TimeControl::fischer(minutes(10), seconds(5)),
+        TimeControl::bronstein(minutes(15), seconds(10)),
+        TimeControl::simple_delay(minutes(30), seconds(5)),
+        TimeControl::from(minutes(60)),
+        // the FIDE classical control
+        TimeControl::from(TimePeriod::moves(
+            40,
+            minutes(90),
+            TimeBonus::Fischer(seconds(30)),
+        ))
+        .then(TimePeriod::sudden_death(
+            minutes(30),
+            TimeBonus::Fischer(seconds(30)),
+        )),
+    ]
+}
+
 #[component]
 pub(crate) fn Settings(cx: Scope) -> Element {
     let settings = use_shared_state::<shared_states::Settings>(cx)?;
@@ -62,6 +91,81 @@ pub(crate) fn Settings(cx: Scope) -> Element {
                         }
                     }
                 }
+                tr {
+                    td { "Move notation: " }
+                    td {
+                        select {
+                            class: "select",
+                            onchange: |event| {
+                                settings.write().notation = match event.value().as_str() {
+                                    "letter" => Notation::Letter,
+                                    _ => Notation::Figurine,
+                                };
+                            },
+                            option {
+                                value: "figurine",
+                                selected: settings.read().notation == Notation::Figurine,
+                                "Figurine (♞f3)"
+                            }
+                            option {
+                                value: "letter",
+                                selected: settings.read().notation == Notation::Letter,
+                                "Letter (Nf3)"
+                            }
+                        }
+                    }
+                }
+                tr {
+                    td { "Time control: " }
+                    td {
+                        select {
+                            class: "select",
+                            onchange: |event| {
+                                if let Some(time_control) = event
+                                    .value()
+                                    .parse()
+                                    .ok()
+                                    .and_then(|i: usize| get_time_controls().into_iter().nth(i))
+                                {
+                                    settings.write().time_control = time_control;
+                                }
+                            },
+                            for (i, time_control) in get_time_controls().into_iter().enumerate() {
+                                option {
+                                    value: "{i}",
+                                    selected: settings.read().time_control == time_control,
+                                    "{time_control}"
+                                }
+                            }
+                        }
+                    }
+                }
+                tr {
+                    td { "New games: " }
+                    td {
+                        select {
+                            class: "select",
+                            onchange: |event| {
+                                let mut settings = settings.write();
+                                settings.chess960 = event.value() == "chess960";
+                                settings.variant = event.value().parse().unwrap_or_default();
+                            },
+                            option {
+                                value: "chess960",
+                                selected: settings.read().chess960,
+                                "Chess960"
+                            }
+                            for variant in Variant::ALL {
+                                option {
+                                    value: "{variant}",
+                                    selected: !settings.read().chess960
+                                        && settings.read().variant == variant,
+                                    "{variant}"
+                                }
+                            }
+                        }
+                    }
+                }
             }
         }
     })
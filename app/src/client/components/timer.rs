@@ -11,10 +11,23 @@ pub(crate) fn Timer(cx: Scope) -> Element {
     let white_time = use_state(cx, || display_time(initial_time));
     let black_time = use_state(cx, || display_time(initial_time));
     let player = game.read().get_real_player();
+    let time_control = {
+        let game = game.read();
+        let (white, black) = (
+            game.get_time_control(Color::White),
+            game.get_time_control(Color::Black),
+        );
+        if white == black {
+            white.to_string()
+        } else {
+            format!("White {white}, Black {black}")
+        }
+    };
 
     use_timer_future(cx, white_time, black_time);
 
     cx.render(rsx! {
+        p { "Time control: {time_control}" }
         p { background_color: if player == Color::White { "rgba(127, 127, 127, 0.35)" } else { "" }, "White time: {white_time}" }
         p { background_color: if player == Color::Black { "rgba(127, 127, 127, 0.35)" } else { "" }, "Black time: {black_time}" }
     })
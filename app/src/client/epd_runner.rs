@@ -0,0 +1,71 @@
+use std::time::Instant;
+
+use anyhow::Result;
+use async_std::{io::BufReader, prelude::*};
+use chess::{
+    read_epd, run_perft_suite, run_suite, Engine, EpdRecord, Move, SearchLimits, SuiteReport,
+};
+
+use super::stockfish::{
+    core::init_stockfish,
+    interface::{run_stockfish, send_command},
+};
+use crate::common::args::{EpdOptions, EpdRunner};
+
+// Runs a test suite from the command line and logs the report
+pub(super) async fn run_epd_suite(options: EpdOptions) {
+    let records = match std::fs::read_to_string(&options.path) {
+        Ok(epd) => match read_epd(&epd) {
+            Ok(records) => records,
+            Err(e) => return log::error!("Failed to read {}: {e}", options.path.display()),
+        },
+        Err(e) => return log::error!("Failed to open {}: {e}", options.path.display()),
+    };
+    log::info!(
+        "Running {} positions of {}",
+        records.len(),
+        options.path.display()
+    );
+    let report = match options.runner {
+        EpdRunner::Stockfish => match run_with_stockfish(&records, options.depth).await {
+            Ok(report) => report,
+            Err(e) => return log::error!("Failed to run the suite with Stockfish: {e:?}"),
+        },
+        EpdRunner::Native => {
+            let mut engine = Engine::new(SearchLimits::depth(options.depth));
+            run_suite(&records, |game| Some(engine.search(game)?.best_move))
+        }
+        EpdRunner::Perft => run_perft_suite(&records),
+    };
+    for failure in report.get_failures() {
+        match failure.best_move {
+            Some(mv) => log::info!("Failed {}: played {mv}", failure.name),
+            None => log::info!("Failed {}", failure.name),
+        }
+    }
+    log::info!("{report}");
+}
+
+async fn run_with_stockfish(records: &[EpdRecord], depth: u8) -> Result<SuiteReport> {
+    let mut process = run_stockfish().await?;
+    init_stockfish(&mut process).await;
+    let stdout = process.stdout.take().unwrap();
+    let mut lines = BufReader::new(stdout).lines();
+    let mut report = SuiteReport::default();
+    for record in records {
+        send_command(&mut process, "ucinewgame").await;
+        send_command(&mut process, &format!("position fen {}", record.get_fen())).await;
+        let start = Instant::now();
+        send_command(&mut process, &format!("go depth {depth}")).await;
+        let mut best_move = None;
+        while let Some(line) = lines.next().await {
+            if let Some(bestmove) = line?.strip_prefix("bestmove ") {
+                best_move = bestmove.split_whitespace().next().and_then(Move::from_lan);
+                break;
+            }
+        }
+        report.add_move(record, best_move, start.elapsed());
+    }
+    send_command(&mut process, "quit").await;
+    Ok(report)
+}
@@ -50,7 +50,7 @@ async fn write_to_socket(rx: &Receiver<Move>, mut socket: WriteStream) {
 fn handle_message(message: Result<Message>, game: &UseSharedState<Game>) -> anyhow::Result<()> {
     let mv = serde_json::from_str::<Move>(&message?.into_text()?)?;
     log::info!("Got move {mv}");
-    game.write().move_piece(mv.from, mv.to)?;
+    game.write().make_move(mv)?;
     Ok(())
 }
 
@@ -1,3 +1,5 @@
+use chess::{Game, GameEvent};
+
 pub(crate) fn inv_sigmoid(x: f64) -> f64 {
     (x / (1.0 - x)).ln()
 }
@@ -5,3 +7,15 @@ pub(crate) fn inv_sigmoid(x: f64) -> f64 {
 pub(crate) fn sigmoid(x: f64) -> f64 {
     1.0 / (1.0 + (-x).exp())
 }
+
+// Logs the moves and status changes of a game as they happen
+pub(crate) fn with_event_log(mut game: Game) -> Game {
+    game.subscribe(|event| match event {
+        GameEvent::MoveMade { piece, mv, .. } => log::info!("{piece} : {mv}"),
+        GameEvent::StatusChanged { from, to } => {
+            log::info!("GameStatus changing from {from:?} to {to:?}")
+        }
+        _ => log::debug!("{event:?}"),
+    });
+    game
+}
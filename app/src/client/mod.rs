@@ -1,5 +1,7 @@
 mod arrows;
 mod components;
+#[cfg(feature = "desktop")]
+mod epd_runner;
 mod game_socket;
 mod helpers;
 mod mouse_click;
@@ -16,6 +18,9 @@ pub fn launch() {
         use dioxus_desktop as desktop;
         use dioxus_fullstack::prelude::server_fn;
 
+        if let Some(options) = crate::common::args::get_epd_options() {
+            return futures::executor::block_on(epd_runner::run_epd_suite(options));
+        }
         server_fn::set_server_url("https://oxide-chess.fly.dev/");
         log::info!("configuring desktop..");
         desktop::launch_cfg(
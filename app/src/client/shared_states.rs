@@ -1,23 +1,37 @@
 use auto_deref::AutoDeref;
-use chess::Color;
+use chess::{Color, Engine, Notation, OpeningBook, Tablebase, TimeControl, Variant};
 
 use crate::{client::components::settings, common::theme::ThemeType};
 
 #[derive(AutoDeref)]
 pub(super) struct Analyze(pub(super) bool);
 
+#[derive(AutoDeref)]
+pub(super) struct Book(pub(super) Option<OpeningBook>);
+
 #[derive(AutoDeref)]
 pub(super) struct BoardSize(pub(super) u32);
 
+/// The built-in engine the bot plays with, which is taken out while it searches.
+#[derive(AutoDeref)]
+pub(super) struct BotEngine(pub(super) Option<Engine>);
+
 #[derive(AutoDeref)]
 pub(super) struct GameId(pub(super) Option<u32>);
 
 #[derive(AutoDeref)]
 pub(super) struct Perspective(pub(super) Color);
 
+#[derive(AutoDeref)]
+pub(super) struct Syzygy(pub(super) Option<Tablebase>);
+
 pub(super) struct Settings {
     pub(super) board_theme: String,
     pub(super) piece_theme: String,
+    pub(super) notation: Notation,
+    pub(super) variant: Variant,
+    pub(super) chess960: bool,
+    pub(super) time_control: TimeControl,
 }
 
 impl Settings {
@@ -25,6 +39,10 @@ impl Settings {
         Self {
             board_theme: settings::load_theme(ThemeType::Board),
             piece_theme: settings::load_theme(ThemeType::Piece),
+            notation: Notation::Figurine,
+            variant: Variant::Standard,
+            chess960: false,
+            time_control: TimeControl::default(),
         }
     }
 }
@@ -81,7 +81,8 @@ pub async fn toggle_stockfish(
             Ok(mut process) => {
                 init_stockfish(&mut process).await;
                 arrows.set(Arrows::with_size(MOVES));
-                update_position(&game.read().get_fen_str(), &mut process).await;
+                let (fen, chess960) = (game.read().get_fen_str(), game.read().is_chess960());
+                update_position(&fen, chess960, &mut process).await;
                 go(&mut process).await;
                 stockfish_process.set(Some(process)).await;
                 update_analysis_arrows(&arrows, &stockfish_process, &eval_hook, &game).await;
@@ -98,12 +99,13 @@ pub async fn toggle_stockfish(
 
 pub async fn on_game_changed(
     fen: String,
+    chess960: bool,
     process: UseAsyncLock<Option<Process>>,
     arrows: UseLock<Arrows>,
 ) {
     if let Some(process) = process.write().await.as_mut() {
         stop(process).await;
-        update_position(&fen, process).await;
+        update_position(&fen, chess960, process).await;
         wait_until_ready(process).await;
         arrows.set(Arrows::with_size(MOVES));
         go(process).await;
@@ -168,7 +170,9 @@ async fn stop_stockfish(process: &mut Process) {
     send_command(process, "quit").await;
 }
 
-async fn update_position(fen_str: &str, process: &mut Process) {
+async fn update_position(fen_str: &str, chess960: bool, process: &mut Process) {
     log::debug!("Setting stockfish position: {fen_str:?}");
+    // Chess960 castling moves are sent as the king taking its own rook
+    send_command(process, &format!("setoption name UCI_Chess960 value {chess960}")).await;
     send_command(process, &format!("position fen {fen_str}")).await;
 }
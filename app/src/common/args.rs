@@ -1,12 +1,55 @@
-use clap::Parser;
+use std::path::PathBuf;
+
+use clap::{Parser, ValueEnum};
+
+/// What answers the positions of a test suite.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum EpdRunner {
+    /// Stockfish's best move
+    Stockfish,
+    /// The built-in engine's best move
+    Native,
+    /// The move generator's perft counts
+    Perft,
+}
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     #[arg(short, long, default_value = "INFO")]
     log_level: log::LevelFilter,
+    /// A directory of Syzygy tablebase files for perfect endgame play
+    #[arg(long)]
+    syzygy_path: Option<PathBuf>,
+    /// Runs the EPD test suite in this file instead of opening the window
+    #[arg(long)]
+    epd: Option<PathBuf>,
+    #[arg(long, value_enum, default_value = "stockfish")]
+    epd_runner: EpdRunner,
+    /// How deep the engine searches each position of the suite
+    #[arg(long, default_value = "12")]
+    epd_depth: u8,
+}
+
+pub struct EpdOptions {
+    pub path: PathBuf,
+    pub runner: EpdRunner,
+    pub depth: u8,
 }
 
 pub fn get_log_level() -> log::LevelFilter {
     Args::parse().log_level
 }
+
+pub fn get_syzygy_path() -> Option<PathBuf> {
+    Args::parse().syzygy_path
+}
+
+pub fn get_epd_options() -> Option<EpdOptions> {
+    let args = Args::parse();
+    Some(EpdOptions {
+        path: args.epd?,
+        runner: args.epd_runner,
+        depth: args.epd_depth,
+    })
+}
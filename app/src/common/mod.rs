@@ -1,2 +1,4 @@
 pub mod args;
+pub(crate) mod opening_book;
+pub(crate) mod tablebase;
 pub(crate) mod theme;
@@ -0,0 +1,7 @@
+// A Polyglot book for standard chess, looked for next to the images
+pub const BOOK_PATH: &str = "books/book.bin";
+
+#[cfg(not(feature = "web"))]
+pub async fn get_opening_book() -> std::io::Result<Vec<u8>> {
+    std::fs::read(BOOK_PATH)
+}
@@ -0,0 +1,26 @@
+use chess::Tablebase;
+
+// The tables are read straight from the disk, which the browser can't do
+#[cfg(feature = "web")]
+pub fn open_tablebase() -> Option<Tablebase> {
+    None
+}
+
+#[cfg(not(feature = "web"))]
+pub fn open_tablebase() -> Option<Tablebase> {
+    let path = super::args::get_syzygy_path()?;
+    match Tablebase::open(&path) {
+        Ok(tablebase) => {
+            log::info!(
+                "Probing endgames of up to {} pieces in {}",
+                tablebase.get_max_pieces(),
+                path.display()
+            );
+            Some(tablebase)
+        }
+        Err(e) => {
+            log::error!("Failed to open the tablebase in {}: {e}", path.display());
+            None
+        }
+    }
+}
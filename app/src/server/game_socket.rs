@@ -36,10 +36,10 @@ async fn close_socket(game_id: u32, send: WebSocketSender) {
     if let Err(err) = send.lock().await.close().await {
         log::error!("Error closing socket: {err:?}");
     }
-    let mut pending_game = PENDING_GAME.lock().await;
-    if *pending_game == Some(game_id) {
-        *pending_game = None;
-    }
+    PENDING_GAMES
+        .lock()
+        .await
+        .retain(|pending_game| pending_game.game_id != game_id);
     GAMES.write().await.remove(&game_id);
 }
 
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, sync::Arc};
 
 use axum::extract::ws::{Message, WebSocket};
+use chess::{TimeControl, Variant};
 use futures::stream::{SplitSink, SplitStream};
 use once_cell::sync::Lazy;
 use tokio::sync::{Mutex, RwLock};
@@ -12,4 +13,15 @@ pub type PlayerConnections = Arc<Mutex<Vec<(WebSocketSender, WebSocketReceiver)>
 pub static GAMES: Lazy<Arc<RwLock<HashMap<u32, PlayerConnections>>>> =
     Lazy::new(|| Arc::new(RwLock::new(HashMap::new())));
 
-pub static PENDING_GAME: Lazy<Arc<Mutex<Option<u32>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+
+/// A game waiting for its second player, with its variant, its clock and the
+/// Chess960 start position if it has one.
+pub struct PendingGame {
+    pub game_id: u32,
+    pub variant: Variant,
+    pub chess960_index: Option<usize>,
+    pub time_control: TimeControl,
+}
+
+pub static PENDING_GAMES: Lazy<Arc<Mutex<Vec<PendingGame>>>> =
+    Lazy::new(|| Arc::new(Mutex::new(Vec::new())));
@@ -0,0 +1,8 @@
+use dioxus_fullstack::prelude::*;
+
+use crate::common::opening_book;
+
+#[server(GetOpeningBook, "/api")]
+pub async fn get_opening_book() -> Result<Vec<u8>, ServerFnError> {
+    Ok(opening_book::get_opening_book().await?)
+}
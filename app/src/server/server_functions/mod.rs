@@ -1,9 +1,12 @@
 pub(crate) mod accounts;
 #[cfg(feature = "ssr")]
 pub mod games;
+mod get_opening_book;
 mod get_themes;
 mod setup_remote_game;
 
+#[cfg(feature = "web")]
+pub(crate) use get_opening_book::get_opening_book;
 #[cfg(feature = "web")]
 pub(crate) use get_themes::get_themes;
 #[cfg(not(feature = "ssr"))]
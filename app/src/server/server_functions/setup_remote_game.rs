@@ -1,4 +1,4 @@
-use chess::Color;
+use chess::{Color, TimeControl, Variant};
 use dioxus_fullstack::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -6,21 +6,40 @@ use serde::{Deserialize, Serialize};
 pub struct RemoteGameInfo {
     pub game_id: u32,
     pub local_color: Color,
+    pub variant: Variant,
+    pub chess960_index: Option<usize>,
+    pub time_control: TimeControl,
 }
 
 #[server(SetupRemoteGame, "/api")]
-pub async fn setup_remote_game() -> Result<RemoteGameInfo, ServerFnError> {
+pub async fn setup_remote_game(
+    variant: Variant,
+    chess960: bool,
+    time_control: TimeControl,
+) -> Result<RemoteGameInfo, ServerFnError> {
     use rand::distributions::{Distribution, Uniform};
 
-    use super::games::{PlayerConnections, GAMES, PENDING_GAME};
+    use super::games::{PendingGame, PlayerConnections, GAMES, PENDING_GAMES};
 
     let mut games = GAMES.write().await;
-    let mut pending_game = PENDING_GAME.lock().await;
-    if let Some(game_id) = *pending_game {
-        *pending_game = None;
+    let mut pending_games = PENDING_GAMES.lock().await;
+    // pair up with someone waiting to play the same kind of game
+    if let Some(i) = pending_games.iter().position(|pending_game| {
+        pending_game.variant == variant
+            && pending_game.chess960_index.is_some() == chess960
+            && pending_game.time_control == time_control
+    }) {
+        let PendingGame {
+            game_id,
+            chess960_index,
+            ..
+        } = pending_games.remove(i);
         return Ok(RemoteGameInfo {
             game_id,
             local_color: Color::Black,
+            variant,
+            chess960_index,
+            time_control,
         });
     }
 
@@ -30,12 +49,22 @@ pub async fn setup_remote_game() -> Result<RemoteGameInfo, ServerFnError> {
     while games.contains_key(&game_id) {
         game_id = range.sample(&mut rng);
     }
+    let chess960_index =
+        chess960.then(|| Uniform::from(0..chess::CHESS960_POSITIONS).sample(&mut rng));
 
     games.insert(game_id, PlayerConnections::default());
-    *pending_game = Some(game_id);
+    pending_games.push(PendingGame {
+        game_id,
+        variant,
+        chess960_index,
+        time_control: time_control.clone(),
+    });
 
     Ok(RemoteGameInfo {
         game_id,
         local_color: Color::White,
+        variant,
+        chess960_index,
+        time_control,
     })
 }
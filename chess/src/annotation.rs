@@ -0,0 +1,210 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use web_time::Duration;
+
+// The move assessments written after a move, in the order of their NAGs
+const MOVE_ASSESSMENTS: [&str; 6] = ["!", "?", "!!", "??", "!?", "?!"];
+
+/// The NAG of a move assessment like "!?".
+pub(super) fn get_assessment_nag(symbol: &str) -> Option<u8> {
+    let index = MOVE_ASSESSMENTS.iter().position(|&other| other == symbol)?;
+    Some(index as u8 + 1)
+}
+
+/// An engine evaluation stored with a move, as in a `[%eval]` command.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EngineEval {
+    /// In centipawns, from White's point of view.
+    Centipawns(i32),
+    /// The number of moves to mate, negative when Black mates.
+    Mate(i32),
+}
+
+impl EngineEval {
+    fn from_pgn(value: &str) -> Option<Self> {
+        match value.strip_prefix('#') {
+            Some(mate) => Some(Self::Mate(mate.parse().ok()?)),
+            None => {
+                let pawns: f64 = value.parse().ok()?;
+                pawns
+                    .is_finite()
+                    .then(|| Self::Centipawns((pawns * 100.0).round() as i32))
+            }
+        }
+    }
+}
+
+/// Writes the evaluation as in PGN, in pawns or as "#" and the moves to mate.
+impl fmt::Display for EngineEval {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Centipawns(cp) => write!(f, "{:.2}", *cp as f64 / 100.0),
+            Self::Mate(mate) => write!(f, "#{mate}"),
+        }
+    }
+}
+
+// Clock times are written as h:mm:ss, with the milliseconds when there are any
+fn write_clock(time: Duration) -> String {
+    let seconds = time.as_secs();
+    let mut clock = format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    );
+    if time.subsec_millis() != 0 {
+        clock.push_str(format!(".{:03}", time.subsec_millis()).trim_end_matches('0'));
+    }
+    clock
+}
+
+fn read_clock(value: &str) -> Option<Duration> {
+    let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let mut seconds = 0;
+    for part in whole.split(':') {
+        seconds = seconds * 60 + part.parse::<u64>().ok()?;
+    }
+    if fraction.len() > 3 || !fraction.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let millis = format!("{fraction:0<3}").parse().ok()?;
+    Some(Duration::from_secs(seconds) + Duration::from_millis(millis))
+}
+
+/// What a player or coach wrote about a move: comments, numeric annotation
+/// glyphs, and the `[%clk]` and `[%eval]` commands of PGN comments.
+#[derive(Clone, Default, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<String>,
+    /// Numeric annotation glyphs, such as 1 for "!" and 6 for "?!".
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub nags: Vec<u8>,
+    /// The mover's remaining time after the move.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub clock: Option<Duration>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eval: Option<EngineEval>,
+}
+
+impl Annotation {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Adds a PGN comment, taking out the commands it knows. Other commands,
+    /// like arrows, stay in the comment.
+    pub(super) fn add_pgn_comment(&mut self, comment: &str) {
+        let mut text = String::new();
+        let mut rest = comment;
+        while let Some(start) = rest.find("[%") {
+            let Some(length) = rest[start..].find(']') else {
+                break;
+            };
+            let command = &rest[start..start + length + 1];
+            let body = command[2..command.len() - 1].trim();
+            let (name, value) = body.split_once(char::is_whitespace).unwrap_or((body, ""));
+            let value = value.trim();
+            text.push_str(&rest[..start]);
+            match (name, read_clock(value), EngineEval::from_pgn(value)) {
+                ("clk", Some(clock), _) => self.clock = Some(clock),
+                ("eval", _, Some(eval)) => self.eval = Some(eval),
+                _ => text.push_str(command),
+            }
+            rest = &rest[start + length + 1..];
+        }
+        text.push_str(rest);
+        // line breaks of the file aren't part of the comment
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if !text.is_empty() {
+            self.comments.push(text);
+        }
+    }
+
+    /// Whether the annotation writes any comments after the move.
+    pub(super) fn has_pgn_comments(&self) -> bool {
+        !self.comments.is_empty() || self.clock.is_some() || self.eval.is_some()
+    }
+
+    /// The tokens written after the move: its NAGs, then a comment with the
+    /// commands, then the other comments.
+    pub(super) fn get_pgn_tokens(&self) -> Vec<String> {
+        let mut commands = vec![];
+        if let Some(eval) = self.eval {
+            commands.push(format!("[%eval {eval}]"));
+        }
+        if let Some(clock) = self.clock {
+            commands.push(format!("[%clk {}]", write_clock(clock)));
+        }
+        let comments = (!commands.is_empty())
+            .then(|| commands.join(" "))
+            .into_iter()
+            .chain(self.comments.iter().cloned())
+            .filter(|comment| !comment.trim().is_empty());
+
+        let mut tokens: Vec<String> = self.nags.iter().map(|nag| format!("${nag}")).collect();
+        for comment in comments {
+            // long comments are split into words so the line length holds
+            let start = tokens.len();
+            tokens.extend(comment.split_whitespace().map(String::from));
+            tokens[start].insert(0, '{');
+            tokens.last_mut().unwrap().push('}');
+        }
+        tokens
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clock() {
+        let time = Duration::from_secs(3725) + Duration::from_millis(500);
+        assert_eq!(write_clock(time), "1:02:05.5");
+        assert_eq!(read_clock("1:02:05.5"), Some(time));
+        assert_eq!(write_clock(Duration::from_secs(59)), "0:00:59");
+        assert_eq!(read_clock("0:59"), Some(Duration::from_secs(59)));
+        assert_eq!(read_clock("0:0a:59"), None);
+        assert_eq!(read_clock("0:00:59.1234"), None);
+    }
+
+    #[test]
+    fn test_add_pgn_comment() {
+        let mut annotation = Annotation::default();
+        annotation.add_pgn_comment("[%eval -0.35] [%clk 0:04:58]");
+        annotation.add_pgn_comment("A common\nmistake [%csl Ge4] here [%eval #-3]");
+        annotation.add_pgn_comment("[%clk never]");
+        assert_eq!(
+            annotation,
+            Annotation {
+                comments: vec![
+                    "A common mistake [%csl Ge4] here".into(),
+                    "[%clk never]".into()
+                ],
+                nags: vec![],
+                clock: Some(Duration::from_secs(298)),
+                eval: Some(EngineEval::Mate(-3)),
+            }
+        );
+    }
+
+    #[test]
+    fn test_get_pgn_tokens() {
+        let annotation = Annotation {
+            comments: vec!["Only move".into()],
+            nags: vec![get_assessment_nag("!!").unwrap()],
+            clock: Some(Duration::from_secs(90)),
+            eval: Some(EngineEval::Centipawns(-5)),
+        };
+        assert_eq!(
+            annotation.get_pgn_tokens().join(" "),
+            "$3 {[%eval -0.05] [%clk 0:01:30]} {Only move}"
+        );
+        assert!(Annotation::default().get_pgn_tokens().is_empty());
+        assert_eq!(get_assessment_nag("?!"), Some(6));
+        assert_eq!(get_assessment_nag("!!!"), None);
+    }
+}
@@ -0,0 +1,301 @@
+use std::ops::{BitAnd, BitAndAssign, BitOr, BitOrAssign, BitXor, Not};
+
+use crate::{color::Color, position::Position};
+
+const KNIGHT_OFFSETS: [(i8, i8); 8] = [
+    (1, 2),
+    (1, -2),
+    (-1, 2),
+    (-1, -2),
+    (2, 1),
+    (2, -1),
+    (-2, 1),
+    (-2, -1),
+];
+const KING_OFFSETS: [(i8, i8); 8] = [
+    (1, 0),
+    (-1, 0),
+    (0, 1),
+    (0, -1),
+    (1, 1),
+    (1, -1),
+    (-1, 1),
+    (-1, -1),
+];
+const WHITE_PAWN_OFFSETS: [(i8, i8); 2] = [(1, 1), (-1, 1)];
+const BLACK_PAWN_OFFSETS: [(i8, i8); 2] = [(1, -1), (-1, -1)];
+
+// Rays whose squares have increasing indices come first, so the nearest blocker
+// is the lowest set bit for them and the highest set bit for the others.
+const DIRECTIONS: [(i8, i8); 8] = [
+    (0, 1),
+    (1, 0),
+    (1, 1),
+    (-1, 1),
+    (0, -1),
+    (-1, 0),
+    (-1, -1),
+    (1, -1),
+];
+const ROOK_DIRECTIONS: [usize; 4] = [0, 1, 4, 5];
+const BISHOP_DIRECTIONS: [usize; 4] = [2, 3, 6, 7];
+
+static KNIGHT_ATTACKS: [Bitboard; 64] = step_attacks(&KNIGHT_OFFSETS);
+static KING_ATTACKS: [Bitboard; 64] = step_attacks(&KING_OFFSETS);
+static PAWN_ATTACKS: [[Bitboard; 64]; 2] = [
+    step_attacks(&WHITE_PAWN_OFFSETS),
+    step_attacks(&BLACK_PAWN_OFFSETS),
+];
+static RAYS: [[Bitboard; 64]; 8] = rays();
+
+/// A set of squares with one bit per square, a1 being bit 0 and h8 bit 63.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Default, Debug)]
+pub(super) struct Bitboard(pub(super) u64);
+
+impl Bitboard {
+    pub(super) const DARK_SQUARES: Self = Self(0xaa55_aa55_aa55_aa55);
+    pub(super) const EMPTY: Self = Self(0);
+    pub(super) const FULL: Self = Self(!0);
+
+    pub(super) const fn from_square(square: usize) -> Self {
+        Self(1 << square)
+    }
+
+    pub(super) fn from_position(position: &Position) -> Self {
+        Self::from_square(position.get_square())
+    }
+
+    pub(super) fn contains(self, square: usize) -> bool {
+        self.0 & (1 << square) != 0
+    }
+
+    pub(super) fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    pub(super) fn len(self) -> u32 {
+        self.0.count_ones()
+    }
+
+    pub(super) fn has_many(self) -> bool {
+        self.0 & self.0.wrapping_sub(1) != 0
+    }
+
+    pub(super) fn first(self) -> Option<usize> {
+        (!self.is_empty()).then(|| self.0.trailing_zeros() as usize)
+    }
+
+    fn last(self) -> Option<usize> {
+        (!self.is_empty()).then(|| 63 - self.0.leading_zeros() as usize)
+    }
+}
+
+impl Iterator for Bitboard {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let square = self.first()?;
+        self.0 &= self.0 - 1;
+        Some(square)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let count = self.len() as usize;
+        (count, Some(count))
+    }
+}
+
+impl BitAnd for Bitboard {
+    type Output = Self;
+
+    fn bitand(self, rhs: Self) -> Self::Output {
+        Self(self.0 & rhs.0)
+    }
+}
+
+impl BitAndAssign for Bitboard {
+    fn bitand_assign(&mut self, rhs: Self) {
+        self.0 &= rhs.0
+    }
+}
+
+impl BitOr for Bitboard {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self::Output {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Bitboard {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0
+    }
+}
+
+impl BitXor for Bitboard {
+    type Output = Self;
+
+    fn bitxor(self, rhs: Self) -> Self::Output {
+        Self(self.0 ^ rhs.0)
+    }
+}
+
+impl Not for Bitboard {
+    type Output = Self;
+
+    fn not(self) -> Self::Output {
+        Self(!self.0)
+    }
+}
+
+const fn offset_square(square: usize, (dx, dy): (i8, i8)) -> Option<usize> {
+    let x = (square % 8) as i8 + dx;
+    let y = (square / 8) as i8 + dy;
+    if x >= 0 && x < 8 && y >= 0 && y < 8 {
+        Some((y * 8 + x) as usize)
+    } else {
+        None
+    }
+}
+
+const fn step_attacks(offsets: &[(i8, i8)]) -> [Bitboard; 64] {
+    let mut attacks = [Bitboard::EMPTY; 64];
+    let mut square = 0;
+    while square < 64 {
+        let mut i = 0;
+        while i < offsets.len() {
+            if let Some(to) = offset_square(square, offsets[i]) {
+                attacks[square].0 |= 1 << to;
+            }
+            i += 1;
+        }
+        square += 1;
+    }
+    attacks
+}
+
+const fn rays() -> [[Bitboard; 64]; 8] {
+    let mut rays = [[Bitboard::EMPTY; 64]; 8];
+    let mut direction = 0;
+    while direction < 8 {
+        let mut square = 0;
+        while square < 64 {
+            let mut current = offset_square(square, DIRECTIONS[direction]);
+            while let Some(to) = current {
+                rays[direction][square].0 |= 1 << to;
+                current = offset_square(to, DIRECTIONS[direction]);
+            }
+            square += 1;
+        }
+        direction += 1;
+    }
+    rays
+}
+
+pub(super) fn knight_attacks(square: usize) -> Bitboard {
+    KNIGHT_ATTACKS[square]
+}
+
+pub(super) fn king_attacks(square: usize) -> Bitboard {
+    KING_ATTACKS[square]
+}
+
+pub(super) fn pawn_attacks(player: Color, square: usize) -> Bitboard {
+    PAWN_ATTACKS[player as usize][square]
+}
+
+fn ray_attacks(direction: usize, square: usize, occupied: Bitboard) -> Bitboard {
+    let ray = RAYS[direction][square];
+    let blocker = if direction < 4 {
+        (ray & occupied).first()
+    } else {
+        (ray & occupied).last()
+    };
+    match blocker {
+        Some(blocker) => ray ^ RAYS[direction][blocker],
+        None => ray,
+    }
+}
+
+fn slider_attacks(directions: [usize; 4], square: usize, occupied: Bitboard) -> Bitboard {
+    directions
+        .into_iter()
+        .fold(Bitboard::EMPTY, |attacks, direction| {
+            attacks | ray_attacks(direction, square, occupied)
+        })
+}
+
+pub(super) fn rook_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    slider_attacks(ROOK_DIRECTIONS, square, occupied)
+}
+
+pub(super) fn bishop_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    slider_attacks(BISHOP_DIRECTIONS, square, occupied)
+}
+
+/// The squares strictly between `from` and `to`, empty if they don't share a line.
+pub(super) fn between(from: usize, to: usize) -> Bitboard {
+    (0..8)
+        .find(|&direction| RAYS[direction][from].contains(to))
+        .map_or(Bitboard::EMPTY, |direction| {
+            RAYS[direction][from] & !RAYS[direction][to] & !Bitboard::from_square(to)
+        })
+}
+
+/// The whole line through `from` and `to`, empty if they don't share a line.
+pub(super) fn line(from: usize, to: usize) -> Bitboard {
+    (0..8)
+        .find(|&direction| RAYS[direction][from].contains(to))
+        .map_or(Bitboard::EMPTY, |direction| {
+            RAYS[direction][from] | RAYS[(direction + 4) % 8][from] | Bitboard::from_square(from)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn squares(names: &[&str]) -> Bitboard {
+        names.iter().fold(Bitboard::EMPTY, |bitboard, name| {
+            bitboard | Bitboard::from_position(&Position::parse(name).unwrap())
+        })
+    }
+
+    #[test]
+    fn test_step_attacks() {
+        let a1 = Position::new(0, 0).get_square();
+        assert_eq!(knight_attacks(a1), squares(&["b3", "c2"]));
+        assert_eq!(king_attacks(a1), squares(&["a2", "b1", "b2"]));
+        assert_eq!(knight_attacks(Position::new(4, 3).get_square()).len(), 8);
+        let e4 = Position::new(4, 3).get_square();
+        assert_eq!(pawn_attacks(Color::White, e4), squares(&["d5", "f5"]));
+        assert_eq!(pawn_attacks(Color::Black, e4), squares(&["d3", "f3"]));
+    }
+
+    #[test]
+    fn test_slider_attacks() {
+        let d4 = Position::new(3, 3).get_square();
+        let occupied = squares(&["d6", "b4", "f2", "a7"]);
+        assert_eq!(
+            rook_attacks(d4, occupied),
+            squares(&["d5", "d6", "c4", "b4", "e4", "f4", "g4", "h4", "d3", "d2", "d1"])
+        );
+        assert_eq!(
+            bishop_attacks(d4, occupied),
+            squares(&["c5", "b6", "a7", "e5", "f6", "g7", "h8", "c3", "b2", "a1", "e3", "f2"])
+        );
+    }
+
+    #[test]
+    fn test_between_and_line() {
+        let (a1, h8, c3) = (0, 63, Position::new(2, 2).get_square());
+        assert_eq!(
+            between(a1, h8),
+            squares(&["b2", "c3", "d4", "e5", "f6", "g7"])
+        );
+        assert_eq!(between(h8, c3), squares(&["d4", "e5", "f6", "g7"]));
+        assert_eq!(line(c3, h8), between(a1, h8) | squares(&["a1", "h8"]));
+        assert!(between(a1, Position::new(1, 2).get_square()).is_empty());
+    }
+}
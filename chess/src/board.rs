@@ -1,33 +1,107 @@
 use std::{hash::Hash, slice::Iter};
 
-use crate::{color::Color, piece::Piece, position::Position};
+use crate::{bitboard::Bitboard, color::Color, piece::Piece, position::Position, zobrist};
 
 const BOARD_SIZE: usize = 8;
+// Where the two knights go among the five squares left once the bishops and queen are placed
+const CHESS960_KNIGHT_SQUARES: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+/// The number of Chess960 start positions.
+pub const CHESS960_POSITIONS: usize = 960;
 
 pub(super) type Square = Option<Piece>;
 
+/// The pieces on the board, kept both as a square array and as bitboards
+/// for each piece kind and color.
 #[derive(Clone, Copy, PartialEq, Eq, Hash)]
-pub(super) struct Board([[Square; BOARD_SIZE]; BOARD_SIZE]);
+pub(super) struct Board {
+    squares: [[Square; BOARD_SIZE]; BOARD_SIZE],
+    pieces: [Bitboard; 6],
+    colors: [Bitboard; 2],
+    // the Zobrist key of the pieces alone, updated as they move
+    zobrist_key: u64,
+}
 
 impl Default for Board {
     fn default() -> Self {
-        let mut squares = [[None; BOARD_SIZE]; BOARD_SIZE];
+        Self::with_back_rank(Self::get_back_rank)
+    }
+}
+
+impl Board {
+    pub(super) fn empty() -> Self {
+        Self {
+            squares: [[None; BOARD_SIZE]; BOARD_SIZE],
+            pieces: [Bitboard::EMPTY; 6],
+            colors: [Bitboard::EMPTY; 2],
+            zobrist_key: 0,
+        }
+    }
 
-        // Initialize pawns
-        for i in 0..8 {
-            squares[1][i] = Some(Piece::Pawn(Color::White));
-            squares[6][i] = Some(Piece::Pawn(Color::Black));
+    /// The start position with the given Scharnagl number, where 518 is the standard one.
+    pub(super) fn chess960(index: usize) -> Self {
+        Self::with_back_rank(|player| Self::get_chess960_back_rank(index, player))
+    }
+
+    fn with_back_rank(get_back_rank: impl Fn(Color) -> [Square; BOARD_SIZE]) -> Self {
+        let mut board = Self::empty();
+        for (x, (white, black)) in get_back_rank(Color::White)
+            .into_iter()
+            .zip(get_back_rank(Color::Black))
+            .enumerate()
+        {
+            board.set_piece(&Position::new(x, 0), white);
+            board.set_piece(&Position::new(x, 1), Some(Piece::Pawn(Color::White)));
+            board.set_piece(
+                &Position::new(x, BOARD_SIZE - 2),
+                Some(Piece::Pawn(Color::Black)),
+            );
+            board.set_piece(&Position::new(x, BOARD_SIZE - 1), black);
         }
+        board
+    }
 
-        // Initialize the other pieces
-        squares[0] = Self::get_back_rank(Color::White);
-        squares[BOARD_SIZE - 1] = Self::get_back_rank(Color::Black);
+    fn get_chess960_back_rank(index: usize, player: Color) -> [Square; BOARD_SIZE] {
+        let mut rank = [None; BOARD_SIZE];
+        let mut n = index;
 
-        Self(squares)
+        rank[n % 4 * 2 + 1] = Some(Piece::Bishop(player));
+        n /= 4;
+        rank[n % 4 * 2] = Some(Piece::Bishop(player));
+        n /= 4;
+        Self::place_on_empty_square(&mut rank, n % 6, Piece::Queen(player));
+        n /= 6;
+        // place the second knight first so the first knight's index still holds
+        let (first_knight, second_knight) = CHESS960_KNIGHT_SQUARES[n];
+        Self::place_on_empty_square(&mut rank, second_knight, Piece::Knight(player));
+        Self::place_on_empty_square(&mut rank, first_knight, Piece::Knight(player));
+        // the king always goes between the rooks
+        for piece in [
+            Piece::Rook(player),
+            Piece::King(player),
+            Piece::Rook(player),
+        ] {
+            Self::place_on_empty_square(&mut rank, 0, piece);
+        }
+        rank
+    }
+
+    fn place_on_empty_square(rank: &mut [Square; BOARD_SIZE], n: usize, piece: Piece) {
+        if let Some(square) = rank.iter_mut().filter(|square| square.is_none()).nth(n) {
+            *square = Some(piece);
+        }
     }
-}
 
-impl Board {
     fn get_back_rank(player: Color) -> [Square; 8] {
         [
             Some(Piece::Rook(player)),
@@ -42,18 +116,54 @@ impl Board {
     }
 
     pub(super) fn get_piece(&self, at: &Position) -> Square {
-        self.0[at.y][at.x]
+        self.squares[at.y][at.x]
     }
 
     pub(super) fn set_piece(&mut self, at: &Position, square: Square) {
-        self.0[at.y][at.x] = square;
+        self.take_piece(at);
+        if let Some(piece) = square {
+            let bitboard = Bitboard::from_position(at);
+            self.pieces[piece.get_index()] |= bitboard;
+            self.colors[piece.get_player() as usize] |= bitboard;
+            self.zobrist_key ^= zobrist::get_piece_key(piece, at.get_square());
+        }
+        self.squares[at.y][at.x] = square;
     }
 
     pub(super) fn take_piece(&mut self, from: &Position) -> Square {
-        self.0[from.y][from.x].take()
+        let square = self.squares[from.y][from.x].take();
+        if let Some(piece) = square {
+            let bitboard = !Bitboard::from_position(from);
+            self.pieces[piece.get_index()] &= bitboard;
+            self.colors[piece.get_player() as usize] &= bitboard;
+            self.zobrist_key ^= zobrist::get_piece_key(piece, from.get_square());
+        }
+        square
+    }
+
+    pub(super) fn get_rows(&self) -> Iter<'_, [Square; BOARD_SIZE]> {
+        self.squares.iter()
+    }
+
+    pub(super) fn get_bitboard(&self, piece: Piece) -> Bitboard {
+        self.pieces[piece.get_index()] & self.colors[piece.get_player() as usize]
+    }
+
+    pub(super) fn get_color_bitboard(&self, player: Color) -> Bitboard {
+        self.colors[player as usize]
+    }
+
+    pub(super) fn get_occupied(&self) -> Bitboard {
+        self.colors[0] | self.colors[1]
+    }
+
+    pub(super) fn get_zobrist_key(&self) -> u64 {
+        self.zobrist_key
     }
 
-    pub(super) fn get_rows(&self) -> Iter<[Square; BOARD_SIZE]> {
-        self.0.iter()
+    pub(super) fn find_king(&self, player: Color) -> Option<Position> {
+        self.get_bitboard(Piece::King(player))
+            .first()
+            .map(Position::from_square)
     }
 }
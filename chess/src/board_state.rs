@@ -1,41 +1,113 @@
 use std::hash::Hash;
 
 use crate::{
+    bitboard::{self, Bitboard},
     board::{Board, Square},
     castling_rights::CastlingRights,
     color::Color,
     displacement::Displacement,
+    movegen,
     moves::Move,
     piece::Piece,
     position::Position,
     result::{ChessError, ChessResult},
+    variant::{Variant, THREE_CHECK_CHECKS},
+    zobrist,
 };
 
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
 /// A struct encapsulating the state for the `Board`.
 pub(super) struct BoardState {
     pub(super) player: Color,
-    board: Board,
+    pub(super) board: Board,
     pub(super) castling_rights: CastlingRights,
     pub(super) en_passant_position: Option<Position>,
     pub(super) white_king_position: Position,
     pub(super) black_king_position: Position,
+    pub(super) variant: Variant,
+    // the checks each player has left to give in Three-check
+    pub(super) remaining_checks: [u8; 2],
+    zobrist_key: u64,
 }
 
 impl Default for BoardState {
     fn default() -> Self {
-        Self {
+        let mut state = Self {
             player: Color::default(),
             board: Board::default(),
             castling_rights: CastlingRights::default(),
             en_passant_position: None,
             white_king_position: Position::WHITE_KING,
             black_king_position: Position::BLACK_KING,
-        }
+            variant: Variant::default(),
+            remaining_checks: [THREE_CHECK_CHECKS; 2],
+            zobrist_key: 0,
+        };
+        state.update_zobrist_key();
+        state
     }
 }
 
 impl BoardState {
+    pub(super) fn new(
+        board: Board,
+        player: Color,
+        castling_rights: CastlingRights,
+        en_passant_position: Option<Position>,
+    ) -> Self {
+        let mut state = Self {
+            player,
+            board,
+            castling_rights,
+            en_passant_position,
+            white_king_position: board.find_king(Color::White).unwrap_or_default(),
+            black_king_position: board.find_king(Color::Black).unwrap_or_default(),
+            variant: Variant::default(),
+            remaining_checks: [THREE_CHECK_CHECKS; 2],
+            zobrist_key: 0,
+        };
+        state.update_zobrist_key();
+        state
+    }
+
+    pub(super) fn chess960(index: usize) -> Self {
+        let board = Board::chess960(index);
+        Self::new(board, Color::White, CastlingRights::chess960(&board), None)
+    }
+
+    pub(super) fn with_variant(mut self, variant: Variant) -> Self {
+        self.variant = variant;
+        self
+    }
+
+    pub(super) fn with_remaining_checks(mut self, remaining_checks: [u8; 2]) -> Self {
+        self.remaining_checks = remaining_checks;
+        self.update_zobrist_key();
+        self
+    }
+
+    pub(super) fn get_zobrist_key(&self) -> u64 {
+        self.zobrist_key
+    }
+
+    // The pieces keep their part of the key up to date as they move. The en
+    // passant square only counts when the capture is actually possible, so
+    // positions that differ by a dead en passant square are repetitions.
+    fn update_zobrist_key(&mut self) {
+        let en_passant_key = match self.en_passant_position {
+            Some(position) if movegen::can_capture_en_passant(self) => {
+                zobrist::get_en_passant_key(position.x)
+            }
+            _ => 0,
+        };
+        self.zobrist_key = self.board.get_zobrist_key()
+            ^ self.castling_rights.get_zobrist_key()
+            ^ en_passant_key
+            ^ zobrist::get_player_key(self.player)
+            ^ zobrist::get_remaining_checks_key(Color::White, self.remaining_checks[0])
+            ^ zobrist::get_remaining_checks_key(Color::Black, self.remaining_checks[1]);
+    }
+
     pub(super) fn has_insufficient_material(&self) -> bool {
         let mut white_minors = 0;
         let mut black_minors = 0;
@@ -75,6 +147,33 @@ impl BoardState {
         }
     }
 
+    /// Whether `player` can't checkmate by any sequence of legal moves, even with
+    /// the opponent's help. Used when the opponent runs out of time.
+    pub(super) fn has_insufficient_material_for(&self, player: Color) -> bool {
+        let board = &self.board;
+        let [own, opponent] = [player, !player].map(|player| board.get_color_bitboard(player));
+        let all = |piece: fn(Color) -> Piece| {
+            board.get_bitboard(piece(Color::White)) | board.get_bitboard(piece(Color::Black))
+        };
+        if !(own & (all(Piece::Pawn) | all(Piece::Rook) | all(Piece::Queen))).is_empty() {
+            return false;
+        }
+        if !(own & all(Piece::Knight)).is_empty() {
+            // a lone knight can only mate with the opponent's own pieces in the
+            // way, and a queen can't be used to block
+            return own.len() <= 2
+                && (opponent & !(all(Piece::King) | all(Piece::Queen))).is_empty();
+        }
+        let bishops = all(Piece::Bishop);
+        if !(own & bishops).is_empty() {
+            // bishops on one color can't mate unless something else can block
+            let one_color = (bishops & Bitboard::DARK_SQUARES).is_empty()
+                || (bishops & !Bitboard::DARK_SQUARES).is_empty();
+            return one_color && all(Piece::Knight).is_empty() && all(Piece::Pawn).is_empty();
+        }
+        true
+    }
+
     pub(super) fn get_piece(&self, at: &Position) -> Square {
         self.board.get_piece(at)
     }
@@ -86,22 +185,50 @@ impl BoardState {
     }
 
     pub(super) fn move_piece(&mut self, mv: &Move) {
-        let mut piece = self.board.take_piece(&mv.from).unwrap();
-        if self.can_promote_piece(piece, &mv.to) {
-            piece = Piece::Queen(self.player)
-        }
-        self.board
-            .set_piece(&Position::new(mv.to.x, mv.to.y), Some(piece));
+        let mut piece = self.board.get_piece(&mv.from).unwrap();
+        let to = if let Some(castling) = self.castling_rights.get_castling(mv, &self.board) {
+            // In Chess960 the king may land where the rook started, so lift both first
+            let king = self.board.take_piece(&mv.from);
+            let rook = self.board.take_piece(&castling.rook_from);
+            self.board.set_piece(&castling.king_to, king);
+            self.board.set_piece(&castling.rook_to, rook);
+            castling.king_to
+        } else {
+            let is_capture = self.board.get_piece(&mv.to).is_some()
+                || (piece.is_pawn() && Some(mv.to) == self.en_passant_position);
+            self.board.take_piece(&mv.from);
+            if self.can_promote_piece(piece, &mv.to) {
+                piece = mv.promotion.unwrap_or(Piece::Queen(self.player))
+            }
+            self.board.set_piece(&mv.to, Some(piece));
+            if self.variant == Variant::Atomic && is_capture {
+                self.explode(&mv.to);
+            }
+            mv.to
+        };
 
         // Update king's position if a king is moved
         if piece == Piece::King(self.player) {
             match self.player {
-                Color::White => self.white_king_position = mv.to,
-                Color::Black => self.black_king_position = mv.to,
+                Color::White => self.white_king_position = to,
+                Color::Black => self.black_king_position = to,
             }
         }
 
-        self.update(mv)
+        self.update(mv, piece)
+    }
+
+    // The capturing piece and every piece but pawns around it are blown up
+    fn explode(&mut self, at: &Position) {
+        self.handle_capturing_en_passant(at);
+        let blast = bitboard::king_attacks(at.get_square())
+            & self.board.get_occupied()
+            & !self.board.get_bitboard(Piece::Pawn(Color::White))
+            & !self.board.get_bitboard(Piece::Pawn(Color::Black));
+        for square in blast | Bitboard::from_position(at) {
+            self.board.set_piece(&Position::from_square(square), None);
+        }
+        self.castling_rights.remove_unsupported_rights(&self.board);
     }
 
     pub(super) fn is_in_bounds(at: &Position) -> ChessResult {
@@ -119,17 +246,16 @@ impl BoardState {
         Ok(())
     }
 
-    fn update(&mut self, mv: &Move) {
-        self.castling_rights
-            .handle_castling_the_rook(mv, &mut self.board, self.player);
-        self.castling_rights.update_castling_rights(&self.board);
+    fn update(&mut self, mv: &Move, piece: Piece) {
+        self.castling_rights.update(mv, piece);
         self.handle_capturing_en_passant(&mv.to);
         self.update_en_passant(mv);
         self.player = !self.player;
-    }
-
-    pub(super) fn has_piece(&self, position: &Position) -> bool {
-        Self::is_in_bounds(position).is_ok() && self.board.get_piece(position).is_some()
+        if self.variant == Variant::ThreeCheck && movegen::is_king_under_attack(self) {
+            let remaining_checks = &mut self.remaining_checks[!self.player as usize];
+            *remaining_checks = remaining_checks.saturating_sub(1);
+        }
+        self.update_zobrist_key();
     }
 
     pub(super) fn was_double_move(&self, mv: &Move) -> bool {
@@ -143,7 +269,10 @@ impl BoardState {
     }
 
     fn handle_capturing_en_passant(&mut self, to: &Position) {
-        if Some(*to) == self.en_passant_position {
+        // only a pawn landing on the en passant square captures en passant
+        if Some(*to) == self.en_passant_position
+            && self.board.get_piece(to) == Some(Piece::Pawn(self.player))
+        {
             self.board.set_piece(
                 &(*to - Displacement::get_pawn_advance_vector(self.player)),
                 None,
@@ -159,3 +288,105 @@ impl BoardState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::fen::Fen;
+
+    #[test]
+    fn test_piece_on_en_passant_square() {
+        // a bishop landing on the en passant square doesn't capture the pawn
+        let fen: Fen = "rnbqkbnr/ppppppp1/8/7p/8/3P4/PPP1PPPP/RNBQKBNR w KQkq h6 0 2"
+            .parse()
+            .unwrap();
+        let mut state = fen.state;
+        state.move_piece(&Move::new(Position::new(2, 0), Position::new(7, 5)));
+        assert_eq!(
+            state.get_piece(&Position::new(7, 4)),
+            Some(Piece::Pawn(Color::Black))
+        );
+    }
+
+    fn play(fen: &str, moves: &[&str]) -> BoardState {
+        let mut state = fen.parse::<Fen>().unwrap().state;
+        for lan in moves {
+            state.move_piece(&Move::from_lan(lan).unwrap());
+        }
+        state
+    }
+
+    #[test]
+    fn test_insufficient_material_for() {
+        for (fen, white, black) in [
+            ("8/8/4k3/8/8/3K4/8/7q w - - 0 1", true, false),
+            // a knight can mate a king boxed in by its own pawn, but not by a queen
+            ("8/8/4k3/8/8/3K4/8/6N1 w - - 0 1", true, true),
+            ("8/8/4kp2/8/8/3K4/8/6N1 w - - 0 1", false, false),
+            ("8/8/4kq2/8/8/3K4/8/6N1 w - - 0 1", true, false),
+            // bishops only mate on both colors or with something to block
+            ("8/8/4k1b1/8/8/3K4/8/5B2 w - - 0 1", true, true),
+            ("8/8/4kb2/8/8/3K4/8/5B2 w - - 0 1", false, false),
+            ("8/8/4kn2/8/8/3K4/8/5B2 w - - 0 1", false, false),
+        ] {
+            let state = play(fen, &[]);
+            assert_eq!(state.has_insufficient_material_for(Color::White), white);
+            assert_eq!(state.has_insufficient_material_for(Color::Black), black);
+        }
+    }
+
+    #[test]
+    fn test_zobrist_key() {
+        let initial = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+        // the incrementally updated key matches the key of the same position from scratch
+        let state = play(initial, &["e2e4", "d7d5", "e4d5", "g8f6", "f1b5", "c7c6"]);
+        let fen = "rnbqkb1r/pp2pppp/2p2n2/1B1P4/8/8/PPPP1PPP/RNBQK1NR w KQkq - 0 4";
+        assert_eq!(state.get_zobrist_key(), play(fen, &[]).get_zobrist_key());
+
+        // transpositions share a key
+        assert_eq!(
+            play(initial, &["g1f3", "g8f6", "b1c3"]).get_zobrist_key(),
+            play(initial, &["b1c3", "g8f6", "g1f3"]).get_zobrist_key()
+        );
+        assert_eq!(
+            play(initial, &[]).get_zobrist_key(),
+            play(initial, &["g1f3", "g8f6", "f3g1", "f6g8"]).get_zobrist_key()
+        );
+
+        // castling rights and the player to move are part of the key
+        let castled = "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1";
+        let keys: HashSet<u64> = [
+            castled,
+            "r3k2r/8/8/8/8/8/8/R3K2R w Kkq - 0 1",
+            "r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1",
+        ]
+        .into_iter()
+        .map(|fen| play(fen, &[]).get_zobrist_key())
+        .collect();
+        assert_eq!(keys.len(), 3);
+    }
+
+    #[test]
+    fn test_zobrist_key_en_passant() {
+        let key = |fen| play(fen, &[]).get_zobrist_key();
+
+        // no black pawn can capture on e3
+        assert_eq!(
+            key("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            key("rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+        // the d4 pawn can
+        assert_ne!(
+            key("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1"),
+            key("rnbqkbnr/ppp1pppp/8/8/3pP3/8/PPPP1PPP/RNBQKBNR b KQkq - 0 1")
+        );
+        // but not when it's pinned to its king
+        assert_eq!(
+            key("8/8/8/8/k2pP2R/8/8/4K3 b - e3 0 1"),
+            key("8/8/8/8/k2pP2R/8/8/4K3 b - - 0 1")
+        );
+    }
+}
@@ -1,7 +1,4 @@
-use crate::{
-    board::Board, color::Color, displacement::Displacement, moves::Move, piece::Piece,
-    position::Position,
-};
+use crate::{board::Board, color::Color, moves::Move, piece::Piece, position::Position, zobrist};
 
 #[derive(Clone, Copy)]
 pub(super) enum CastlingRightsKind {
@@ -11,143 +8,316 @@ pub(super) enum CastlingRightsKind {
     BlackQueenside,
 }
 
+impl CastlingRightsKind {
+    const ALL: [Self; 4] = [
+        Self::WhiteKingside,
+        Self::WhiteQueenside,
+        Self::BlackKingside,
+        Self::BlackQueenside,
+    ];
+
+    fn new(player: Color, side: CastlingSide) -> Self {
+        match (player, side) {
+            (Color::White, CastlingSide::Kingside) => Self::WhiteKingside,
+            (Color::White, CastlingSide::Queenside) => Self::WhiteQueenside,
+            (Color::Black, CastlingSide::Kingside) => Self::BlackKingside,
+            (Color::Black, CastlingSide::Queenside) => Self::BlackQueenside,
+        }
+    }
+
+    fn get_player(self) -> Color {
+        match self {
+            Self::WhiteKingside | Self::WhiteQueenside => Color::White,
+            Self::BlackKingside | Self::BlackQueenside => Color::Black,
+        }
+    }
+
+    fn get_side(self) -> CastlingSide {
+        match self {
+            Self::WhiteKingside | Self::BlackKingside => CastlingSide::Kingside,
+            Self::WhiteQueenside | Self::BlackQueenside => CastlingSide::Queenside,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum CastlingSide {
+    Kingside,
+    Queenside,
+}
+
+impl CastlingSide {
+    // The king and rook end up on the same squares in Chess960 as in standard chess
+    fn get_king_file(self) -> usize {
+        match self {
+            Self::Kingside => 6,
+            Self::Queenside => 2,
+        }
+    }
+
+    fn get_rook_file(self) -> usize {
+        match self {
+            Self::Kingside => 5,
+            Self::Queenside => 3,
+        }
+    }
+}
+
+/// Where the king and rook move when castling.
+pub(super) struct Castling {
+    pub(super) side: CastlingSide,
+    pub(super) king_to: Position,
+    pub(super) rook_from: Position,
+    pub(super) rook_to: Position,
+}
+
 #[derive(Clone, Copy, Hash, PartialEq, Eq)]
-pub(super) struct CastlingRights([bool; 4]);
+pub(super) struct CastlingRights {
+    // The file of the rook each right castles with
+    rook_files: [Option<usize>; 4],
+    // Chess960 castling is written as the king capturing its own rook, since
+    // the king may not move at all, and FEN names the rooks by their files
+    chess960: bool,
+}
 
 impl Default for CastlingRights {
     fn default() -> Self {
-        Self([true, true, true, true])
+        Self {
+            rook_files: [Some(7), Some(0), Some(7), Some(0)],
+            chess960: false,
+        }
     }
 }
 
 impl CastlingRights {
-    pub(super) fn rook_positions() -> [(Position, Piece, CastlingRightsKind); 4] {
-        [
-            (
-                Position::WHITE_KINGSIDE_ROOK,
-                Piece::Rook(Color::White),
-                CastlingRightsKind::WhiteKingside,
-            ),
-            (
-                Position::WHITE_QUEENSIDE_ROOK,
-                Piece::Rook(Color::White),
-                CastlingRightsKind::WhiteQueenside,
-            ),
-            (
-                Position::BLACK_KINGSIDE_ROOK,
-                Piece::Rook(Color::Black),
-                CastlingRightsKind::BlackKingside,
-            ),
-            (
-                Position::BLACK_QUEENSIDE_ROOK,
-                Piece::Rook(Color::Black),
-                CastlingRightsKind::BlackQueenside,
-            ),
-        ]
+    pub(super) fn none() -> Self {
+        Self {
+            rook_files: [None; 4],
+            chess960: false,
+        }
     }
 
-    pub(super) fn king_positions() -> [(Position, Piece, CastlingRightsKind, CastlingRightsKind); 2]
-    {
-        [
-            (
-                Position::WHITE_KING,
-                Piece::King(Color::White),
-                CastlingRightsKind::WhiteKingside,
-                CastlingRightsKind::WhiteQueenside,
-            ),
-            (
-                Position::BLACK_KING,
-                Piece::King(Color::Black),
-                CastlingRightsKind::BlackKingside,
-                CastlingRightsKind::BlackQueenside,
-            ),
-        ]
+    /// All four rights of a Chess960 start position.
+    pub(super) fn chess960(board: &Board) -> Self {
+        let mut rights = Self::none();
+        rights.chess960 = true;
+        for right in CastlingRightsKind::ALL {
+            rights.rook_files[right as usize] = Self::find_outermost_rook(board, right);
+        }
+        rights
     }
 
-    pub(super) fn get_castling_positions(player: Color) -> (Position, Position, Position) {
-        match player {
-            Color::White => (
-                Position::WHITE_KING,
-                Position::WHITE_KINGSIDE_ROOK,
-                Position::WHITE_QUEENSIDE_ROOK,
-            ),
-            Color::Black => (
-                Position::BLACK_KING,
-                Position::BLACK_KINGSIDE_ROOK,
-                Position::BLACK_QUEENSIDE_ROOK,
-            ),
-        }
-    }
-
-    pub(super) fn get_castling_info(
-        player: Color,
-    ) -> (Position, CastlingRightsKind, CastlingRightsKind) {
+    pub(super) fn is_chess960(&self) -> bool {
+        self.chess960
+    }
+
+    pub(super) fn is_empty(&self) -> bool {
+        self.rook_files.iter().all(Option::is_none)
+    }
+
+    fn get_back_rank(player: Color) -> usize {
         match player {
-            Color::White => (
-                Position::WHITE_KING,
-                CastlingRightsKind::WhiteKingside,
-                CastlingRightsKind::WhiteQueenside,
-            ),
-            Color::Black => (
-                Position::BLACK_KING,
-                CastlingRightsKind::BlackKingside,
-                CastlingRightsKind::BlackQueenside,
-            ),
-        }
-    }
-
-    pub(super) fn update_castling_rights(&mut self, board: &Board) {
-        for (position, piece, rights) in CastlingRights::rook_positions() {
-            if board.get_piece(&position) != Some(piece) {
-                self.0[rights as usize] = false;
-            }
+            Color::White => 0,
+            Color::Black => 7,
         }
+    }
 
-        for (position, piece, kingside_rights, queenside_rights) in CastlingRights::king_positions()
-        {
-            if board.get_piece(&position) != Some(piece) {
-                self.0[kingside_rights as usize] = false;
-                self.0[queenside_rights as usize] = false;
-            }
+    fn find_outermost_rook(board: &Board, right: CastlingRightsKind) -> Option<usize> {
+        let player = right.get_player();
+        let y = Self::get_back_rank(player);
+        let king = board.find_king(player).filter(|king| king.y == y)?;
+        let is_rook =
+            |&x: &usize| board.get_piece(&Position::new(x, y)) == Some(Piece::Rook(player));
+        match right.get_side() {
+            CastlingSide::Kingside => (king.x + 1..8).rev().find(is_rook),
+            CastlingSide::Queenside => (0..king.x).find(is_rook),
         }
     }
 
-    pub(super) fn handle_castling_the_rook(&self, mv: &Move, board: &mut Board, player: Color) {
-        let (king, kingside_rook, queenside_rook) = CastlingRights::get_castling_positions(player);
+    pub(super) fn get_castling_rights(player: Color) -> [CastlingRightsKind; 2] {
+        [
+            CastlingRightsKind::new(player, CastlingSide::Kingside),
+            CastlingRightsKind::new(player, CastlingSide::Queenside),
+        ]
+    }
 
-        if mv.from == king {
-            if mv.to == king + Displacement::RIGHT * 2 {
-                let rook = board.take_piece(&kingside_rook);
-                board.set_piece(&(kingside_rook + Displacement::LEFT * 2), rook);
-            } else if mv.to == king + Displacement::LEFT * 2 {
-                let rook = board.take_piece(&queenside_rook);
-                board.set_piece(&(queenside_rook + Displacement::RIGHT * 3), rook);
-            }
-        }
+    pub(super) fn get_rook_position(&self, right: CastlingRightsKind) -> Option<Position> {
+        self.rook_files[right as usize]
+            .map(|x| Position::new(x, Self::get_back_rank(right.get_player())))
     }
 
-    pub(super) fn has_castling_right(&self, right: CastlingRightsKind) -> bool {
-        self.0[right as usize]
+    pub(super) fn get_destinations(right: CastlingRightsKind) -> (Position, Position) {
+        let (side, y) = (right.get_side(), Self::get_back_rank(right.get_player()));
+        (
+            Position::new(side.get_king_file(), y),
+            Position::new(side.get_rook_file(), y),
+        )
     }
 
-    pub(super) fn get_fen_str(&self) -> String {
-        let mut fen = String::default();
-        if self.0[CastlingRightsKind::WhiteKingside as usize] {
-            fen.push(Piece::King(Color::White).get_fen_char())
+    /// The move that castles with `right`, given where the king stands.
+    pub(super) fn get_castling_move(&self, right: CastlingRightsKind, king: Position) -> Move {
+        match self.get_rook_position(right) {
+            Some(rook) if self.chess960 => Move::new(king, rook),
+            _ => Move::new(king, Self::get_destinations(right).0),
+        }
+    }
+
+    /// Works out whether `mv` castles, and if so where the king and rook go.
+    pub(super) fn get_castling(&self, mv: &Move, board: &Board) -> Option<Castling> {
+        let Some(Piece::King(player)) = board.get_piece(&mv.from) else {
+            return None;
+        };
+        if mv.from.y != mv.to.y {
+            return None;
         }
-        if self.0[CastlingRightsKind::WhiteQueenside as usize] {
-            fen.push(Piece::Queen(Color::White).get_fen_char())
+        let side = if mv.to.x > mv.from.x {
+            CastlingSide::Kingside
+        } else {
+            CastlingSide::Queenside
+        };
+        let rook_from = if self.chess960 {
+            (board.get_piece(&mv.to) == Some(Piece::Rook(player))).then_some(mv.to)?
+        } else if mv.from.x.abs_diff(mv.to.x) == 2 {
+            self.get_rook_position(CastlingRightsKind::new(player, side))?
+        } else {
+            return None;
+        };
+        let (king_to, rook_to) = Self::get_destinations(CastlingRightsKind::new(player, side));
+        Some(Castling {
+            side,
+            king_to,
+            rook_from,
+            rook_to,
+        })
+    }
+
+    /// Drops the rights a move gives up: all of them when the king moves, and
+    /// a rook's when it moves or is captured.
+    pub(super) fn update(&mut self, mv: &Move, piece: Piece) {
+        if let Piece::King(player) = piece {
+            for right in Self::get_castling_rights(player) {
+                self.rook_files[right as usize] = None;
+            }
         }
-        if self.0[CastlingRightsKind::BlackKingside as usize] {
-            fen.push(Piece::King(Color::Black).get_fen_char())
+        for right in CastlingRightsKind::ALL {
+            if self
+                .get_rook_position(right)
+                .is_some_and(|rook| rook == mv.from || rook == mv.to)
+            {
+                self.rook_files[right as usize] = None;
+            }
         }
-        if self.0[CastlingRightsKind::BlackQueenside as usize] {
-            fen.push(Piece::Queen(Color::Black).get_fen_char())
+    }
+
+    /// Drops any rights the pieces on the board can no longer support.
+    pub(super) fn remove_unsupported_rights(&mut self, board: &Board) {
+        for right in CastlingRightsKind::ALL {
+            let player = right.get_player();
+            let is_supported = self.get_rook_position(right).is_some_and(|rook| {
+                board.get_piece(&rook) == Some(Piece::Rook(player))
+                    && board.find_king(player).is_some_and(|king| {
+                        king.y == rook.y
+                            && match right.get_side() {
+                                CastlingSide::Kingside => rook.x > king.x,
+                                CastlingSide::Queenside => rook.x < king.x,
+                            }
+                    })
+            });
+            if !is_supported {
+                self.rook_files[right as usize] = None;
+            }
         }
+    }
+
+    // Whether the king and rooks with rights stand where they do in standard chess
+    fn is_standard(&self, board: &Board) -> bool {
+        CastlingRightsKind::ALL.into_iter().all(|right| {
+            let standard_file = match right.get_side() {
+                CastlingSide::Kingside => 7,
+                CastlingSide::Queenside => 0,
+            };
+            self.rook_files[right as usize].is_none_or(|file| {
+                file == standard_file
+                    && board.find_king(right.get_player()).map(|king| king.x) == Some(4)
+            })
+        })
+    }
+
+    pub(super) fn get_zobrist_key(&self) -> u64 {
+        self.rook_files
+            .iter()
+            .enumerate()
+            .filter(|(_, file)| file.is_some())
+            .fold(0, |key, (right, _)| {
+                key ^ zobrist::get_castling_right_key(right)
+            })
+    }
+
+    /// Standard games use KQkq, Chess960 games use the rook files (Shredder-FEN).
+    pub(super) fn get_fen_str(&self) -> String {
+        let fen: String = CastlingRightsKind::ALL
+            .into_iter()
+            .filter_map(|right| {
+                let file = self.rook_files[right as usize]?;
+                let c = match (self.chess960, right.get_side()) {
+                    (true, _) => (b'a' + file as u8) as char,
+                    (false, CastlingSide::Kingside) => 'k',
+                    (false, CastlingSide::Queenside) => 'q',
+                };
+                Some(match right.get_player() {
+                    Color::White => c.to_ascii_uppercase(),
+                    Color::Black => c,
+                })
+            })
+            .collect();
         if fen.is_empty() {
             String::from('-')
         } else {
             fen
         }
     }
+
+    /// Reads KQkq, X-FEN and Shredder-FEN castling rights. Rook files or a king
+    /// and rooks off their standard squares make this a Chess960 position.
+    pub(super) fn from_fen_str(fen: &str, board: &Board) -> Option<Self> {
+        let mut rights = Self::none();
+        if fen == "-" {
+            return Some(rights);
+        }
+        for c in fen.chars() {
+            let player = if c.is_ascii_uppercase() {
+                Color::White
+            } else {
+                Color::Black
+            };
+            let king = board.find_king(player);
+            let (right, file) = match c.to_ascii_lowercase() {
+                'k' => {
+                    let right = CastlingRightsKind::new(player, CastlingSide::Kingside);
+                    (right, Self::find_outermost_rook(board, right))
+                }
+                'q' => {
+                    let right = CastlingRightsKind::new(player, CastlingSide::Queenside);
+                    (right, Self::find_outermost_rook(board, right))
+                }
+                file @ 'a'..='h' => {
+                    rights.chess960 = true;
+                    let x = (file as u8 - b'a') as usize;
+                    let side = if king.is_some_and(|king| x > king.x) {
+                        CastlingSide::Kingside
+                    } else {
+                        CastlingSide::Queenside
+                    };
+                    (CastlingRightsKind::new(player, side), Some(x))
+                }
+                _ => return None,
+            };
+            rights.rook_files[right as usize] = file;
+        }
+        rights.remove_unsupported_rights(board);
+        if !rights.is_standard(board) {
+            rights.chess960 = true;
+        }
+        Some(rights)
+    }
 }
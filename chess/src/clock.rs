@@ -0,0 +1,60 @@
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use web_time::{Duration, Instant};
+
+/// Where the `Timer` reads the time from. Only the differences between readings
+/// matter, so a clock can count from any starting point.
+pub trait Clock: fmt::Debug + Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+/// The system's monotonic clock.
+#[derive(Clone, Copy, Debug)]
+pub struct RealClock {
+    origin: Instant,
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+/// A clock that only moves when told to, for tests and for replaying the
+/// timestamps of stored moves. Clones share the same time, so a game can be
+/// given one clone while the caller keeps another to move it.
+#[derive(Clone, Default, Debug)]
+pub struct ManualClock {
+    now: Arc<Mutex<Duration>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap() += duration;
+    }
+
+    pub fn set(&self, now: Duration) {
+        *self.now.lock().unwrap() = now;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+}
@@ -14,6 +14,14 @@ impl Color {
             Self::Black => 'b',
         }
     }
+
+    pub(super) fn from_fen_char(c: char) -> Option<Self> {
+        match c {
+            'w' => Some(Self::White),
+            'b' => Some(Self::Black),
+            _ => None,
+        }
+    }
 }
 
 impl std::ops::Not for Color {
@@ -0,0 +1,598 @@
+use std::cmp::Reverse;
+
+use crate::{
+    board_state::BoardState,
+    evaluation::{evaluate, get_piece_value},
+    game::Game,
+    movegen,
+    moves::Move,
+    piece::Piece,
+    transposition_table::{Bound, TranspositionTable},
+    variant::Variant,
+};
+
+const MAX_DEPTH: u8 = 64;
+const MAX_PLY: usize = 128;
+const MATE_SCORE: i32 = 30_000;
+const INFINITY: i32 = 32_000;
+// Scores this close to a mate are mates, counted in plies from the root
+const MATE_THRESHOLD: i32 = MATE_SCORE - MAX_PLY as i32;
+
+/// How long the engine may think, which is also how its strength is set. The
+/// search stops at whichever limit it reaches first.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SearchLimits {
+    /// The number of plies searched before only captures are looked at.
+    pub depth: u8,
+    /// The number of positions the search may visit.
+    pub nodes: Option<u64>,
+}
+
+impl Default for SearchLimits {
+    fn default() -> Self {
+        Self::depth(4)
+    }
+}
+
+impl SearchLimits {
+    pub fn depth(depth: u8) -> Self {
+        Self {
+            depth: depth.clamp(1, MAX_DEPTH),
+            nodes: None,
+        }
+    }
+
+    /// Searches as deep as `nodes` positions allow. Unlike a depth limit, this
+    /// takes about as long in every position.
+    pub fn nodes(nodes: u64) -> Self {
+        Self {
+            depth: MAX_DEPTH,
+            nodes: Some(nodes),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct SearchResult {
+    pub best_move: Move,
+    /// In centipawns, from the point of view of the player to move.
+    pub score: i32,
+    /// The depth of the last search that finished.
+    pub depth: u8,
+    pub nodes: u64,
+}
+
+impl SearchResult {
+    /// The number of moves to a forced mate, negative when the player to move
+    /// is the one getting mated.
+    pub fn get_mate_in(&self) -> Option<i32> {
+        if self.score >= MATE_THRESHOLD {
+            Some((MATE_SCORE - self.score + 1) / 2)
+        } else if self.score <= -MATE_THRESHOLD {
+            Some(-(MATE_SCORE + self.score) / 2)
+        } else {
+            None
+        }
+    }
+}
+
+/// An alpha-beta search with iterative deepening, a quiescence search and a
+/// transposition table, for playing against the computer without an external
+/// engine. It knows the rules of every variant, but evaluates positions as in
+/// standard chess.
+#[derive(Clone)]
+pub struct Engine {
+    limits: SearchLimits,
+    table: TranspositionTable,
+    // the variant the table was filled in, as the keys don't include it
+    variant: Variant,
+    // quiet moves that caused a cutoff, by ply
+    killers: Vec<[Option<Move>; 2]>,
+    // the positions of the game and of the line being searched
+    keys: Vec<u64>,
+    nodes: u64,
+    // the node count to pause the search at, to be picked up again later
+    pause_at: Option<u64>,
+    can_stop: bool,
+    stopped: bool,
+    root_best_move: Option<Move>,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Self::new(SearchLimits::default())
+    }
+}
+
+impl Engine {
+    pub fn new(limits: SearchLimits) -> Self {
+        Self {
+            limits,
+            table: TranspositionTable::default(),
+            variant: Variant::default(),
+            killers: vec![[None; 2]; MAX_PLY],
+            keys: Vec::new(),
+            nodes: 0,
+            pause_at: None,
+            can_stop: false,
+            stopped: false,
+            root_best_move: None,
+        }
+    }
+
+    pub fn get_limits(&self) -> SearchLimits {
+        self.limits
+    }
+
+    pub fn set_limits(&mut self, limits: SearchLimits) {
+        self.limits = limits;
+    }
+
+    /// Forgets everything learned in earlier searches, such as before a new game.
+    pub fn clear(&mut self) {
+        self.table.clear();
+    }
+
+    /// Finds the best move in the position `game` is showing, or `None` if the
+    /// player to move has no moves or has already lost.
+    pub fn search(&mut self, game: &Game) -> Option<SearchResult> {
+        let result = self.iterate(game).last()?;
+        Some(SearchResult {
+            nodes: self.nodes,
+            ..result
+        })
+    }
+
+    /// Searches one ply deeper at each step, up to the limits, so that the
+    /// caller can do other work between iterations, like letting a UI redraw.
+    /// The last result is the one `search` gives.
+    pub fn iterate(&mut self, game: &Game) -> SearchIterations<'_> {
+        let state = *game.get_current_state();
+        let is_over = state.variant.get_loser(&state).is_some()
+            || movegen::get_legal_moves(&state).is_empty();
+        if state.variant != self.variant {
+            self.table.clear();
+            self.variant = state.variant;
+        }
+        self.keys = game.get_repetition_keys();
+        self.nodes = 0;
+        self.stopped = false;
+        self.killers.fill([None; 2]);
+        SearchIterations {
+            state,
+            halfmove_clock: game.get_halfmove_clock(),
+            depth: 1,
+            is_done: is_over,
+            pause_nodes: None,
+            last_result: None,
+            engine: self,
+        }
+    }
+
+    fn count_node(&mut self) {
+        self.nodes += 1;
+        if self.can_stop
+            && (self.is_out_of_nodes() || self.pause_at.is_some_and(|at| self.nodes >= at))
+        {
+            self.stopped = true;
+        }
+    }
+
+    fn is_out_of_nodes(&self) -> bool {
+        self.limits.nodes.is_some_and(|limit| self.nodes >= limit)
+    }
+
+    // The score of a position the game has ended in, or `None` if it goes on
+    fn get_end_score(state: &BoardState, moves: &[Move], ply: usize) -> Option<i32> {
+        let variant = state.variant;
+        let mated = -MATE_SCORE + ply as i32;
+        if let Some(loser) = variant.get_loser(state) {
+            return Some(if loser == state.player { mated } else { -mated });
+        }
+        if moves.is_empty() {
+            return Some(if variant.is_king_under_attack(state) {
+                mated
+            } else if variant.stalemate_loses() {
+                // the stalemated player wins
+                -mated
+            } else {
+                0
+            });
+        }
+        variant.has_insufficient_material(state).then_some(0)
+    }
+
+    fn is_repetition(&self, halfmove_clock: u8) -> bool {
+        let (key, earlier) = self.keys.split_last().unwrap();
+        earlier
+            .iter()
+            .rev()
+            .take(halfmove_clock as usize)
+            .any(|other| other == key)
+    }
+
+    // Captures come first, the most valuable victims taken by the least
+    // valuable attackers before the rest
+    fn order_moves(&self, state: &BoardState, moves: &mut [Move], best: Option<Move>, ply: usize) {
+        let killers = self.killers.get(ply).copied().unwrap_or_default();
+        moves.sort_by_cached_key(|mv| {
+            Reverse(if Some(*mv) == best {
+                1_000_000
+            } else if let Some(victim) = movegen::get_captured_piece(state, mv) {
+                let attacker = state.get_piece(&mv.from).unwrap();
+                100_000 + 10 * get_piece_value(victim) - get_piece_value(attacker)
+            } else if let Some(promotion) = mv.promotion {
+                90_000 + get_piece_value(promotion)
+            } else if killers[0] == Some(*mv) {
+                80_001
+            } else if killers[1] == Some(*mv) {
+                80_000
+            } else {
+                0
+            })
+        });
+    }
+
+    fn store_killer(&mut self, mv: Move, ply: usize) {
+        if let Some(killers) = self.killers.get_mut(ply) {
+            if killers[0] != Some(mv) {
+                killers[1] = killers[0];
+                killers[0] = Some(mv);
+            }
+        }
+    }
+
+    // Mates are stored as distances from the position rather than the root
+    fn to_table_score(score: i32, ply: usize) -> i32 {
+        match score {
+            score if score >= MATE_THRESHOLD => score + ply as i32,
+            score if score <= -MATE_THRESHOLD => score - ply as i32,
+            score => score,
+        }
+    }
+
+    fn from_table_score(score: i32, ply: usize) -> i32 {
+        match score {
+            score if score >= MATE_THRESHOLD => score - ply as i32,
+            score if score <= -MATE_THRESHOLD => score + ply as i32,
+            score => score,
+        }
+    }
+
+    fn negamax(
+        &mut self,
+        state: &BoardState,
+        depth: u8,
+        mut alpha: i32,
+        beta: i32,
+        ply: usize,
+        halfmove_clock: u8,
+    ) -> i32 {
+        if ply > 0 && (halfmove_clock >= 100 || self.is_repetition(halfmove_clock)) {
+            return 0;
+        }
+        if depth == 0 || ply >= MAX_PLY - 1 {
+            return self.quiescence(state, alpha, beta, ply);
+        }
+        self.count_node();
+        if self.stopped {
+            return 0;
+        }
+        let mut moves = movegen::get_legal_moves(state);
+        if let Some(score) = Self::get_end_score(state, &moves, ply) {
+            return score;
+        }
+
+        let key = state.get_zobrist_key();
+        let mut table_move = None;
+        if let Some(entry) = self.table.get(key) {
+            table_move = entry.best_move;
+            let score = Self::from_table_score(entry.score, ply);
+            if ply > 0 && entry.depth >= depth {
+                match entry.bound {
+                    Bound::Exact => return score,
+                    Bound::Lower if score >= beta => return score,
+                    Bound::Upper if score <= alpha => return score,
+                    _ => (),
+                }
+            }
+        }
+
+        self.order_moves(state, &mut moves, table_move, ply);
+        let original_alpha = alpha;
+        let mut best_score = -INFINITY;
+        let mut best_move = None;
+        for mv in moves {
+            let is_capture = movegen::get_captured_piece(state, &mv).is_some();
+            let is_pawn_move = state.get_piece(&mv.from).is_some_and(Piece::is_pawn);
+            let mut next_state = *state;
+            next_state.move_piece(&mv);
+            let next_halfmove_clock = if is_capture || is_pawn_move {
+                0
+            } else {
+                halfmove_clock.saturating_add(1)
+            };
+
+            self.keys.push(next_state.get_zobrist_key());
+            let score = -self.negamax(
+                &next_state,
+                depth - 1,
+                -beta,
+                -alpha,
+                ply + 1,
+                next_halfmove_clock,
+            );
+            self.keys.pop();
+            if self.stopped {
+                return 0;
+            }
+
+            if score > best_score {
+                best_score = score;
+                best_move = Some(mv);
+                if ply == 0 {
+                    self.root_best_move = best_move;
+                }
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                if !is_capture && mv.promotion.is_none() {
+                    self.store_killer(mv, ply);
+                }
+                break;
+            }
+        }
+
+        let bound = if best_score <= original_alpha {
+            Bound::Upper
+        } else if best_score >= beta {
+            Bound::Lower
+        } else {
+            Bound::Exact
+        };
+        self.table.insert(
+            key,
+            depth,
+            Self::to_table_score(best_score, ply),
+            bound,
+            best_move,
+        );
+        best_score
+    }
+
+    // Plays out the captures and promotions of a position, so that it isn't
+    // judged in the middle of an exchange. A player in check has to answer it.
+    fn quiescence(&mut self, state: &BoardState, mut alpha: i32, beta: i32, ply: usize) -> i32 {
+        self.count_node();
+        if self.stopped {
+            return 0;
+        }
+        let moves = movegen::get_legal_moves(state);
+        if let Some(score) = Self::get_end_score(state, &moves, ply) {
+            return score;
+        }
+        if ply >= MAX_PLY - 1 {
+            return evaluate(state);
+        }
+
+        let in_check = state.variant.is_king_under_attack(state);
+        let mut best_score = -INFINITY;
+        if !in_check {
+            best_score = evaluate(state);
+            if best_score >= beta {
+                return best_score;
+            }
+            alpha = alpha.max(best_score);
+        }
+
+        let mut moves: Vec<Move> = moves
+            .into_iter()
+            .filter(|mv| {
+                in_check
+                    || mv.promotion.is_some()
+                    || movegen::get_captured_piece(state, mv).is_some()
+            })
+            .collect();
+        self.order_moves(state, &mut moves, None, ply);
+        for mv in moves {
+            let mut next_state = *state;
+            next_state.move_piece(&mv);
+            let score = -self.quiescence(&next_state, -beta, -alpha, ply + 1);
+            if self.stopped {
+                return 0;
+            }
+            best_score = best_score.max(score);
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+        best_score
+    }
+}
+
+/// The iterations of a search, from `Engine::iterate`.
+pub struct SearchIterations<'a> {
+    engine: &'a mut Engine,
+    state: BoardState,
+    halfmove_clock: u8,
+    depth: u8,
+    is_done: bool,
+    pause_nodes: Option<u64>,
+    last_result: Option<SearchResult>,
+}
+
+impl SearchIterations<'_> {
+    /// Also stops every `nodes` positions in the middle of a depth, giving the
+    /// last finished result again, since a deep iteration can take most of
+    /// the search. The depth goes on from the transposition table at the next
+    /// call, so work is only repeated where it couldn't be stored.
+    pub fn pausing_every(mut self, nodes: u64) -> Self {
+        self.pause_nodes = Some(nodes.max(1));
+        self
+    }
+}
+
+impl Iterator for SearchIterations<'_> {
+    type Item = SearchResult;
+
+    fn next(&mut self) -> Option<SearchResult> {
+        if self.is_done || self.depth > self.engine.limits.depth {
+            return None;
+        }
+        let engine = &mut *self.engine;
+        // the first iteration always finishes, so there's a move to play
+        engine.can_stop = self.depth > 1;
+        engine.pause_at = self.pause_nodes.map(|nodes| engine.nodes + nodes);
+        engine.root_best_move = None;
+        let score = engine.negamax(
+            &self.state,
+            self.depth,
+            -INFINITY,
+            INFINITY,
+            0,
+            self.halfmove_clock,
+        );
+        if engine.stopped && !engine.is_out_of_nodes() {
+            engine.stopped = false;
+            return self.last_result;
+        }
+        let best_move = engine.root_best_move.filter(|_| !engine.stopped);
+        // there's no need to look deeper once a mate is found
+        self.is_done = best_move.is_none() || score.abs() >= MATE_THRESHOLD;
+        let result = SearchResult {
+            best_move: best_move?,
+            score,
+            depth: self.depth,
+            nodes: engine.nodes,
+        };
+        self.depth += 1;
+        self.last_result = Some(result);
+        Some(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+
+    fn search_fen(fen: &str, limits: SearchLimits) -> SearchResult {
+        let game = Game::from_fen(fen).unwrap();
+        Engine::new(limits).search(&game).unwrap()
+    }
+
+    #[test]
+    fn test_mate() {
+        // back rank mate
+        let result = search_fen(
+            "6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1",
+            SearchLimits::depth(3),
+        );
+        assert_eq!(result.best_move, Move::from_lan("a1a8").unwrap());
+        assert_eq!(result.get_mate_in(), Some(1));
+        assert_eq!(result.depth, 1);
+
+        // the rooks need two moves to mate a king on the edge
+        let result = search_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1", SearchLimits::depth(4));
+        assert_eq!(result.get_mate_in(), Some(2));
+
+        // the side getting mated sees it coming: the king's only move walks
+        // into a back rank mate
+        let result = search_fen("7k/8/6K1/8/8/8/8/R7 b - - 0 1", SearchLimits::depth(3));
+        assert_eq!(result.best_move, Move::from_lan("h8g8").unwrap());
+        assert_eq!(result.get_mate_in(), Some(-1));
+    }
+
+    #[test]
+    fn test_material() {
+        // take the free queen
+        let result = search_fen("4k3/8/8/3q4/4P3/8/8/4K3 w - - 0 1", SearchLimits::depth(2));
+        assert_eq!(result.best_move, Move::from_lan("e4d5").unwrap());
+        // which leaves White a pawn up
+        assert!(result.score > 0);
+
+        // but not a pawn that's defended by one
+        let result = search_fen("4k3/8/2p5/3p4/8/8/8/3QK3 w - - 0 1", SearchLimits::depth(2));
+        assert_ne!(result.best_move, Move::from_lan("d1d5").unwrap());
+    }
+
+    #[test]
+    fn test_limits() {
+        let game = Game::new();
+        let mut engine = Engine::new(SearchLimits::nodes(2000));
+        let result = engine.search(&game).unwrap();
+        assert!(result.depth >= 2);
+        // the first iteration always finishes, so a tiny budget still gives a move
+        engine.set_limits(SearchLimits::nodes(1));
+        let result = engine.search(&game).unwrap();
+        assert_eq!(result.depth, 1);
+
+        // a finished game has no move to find
+        let mut game = Game::new();
+        for lan in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.make_move(Move::from_lan(lan).unwrap()).unwrap();
+        }
+        assert!(engine.search(&game).is_none());
+        assert_eq!(engine.iterate(&game).count(), 0);
+    }
+
+    #[test]
+    fn test_iterate() {
+        let game = Game::new();
+        let mut engine = Engine::new(SearchLimits::depth(3));
+        let depths: Vec<u8> = engine.iterate(&game).map(|result| result.depth).collect();
+        assert_eq!(depths, [1, 2, 3]);
+        let last = engine.iterate(&game).last().unwrap();
+        assert_eq!(engine.search(&game).unwrap().best_move, last.best_move);
+
+        // pausing repeats the last finished depth until the next one is done
+        let mut engine = Engine::new(SearchLimits::depth(4));
+        let results: Vec<SearchResult> = engine.iterate(&game).pausing_every(100).collect();
+        assert!(results.len() > 4);
+        assert_eq!(results.last().unwrap().depth, 4);
+        for pair in results.windows(2) {
+            assert!(pair[1].depth == pair[0].depth + 1 || pair[1] == pair[0]);
+        }
+    }
+
+    #[test]
+    fn test_variants() {
+        // the king of the hill wins by reaching the center
+        let game =
+            Game::from_variant_fen(Variant::KingOfTheHill, "7k/8/8/8/8/2K5/8/8 w - - 0 1").unwrap();
+        let result = Engine::default().search(&game).unwrap();
+        assert_eq!(result.best_move, Move::from_lan("c3d4").unwrap());
+        assert_eq!(result.get_mate_in(), Some(1));
+
+        // the engine plays the moves its game would accept
+        let mut game = Game::with_variant(Variant::Antichess);
+        let mut engine = Engine::new(SearchLimits::depth(2));
+        for _ in 0..6 {
+            let Some(result) = engine.search(&game) else {
+                break;
+            };
+            game.make_move(result.best_move).unwrap();
+        }
+        assert_eq!(game.get_current_player(), Color::White);
+    }
+
+    #[test]
+    fn test_variant_change() {
+        // the same position has the same key in every variant, so what was
+        // learned in one can't be used in another
+        let fen = "7k/7p/8/8/8/8/2K5/8 b - - 0 1";
+        let standard = Game::from_fen(fen).unwrap();
+        let king_of_the_hill = Game::from_variant_fen(Variant::KingOfTheHill, fen).unwrap();
+        let mut engine = Engine::new(SearchLimits::depth(5));
+        engine.search(&king_of_the_hill).unwrap();
+        let result = engine.search(&standard).unwrap();
+        assert_eq!(
+            result,
+            Engine::new(SearchLimits::depth(5))
+                .search(&standard)
+                .unwrap()
+        );
+        assert!(result.score > 0);
+    }
+}
@@ -0,0 +1,360 @@
+use std::fmt;
+
+use web_time::{Duration, Instant};
+
+use crate::{
+    game::Game,
+    moves::Move,
+    result::{EpdError, EpdErrorKind},
+};
+
+/// A position of a test suite in the Extended Position Description format:
+/// the first four fields of a FEN, followed by operations such as
+/// `bm Nf3; id "WAC.001";`. Best and avoided moves are given in SAN.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdRecord {
+    fen: String,
+    pub id: Option<String>,
+    /// Finding any of these solves the position.
+    pub best_moves: Vec<Move>,
+    /// Playing any of these fails the position.
+    pub avoid_moves: Vec<Move>,
+    /// The expected perft counts by depth, from the `D1`, `D2`, ... operations
+    /// of move generator suites.
+    pub perft: Vec<(usize, u64)>,
+    /// The other operations with their operands as written.
+    pub operations: Vec<(String, String)>,
+}
+
+impl EpdRecord {
+    pub fn parse(epd: &str) -> Result<Self, EpdErrorKind> {
+        let epd = epd.trim_start();
+        let mut fields = vec![];
+        let mut rest = epd;
+        for _ in 0..4 {
+            let (field, remainder) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+            if !field.is_empty() {
+                fields.push(field);
+            }
+            rest = remainder.trim_start();
+        }
+
+        let operations = Self::split_operations(rest)?;
+        let operand = |opcode: &str| {
+            operations
+                .iter()
+                .find(|(name, _)| name == opcode)
+                .map(|(_, operand)| operand.as_str())
+        };
+        let halfmove_clock = operand("hmvc").unwrap_or("0");
+        let move_number = operand("fmvn").unwrap_or("1");
+        let fen = format!("{} {halfmove_clock} {move_number}", fields.join(" "));
+        let game = Game::from_fen(&fen)?;
+
+        let mut record = Self {
+            fen,
+            id: None,
+            best_moves: vec![],
+            avoid_moves: vec![],
+            perft: vec![],
+            operations: vec![],
+        };
+        for (opcode, operand) in operations {
+            let parse_moves = || {
+                operand
+                    .split_whitespace()
+                    .map(|san| game.parse_san(san))
+                    .collect::<Result<Vec<_>, _>>()
+            };
+            match opcode.as_str() {
+                "bm" => record.best_moves = parse_moves()?,
+                "am" => record.avoid_moves = parse_moves()?,
+                "id" => record.id = Some(operand),
+                "hmvc" | "fmvn" => (),
+                _ => match opcode
+                    .strip_prefix('D')
+                    .and_then(|depth| depth.parse().ok())
+                {
+                    Some(depth) => {
+                        let nodes = operand
+                            .parse()
+                            .map_err(|_| EpdErrorKind::InvalidOperand(opcode.clone()))?;
+                        record.perft.push((depth, nodes));
+                    }
+                    None => record.operations.push((opcode, operand)),
+                },
+            }
+        }
+        Ok(record)
+    }
+
+    // Operations end with a semicolon, which may also be inside a quoted operand
+    fn split_operations(text: &str) -> Result<Vec<(String, String)>, EpdErrorKind> {
+        let mut operations = vec![];
+        let mut operation = String::new();
+        let mut in_string = false;
+        for c in text.chars() {
+            match c {
+                '"' => in_string = !in_string,
+                ';' if !in_string => {
+                    operations.push(operation.clone());
+                    operation.clear();
+                    continue;
+                }
+                _ => (),
+            }
+            operation.push(c);
+        }
+        if in_string {
+            return Err(EpdErrorKind::UnterminatedString);
+        }
+        operations.push(operation);
+
+        Ok(operations
+            .iter()
+            .map(|operation| operation.trim())
+            .filter(|operation| !operation.is_empty())
+            .map(|operation| {
+                let (opcode, operand) = operation
+                    .split_once(char::is_whitespace)
+                    .unwrap_or((operation, ""));
+                let operand = operand.trim();
+                let operand = operand
+                    .strip_prefix('"')
+                    .and_then(|operand| operand.strip_suffix('"'))
+                    .unwrap_or(operand);
+                (opcode.to_string(), operand.to_string())
+            })
+            .collect())
+    }
+
+    /// The position as a FEN, with the halfmove clock and move number from
+    /// the `hmvc` and `fmvn` operations.
+    pub fn get_fen(&self) -> &str {
+        &self.fen
+    }
+
+    pub fn get_game(&self) -> Game {
+        Game::from_fen(&self.fen).expect("the FEN was checked when parsing")
+    }
+
+    /// The name of the position in reports: its id, or else its FEN.
+    pub fn get_name(&self) -> &str {
+        self.id.as_deref().unwrap_or(&self.fen)
+    }
+
+    /// Whether playing `mv` solves the position. Positions with neither best
+    /// nor avoided moves can't be solved by a move.
+    pub fn is_solved_by(&self, mv: &Move) -> bool {
+        if self.best_moves.is_empty() && self.avoid_moves.is_empty() {
+            return false;
+        }
+        (self.best_moves.is_empty() || self.best_moves.contains(mv))
+            && !self.avoid_moves.contains(mv)
+    }
+}
+
+/// Reads a test suite with a position on each line. Empty lines and lines
+/// starting with `#` are skipped.
+pub fn read_epd(epd: &str) -> Result<Vec<EpdRecord>, EpdError> {
+    epd.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| EpdRecord::parse(line).map_err(|kind| EpdError { line: i + 1, kind }))
+        .collect()
+}
+
+/// How the runner did on one position of a suite.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EpdResult {
+    pub name: String,
+    /// The move that was played, for best-move suites.
+    pub best_move: Option<Move>,
+    pub solved: bool,
+    pub duration: Duration,
+}
+
+/// The results of running a suite.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct SuiteReport {
+    pub results: Vec<EpdResult>,
+}
+
+impl SuiteReport {
+    /// Adds the result of `best_move` on `record`, which took `duration` to find.
+    pub fn add_move(&mut self, record: &EpdRecord, best_move: Option<Move>, duration: Duration) {
+        self.results.push(EpdResult {
+            name: record.get_name().to_string(),
+            best_move,
+            solved: best_move.is_some_and(|mv| record.is_solved_by(&mv)),
+            duration,
+        });
+    }
+
+    pub fn get_solved_count(&self) -> usize {
+        self.results.iter().filter(|result| result.solved).count()
+    }
+
+    pub fn get_failed_count(&self) -> usize {
+        self.results.len() - self.get_solved_count()
+    }
+
+    pub fn get_total_duration(&self) -> Duration {
+        self.results.iter().map(|result| result.duration).sum()
+    }
+
+    pub fn get_failures(&self) -> impl Iterator<Item = &EpdResult> {
+        self.results.iter().filter(|result| !result.solved)
+    }
+}
+
+impl fmt::Display for SuiteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let total = self.get_total_duration();
+        let average = total / self.results.len().max(1) as u32;
+        write!(
+            f,
+            "{} of {} solved, {} failed in {:.2}s ({}ms per position)",
+            self.get_solved_count(),
+            self.results.len(),
+            self.get_failed_count(),
+            total.as_secs_f64(),
+            average.as_millis()
+        )
+    }
+}
+
+/// Runs a best-move suite, asking `solve` for the move to play in each
+/// position. Any search fits, such as `Engine::search`.
+pub fn run_suite(
+    records: &[EpdRecord],
+    mut solve: impl FnMut(&Game) -> Option<Move>,
+) -> SuiteReport {
+    let mut report = SuiteReport::default();
+    for record in records {
+        let game = record.get_game();
+        let start = Instant::now();
+        let best_move = solve(&game);
+        report.add_move(record, best_move, start.elapsed());
+    }
+    report
+}
+
+/// Checks the move generator against the perft counts of a suite. A position
+/// is solved when every count matches.
+pub fn run_perft_suite(records: &[EpdRecord]) -> SuiteReport {
+    let mut report = SuiteReport::default();
+    for record in records {
+        let game = record.get_game();
+        let start = Instant::now();
+        let solved = record
+            .perft
+            .iter()
+            .all(|&(depth, nodes)| game.perft(depth) == nodes);
+        report.results.push(EpdResult {
+            name: record.get_name().to_string(),
+            best_move: None,
+            solved,
+            duration: start.elapsed(),
+        });
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        engine::{Engine, SearchLimits},
+        position::Position,
+        result::SanError,
+    };
+
+    const SUITE: &str = r#"
+# two positions from Win At Chess
+2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - bm Qg6; id "WAC.001";
+r1b1kb1r/3q1ppp/pBp1pn2/8/Np3P2/5B2/PPP3PP/R2QK2R w KQkq - bm Bxc6; id "WAC.094";
+
+4k3/8/8/8/8/8/8/R3K3 w Q - am Ra8+ Kd2; c0 "comment; with a semicolon";
+"#;
+
+    #[test]
+    fn test_read_epd() {
+        let records = read_epd(SUITE).unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[0].id.as_deref(), Some("WAC.001"));
+        assert_eq!(
+            records[0].best_moves,
+            [Move::new(Position::new(6, 2), Position::new(6, 5))]
+        );
+        assert_eq!(
+            records[0].get_fen(),
+            "2rr3k/pp3pp1/1nnqbN1p/3pN3/2pP4/2P3Q1/PPB4P/R4RK1 w - - 0 1"
+        );
+        assert_eq!(records[1].get_name(), "WAC.094");
+
+        let avoid = &records[2];
+        assert_eq!(avoid.id, None);
+        assert_eq!(avoid.avoid_moves.len(), 2);
+        assert_eq!(
+            avoid.operations,
+            [("c0".to_string(), "comment; with a semicolon".to_string())]
+        );
+        assert!(!avoid.is_solved_by(&Move::new(Position::new(0, 0), Position::new(0, 7))));
+        assert!(avoid.is_solved_by(&Move::new(Position::new(4, 0), Position::new(5, 1))));
+    }
+
+    #[test]
+    fn test_epd_errors() {
+        assert_eq!(
+            read_epd("4k3/8/8/8/8/8/8/4K3 w - - id \"open").unwrap_err(),
+            EpdError {
+                line: 1,
+                kind: EpdErrorKind::UnterminatedString
+            }
+        );
+        let error = read_epd("\n4k3/8/8/8/8/8/8/4K3 w - - bm Qh5;").unwrap_err();
+        assert_eq!(error.line, 2);
+        assert_eq!(error.kind, SanError::Illegal("Qh5".into()).into());
+        assert_eq!(
+            EpdRecord::parse("4k3/8/8/8/8/8/8/4K3 w - - D1 many;"),
+            Err(EpdErrorKind::InvalidOperand("D1".into()))
+        );
+    }
+
+    #[test]
+    fn test_run_suite() {
+        let records = read_epd(SUITE).unwrap();
+        let mut engine = Engine::new(SearchLimits::depth(2));
+        let report = run_suite(&records[..1], |game| Some(engine.search(game)?.best_move));
+        assert_eq!(report.results.len(), 1);
+        assert_eq!(report.results[0].name, "WAC.001");
+
+        // a solver that always plays the first best move solves everything
+        let report = run_suite(&records[..2], |game| {
+            records
+                .iter()
+                .find(|record| record.get_fen() == game.get_fen_str())
+                .map(|record| record.best_moves[0])
+        });
+        assert_eq!(report.get_solved_count(), 2);
+        assert_eq!(report.get_failed_count(), 0);
+        assert!(report.to_string().starts_with("2 of 2 solved, 0 failed"));
+    }
+
+    #[test]
+    fn test_run_perft_suite() {
+        let records = read_epd(
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - ;D1 20 ;D2 400 ;D3 8902\n\
+             4k3/8/8/8/8/8/8/4K2R w K - ;D1 16",
+        )
+        .unwrap();
+        assert_eq!(records[0].perft, [(1, 20), (2, 400), (3, 8902)]);
+        let report = run_perft_suite(&records);
+        assert_eq!(report.get_solved_count(), 1);
+        assert_eq!(
+            report.get_failures().next().unwrap().name,
+            records[1].get_fen()
+        );
+    }
+}
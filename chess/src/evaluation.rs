@@ -0,0 +1,200 @@
+use crate::{board_state::BoardState, color::Color, piece::Piece, position::Position};
+
+// The tables below are laid out as seen from White's side of the board, with
+// the eighth rank first, and are mirrored for Black.
+type PieceSquareTable = [i32; 64];
+
+#[rustfmt::skip]
+const PAWN_TABLE: PieceSquareTable = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+    50, 50, 50, 50, 50, 50, 50, 50,
+    10, 10, 20, 30, 30, 20, 10, 10,
+     5,  5, 10, 25, 25, 10,  5,  5,
+     0,  0,  0, 20, 20,  0,  0,  0,
+     5, -5,-10,  0,  0,-10, -5,  5,
+     5, 10, 10,-20,-20, 10, 10,  5,
+     0,  0,  0,  0,  0,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const KNIGHT_TABLE: PieceSquareTable = [
+    -50,-40,-30,-30,-30,-30,-40,-50,
+    -40,-20,  0,  0,  0,  0,-20,-40,
+    -30,  0, 10, 15, 15, 10,  0,-30,
+    -30,  5, 15, 20, 20, 15,  5,-30,
+    -30,  0, 15, 20, 20, 15,  0,-30,
+    -30,  5, 10, 15, 15, 10,  5,-30,
+    -40,-20,  0,  5,  5,  0,-20,-40,
+    -50,-40,-30,-30,-30,-30,-40,-50,
+];
+
+#[rustfmt::skip]
+const BISHOP_TABLE: PieceSquareTable = [
+    -20,-10,-10,-10,-10,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5, 10, 10,  5,  0,-10,
+    -10,  5,  5, 10, 10,  5,  5,-10,
+    -10,  0, 10, 10, 10, 10,  0,-10,
+    -10, 10, 10, 10, 10, 10, 10,-10,
+    -10,  5,  0,  0,  0,  0,  5,-10,
+    -20,-10,-10,-10,-10,-10,-10,-20,
+];
+
+#[rustfmt::skip]
+const ROOK_TABLE: PieceSquareTable = [
+     0,  0,  0,  0,  0,  0,  0,  0,
+     5, 10, 10, 10, 10, 10, 10,  5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+    -5,  0,  0,  0,  0,  0,  0, -5,
+     0,  0,  0,  5,  5,  0,  0,  0,
+];
+
+#[rustfmt::skip]
+const QUEEN_TABLE: PieceSquareTable = [
+    -20,-10,-10, -5, -5,-10,-10,-20,
+    -10,  0,  0,  0,  0,  0,  0,-10,
+    -10,  0,  5,  5,  5,  5,  0,-10,
+     -5,  0,  5,  5,  5,  5,  0, -5,
+      0,  0,  5,  5,  5,  5,  0, -5,
+    -10,  5,  5,  5,  5,  5,  0,-10,
+    -10,  0,  5,  0,  0,  0,  0,-10,
+    -20,-10,-10, -5, -5,-10,-10,-20,
+];
+
+// The king hides behind its pawns while the queens and rooks are on the board...
+#[rustfmt::skip]
+const KING_MIDDLEGAME_TABLE: PieceSquareTable = [
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -30,-40,-40,-50,-50,-40,-40,-30,
+    -20,-30,-30,-40,-40,-30,-30,-20,
+    -10,-20,-20,-20,-20,-20,-20,-10,
+     20, 20,  0,  0,  0,  0, 20, 20,
+     20, 30, 10,  0,  0, 10, 30, 20,
+];
+
+// ...and walks to the center once they are gone
+#[rustfmt::skip]
+const KING_ENDGAME_TABLE: PieceSquareTable = [
+    -50,-40,-30,-20,-20,-30,-40,-50,
+    -30,-20,-10,  0,  0,-10,-20,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 30, 40, 40, 30,-10,-30,
+    -30,-10, 20, 30, 30, 20,-10,-30,
+    -30,-30,  0,  0,  0,  0,-30,-30,
+    -50,-30,-30,-30,-30,-30,-30,-50,
+];
+
+// The game phase is counted down from the pieces of the start position
+const MAX_PHASE: i32 = 24;
+
+pub(super) fn get_piece_value(piece: Piece) -> i32 {
+    match piece {
+        Piece::Pawn(..) => 100,
+        Piece::Knight(..) => 320,
+        Piece::Bishop(..) => 330,
+        Piece::Rook(..) => 500,
+        Piece::Queen(..) => 900,
+        Piece::King(..) => 0,
+    }
+}
+
+fn get_phase_weight(piece: Piece) -> i32 {
+    match piece {
+        Piece::Knight(..) | Piece::Bishop(..) => 1,
+        Piece::Rook(..) => 2,
+        Piece::Queen(..) => 4,
+        Piece::Pawn(..) | Piece::King(..) => 0,
+    }
+}
+
+fn get_table_index(piece: Piece, position: &Position) -> usize {
+    let rank = match piece.get_player() {
+        Color::White => 7 - position.y,
+        Color::Black => position.y,
+    };
+    rank * 8 + position.x
+}
+
+/// The score of the position in centipawns, from the point of view of the
+/// player to move. Only material and piece placement are counted, which is
+/// enough for the search to play sensible moves in every variant.
+pub(super) fn evaluate(state: &BoardState) -> i32 {
+    let mut score = 0;
+    let mut king_scores = [(0, 0); 2];
+    let mut phase = 0;
+    for square in 0..64 {
+        let position = Position::from_square(square);
+        let Some(piece) = state.get_piece(&position) else {
+            continue;
+        };
+        let index = get_table_index(piece, &position);
+        let sign = if piece.get_player() == state.player {
+            1
+        } else {
+            -1
+        };
+        phase += get_phase_weight(piece);
+        let value = match piece {
+            Piece::Pawn(..) => PAWN_TABLE[index],
+            Piece::Knight(..) => KNIGHT_TABLE[index],
+            Piece::Bishop(..) => BISHOP_TABLE[index],
+            Piece::Rook(..) => ROOK_TABLE[index],
+            Piece::Queen(..) => QUEEN_TABLE[index],
+            Piece::King(..) => {
+                king_scores[piece.get_player() as usize] =
+                    (KING_MIDDLEGAME_TABLE[index], KING_ENDGAME_TABLE[index]);
+                0
+            }
+        };
+        score += sign * (get_piece_value(piece) + value);
+    }
+
+    // The kings' placement is blended between the two tables by how much
+    // material is left
+    let phase = phase.min(MAX_PHASE);
+    let [player, opponent] = match state.player {
+        Color::White => [king_scores[0], king_scores[1]],
+        Color::Black => [king_scores[1], king_scores[0]],
+    };
+    let king_score = |(middlegame, endgame): (i32, i32)| {
+        (middlegame * phase + endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    };
+    score + king_score(player) - king_score(opponent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, variant::Variant};
+
+    fn evaluate_fen(fen: &str) -> i32 {
+        evaluate(&Fen::parse(fen, Variant::Standard).unwrap().state)
+    }
+
+    #[test]
+    fn test_evaluate() {
+        // the start position is balanced for either player
+        let start = BoardState::default();
+        assert_eq!(evaluate(&start), 0);
+        let mut black_to_move = start;
+        black_to_move.player = Color::Black;
+        assert_eq!(evaluate(&black_to_move), 0);
+
+        // an extra queen is worth about a queen to the side that has it
+        let white_up = evaluate_fen("4k3/8/8/8/8/8/8/3QK3 w - - 0 1");
+        assert!((850..950).contains(&white_up));
+        assert_eq!(evaluate_fen("4k3/8/8/8/8/8/8/3QK3 b - - 0 1"), -white_up);
+
+        // a centralized knight is better than one on the rim
+        assert!(
+            evaluate_fen("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1")
+                > evaluate_fen("4k3/8/8/8/N7/8/8/4K3 w - - 0 1")
+        );
+    }
+}
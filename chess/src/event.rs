@@ -0,0 +1,75 @@
+use std::sync::mpsc::Sender;
+
+use crate::{color::Color, game_status::GameStatus, moves::Move, piece::Piece};
+
+/// Something that happened to a `Game`, sent to its observers.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GameEvent {
+    /// A move was played. Moves that don't continue the game are in variations.
+    MoveMade {
+        piece: Piece,
+        mv: Move,
+        continues_game: bool,
+    },
+    StatusChanged {
+        from: GameStatus,
+        to: GameStatus,
+    },
+    /// The player ran out of time.
+    Flagged(Color),
+    DrawOffered(Color),
+    /// The position being looked at changed without a move being played, and
+    /// is now the one after `current_move`.
+    HistoryNavigated {
+        current_move: Option<Move>,
+    },
+}
+
+/// Identifies an observer of a game, to unsubscribe it later.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct SubscriptionId(usize);
+
+type Observer = Box<dyn FnMut(&GameEvent) + Send + Sync>;
+
+/// The observers of a game, called in the order they subscribed.
+#[derive(Default)]
+pub(super) struct Observers {
+    observers: Vec<(SubscriptionId, Observer)>,
+    next_id: usize,
+}
+
+/// A clone of a game starts without observers, so that trying moves on a copy
+/// doesn't reach the observers of the original.
+impl Clone for Observers {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl Observers {
+    pub(super) fn subscribe(&mut self, observer: Observer) -> SubscriptionId {
+        let id = SubscriptionId(self.next_id);
+        self.next_id += 1;
+        self.observers.push((id, observer));
+        id
+    }
+
+    pub(super) fn subscribe_channel(&mut self, sender: Sender<GameEvent>) -> SubscriptionId {
+        // a dropped receiver just doesn't get the events anymore
+        self.subscribe(Box::new(move |event| {
+            let _ = sender.send(*event);
+        }))
+    }
+
+    pub(super) fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        let count = self.observers.len();
+        self.observers.retain(|(other, _)| *other != id);
+        self.observers.len() != count
+    }
+
+    pub(super) fn notify(&mut self, event: GameEvent) {
+        for (_, observer) in &mut self.observers {
+            observer(&event);
+        }
+    }
+}
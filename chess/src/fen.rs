@@ -0,0 +1,301 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    board::Board,
+    board_state::BoardState,
+    castling_rights::CastlingRights,
+    color::Color,
+    piece::Piece,
+    position::Position,
+    result::FenError,
+    variant::{Variant, THREE_CHECK_CHECKS},
+};
+
+const MAX_FEN_STR: usize = 87;
+
+/// The fields of a FEN string, parsed into the types the `Game` is built from.
+pub(super) struct Fen {
+    pub(super) state: BoardState,
+    pub(super) fifty_move_count: u8,
+    pub(super) move_number: usize,
+}
+
+impl Fen {
+    /// Parses a FEN string for a game of `variant`. Three-check positions may
+    /// give the checks each player has left as an extra "3+3" field after the
+    /// en passant square.
+    pub(super) fn parse(fen: &str, variant: Variant) -> Result<Self, FenError> {
+        let mut fields: Vec<&str> = fen.split_whitespace().collect();
+        if fields.len() < 4 {
+            return Err(FenError::MissingFields(fields.len()));
+        }
+
+        let board = Self::parse_board(fields[0], variant)?;
+        if !variant.has_valid_kings(&board) {
+            return Err(FenError::InvalidKingCount);
+        }
+
+        let mut chars = fields[1].chars();
+        let player = match (chars.next(), chars.next()) {
+            (Some(c), None) => Color::from_fen_char(c).ok_or(FenError::InvalidPlayer)?,
+            _ => return Err(FenError::InvalidPlayer),
+        };
+
+        let castling_rights = CastlingRights::from_fen_str(fields[2], &board)
+            .ok_or(FenError::InvalidCastlingRights)?;
+
+        let en_passant_position = Self::parse_en_passant(fields[3], player, &board)?;
+
+        let remaining_checks = match fields.get(4) {
+            Some(field) if variant == Variant::ThreeCheck && field.contains('+') => {
+                let remaining_checks = Self::parse_remaining_checks(field)?;
+                fields.remove(4);
+                remaining_checks
+            }
+            _ => [THREE_CHECK_CHECKS; 2],
+        };
+
+        let fifty_move_count = fields
+            .get(4)
+            .map_or(Ok(0), |field| field.parse())
+            .map_err(|_| FenError::InvalidHalfmoveClock)?;
+
+        let move_number = fields
+            .get(5)
+            .map_or(Ok(1), |field| field.parse())
+            .ok()
+            .filter(|&n| n > 0)
+            .ok_or(FenError::InvalidMoveNumber)?;
+
+        Ok(Self {
+            state: BoardState::new(board, player, castling_rights, en_passant_position)
+                .with_variant(variant)
+                .with_remaining_checks(remaining_checks),
+            fifty_move_count,
+            move_number,
+        })
+    }
+
+    fn parse_board(placement: &str, variant: Variant) -> Result<Board, FenError> {
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount(ranks.len()));
+        }
+        let mut board = Board::empty();
+        for (i, rank) in ranks.into_iter().enumerate() {
+            let y = 7 - i;
+            let mut x = 0;
+            for c in rank.chars() {
+                if let Some(empty_count) = c.to_digit(10).filter(|n| (1..=8).contains(n)) {
+                    x += empty_count as usize;
+                } else {
+                    let piece = Piece::from_fen_char(c).ok_or(FenError::InvalidPiece(c))?;
+                    if piece.is_pawn() && !variant.allows_pawn_on(piece, y) {
+                        return Err(FenError::PawnOnBackRank);
+                    }
+                    if x < 8 {
+                        board.set_piece(&Position::new(x, y), Some(piece));
+                    }
+                    x += 1;
+                }
+            }
+            if x != 8 {
+                return Err(FenError::WrongRankLength(y + 1));
+            }
+        }
+        Ok(board)
+    }
+
+    fn parse_remaining_checks(field: &str) -> Result<[u8; 2], FenError> {
+        let (white, black) = field
+            .split_once('+')
+            .ok_or(FenError::InvalidRemainingChecks)?;
+        let parse = |checks: &str| {
+            checks
+                .parse()
+                .ok()
+                .filter(|&checks| checks <= THREE_CHECK_CHECKS)
+                .ok_or(FenError::InvalidRemainingChecks)
+        };
+        Ok([parse(white)?, parse(black)?])
+    }
+
+    // The square must be the one a pawn of the opponent just skipped over with
+    // its double step, so that capturing it takes that pawn
+    fn parse_en_passant(
+        field: &str,
+        player: Color,
+        board: &Board,
+    ) -> Result<Option<Position>, FenError> {
+        if field == "-" {
+            return Ok(None);
+        }
+        let position = Position::parse(field).ok_or(FenError::InvalidEnPassant)?;
+        let (expected_rank, pawn_rank, start_rank) = match player {
+            Color::White => (5, 4, 6),
+            Color::Black => (2, 3, 1),
+        };
+        let at_rank = |y| Position::new(position.x, y);
+        if position.y == expected_rank
+            && board.get_piece(&at_rank(pawn_rank)) == Some(Piece::Pawn(!player))
+            && board.get_piece(&position).is_none()
+            && board.get_piece(&at_rank(start_rank)).is_none()
+        {
+            Ok(Some(position))
+        } else {
+            Err(FenError::InvalidEnPassant)
+        }
+    }
+}
+
+impl FromStr for Fen {
+    type Err = FenError;
+
+    fn from_str(fen: &str) -> Result<Self, Self::Err> {
+        Self::parse(fen, Variant::Standard)
+    }
+}
+
+impl fmt::Display for Fen {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut fen = String::with_capacity(MAX_FEN_STR);
+        let mut empty_count = 0;
+        for y in (0..8).rev() {
+            for x in 0..8 {
+                if let Some(piece) = self.state.get_piece(&Position { x, y }) {
+                    if empty_count > 0 {
+                        fen.push_str(&empty_count.to_string());
+                        empty_count = 0;
+                    }
+                    fen.push(piece.get_fen_char());
+                } else {
+                    empty_count += 1;
+                }
+            }
+            if empty_count > 0 {
+                fen.push_str(&empty_count.to_string());
+                empty_count = 0;
+            }
+            if y > 0 {
+                fen.push('/');
+            }
+        }
+        write!(
+            f,
+            "{fen} {} {} {} ",
+            self.state.player.get_fen_char(),
+            self.state.castling_rights.get_fen_str(),
+            self.state
+                .en_passant_position
+                .map_or("-".to_string(), |pos| pos.to_string()),
+        )?;
+        if self.state.variant == Variant::ThreeCheck {
+            let [white, black] = self.state.remaining_checks;
+            write!(f, "{white}+{black} ")?;
+        }
+        write!(f, "{} {}", self.fifty_move_count, self.move_number)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_initial_position() {
+        let fen: Fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        assert!(fen.state == BoardState::default());
+        assert_eq!(fen.fifty_move_count, 0);
+        assert_eq!(fen.move_number, 1);
+    }
+
+    #[test]
+    fn test_parse_chess960_castling_rights() {
+        let shredder: Fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+            .parse()
+            .unwrap();
+        assert!(shredder.state == BoardState::chess960(0));
+        // X-FEN's KQkq castle with the outermost rooks
+        let x_fen: Fen = "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w KQkq - 0 1"
+            .parse()
+            .unwrap();
+        assert!(x_fen.state == shredder.state);
+        assert_eq!(x_fen.state.castling_rights.get_fen_str(), "HFhf");
+
+        // castling rights written with files make any position a Chess960 one,
+        // even the standard start position, which is also Chess960 position 518
+        let standard: Fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w HAha - 0 1"
+            .parse()
+            .unwrap();
+        assert!(standard.state.castling_rights.is_chess960());
+        assert_eq!(standard.state.castling_rights.get_fen_str(), "HAha");
+    }
+
+    #[test]
+    fn test_variant_fen() {
+        let fen = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 2+3 0 1";
+        let three_check = Fen::parse(fen, Variant::ThreeCheck).unwrap();
+        assert_eq!(three_check.state.remaining_checks, [2, 3]);
+        assert_eq!(three_check.to_string(), fen);
+        assert_eq!(
+            Fen::parse(fen, Variant::Standard).err(),
+            Some(FenError::InvalidHalfmoveClock)
+        );
+        assert_eq!(
+            Fen::parse(
+                "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 4+3 0 1",
+                Variant::ThreeCheck
+            )
+            .err(),
+            Some(FenError::InvalidRemainingChecks)
+        );
+
+        // Horde has no white king and pawns on the first rank
+        let horde = "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+        assert!(Fen::parse(horde, Variant::Horde).is_ok());
+        assert_eq!(
+            Fen::parse(horde, Variant::Standard).err(),
+            Some(FenError::PawnOnBackRank)
+        );
+        // Antichess allows any number of kings
+        assert!(Fen::parse("8/8/8/8/8/8/8/KK6 w - - 0 1", Variant::Antichess).is_ok());
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let cases = [
+            ("8/8/8/8/8/8/8/8 w -", FenError::MissingFields(3)),
+            ("8/8/8/8/8/8/8 w - -", FenError::WrongRankCount(7)),
+            ("4k3/8/8/8/8/8/8/4K4 w - -", FenError::WrongRankLength(1)),
+            ("4k3/8/8/8/8/8/8/4X3 w - -", FenError::InvalidPiece('X')),
+            ("4k3/8/8/8/8/8/8/8 w - -", FenError::InvalidKingCount),
+            ("4k3/8/8/8/8/8/8/3PK3 w - -", FenError::PawnOnBackRank),
+            ("4k3/8/8/8/8/8/8/4K3 x - -", FenError::InvalidPlayer),
+            ("4k3/8/8/8/8/8/8/4K3 w X -", FenError::InvalidCastlingRights),
+            ("4k3/8/8/8/8/8/8/4K3 w - e3", FenError::InvalidEnPassant),
+            // no pawn made the double step it names
+            (
+                "4k3/8/8/4P3/8/8/8/4K3 w - d6 0 1",
+                FenError::InvalidEnPassant,
+            ),
+            (
+                "4k3/3p4/8/3pP3/8/8/8/4K3 w - d6 0 1",
+                FenError::InvalidEnPassant,
+            ),
+            (
+                "4k3/8/3n4/3pP3/8/8/8/4K3 w - d6 0 1",
+                FenError::InvalidEnPassant,
+            ),
+            (
+                "4k3/8/8/8/8/8/8/4K3 w - - x",
+                FenError::InvalidHalfmoveClock,
+            ),
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 0", FenError::InvalidMoveNumber),
+        ];
+        for (fen, error) in cases {
+            assert_eq!(fen.parse::<Fen>().err(), Some(error), "{fen}");
+        }
+    }
+}
@@ -1,31 +1,52 @@
-use std::collections::HashSet;
+use std::{
+    collections::HashSet,
+    sync::{mpsc, Arc},
+};
 
+use serde::{Deserialize, Serialize};
 use web_time::Duration;
 
 use crate::{
+    annotation::Annotation,
+    board::CHESS960_POSITIONS,
     board_state::BoardState,
-    castling_rights::{CastlingRights, CastlingRightsKind},
+    clock::Clock,
     color::Color,
-    displacement::Displacement,
+    event::{GameEvent, Observers, SubscriptionId},
+    fen::Fen,
     game_status::{DrawKind, GameStatus},
     history::History,
+    movegen,
     moves::Move,
+    perft,
+    pgn::{self, PgnTags},
     piece::Piece,
     position::Position,
-    result::{ChessError, ChessResult},
+    result::{ChessError, ChessResult, FenError, PgnError, SanError, SaveError},
     round_info::RoundInfo,
-    timer::{Timer, DEFAULT_DURATION},
+    san::{Notation, San},
+    save::{save_line, SavedGame, SavedGameV1, SavedMove},
+    snapshot::PositionSnapshot,
+    time_control::TimeControl,
+    timer::Timer,
     turn::Turn,
+    variant::Variant,
 };
 
-const MAX_FEN_STR: usize = 87;
-
-#[derive(Clone)]
+/// Games are saved as their moves and clocks, in a versioned format that later
+/// versions of the crate can still read. Everything else is rebuilt on load.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(into = "SavedGame", try_from = "SavedGame")]
 pub struct Game {
     valid_moves: HashSet<Move>,
     pub(super) status: GameStatus,
     history: History,
     timer: Timer,
+    // a draw counts as a win for Black
+    armageddon: bool,
+    // the player whose draw offer is waiting for an answer
+    draw_offer: Option<Color>,
+    observers: Observers,
 }
 
 impl Default for Game {
@@ -43,12 +64,117 @@ impl Game {
         GameBuilder::new()
     }
 
-    pub fn with_start_time(start_time: Duration) -> Self {
-        GameBuilder::new().start_time(start_time).build()
+    /// Starts a game on the clock, from a plain starting time or a full `TimeControl`.
+    pub fn with_start_time(time_control: impl Into<TimeControl>) -> Self {
+        let time_control = time_control.into();
+        GameBuilder::new()
+            .time_controls(time_control.clone(), time_control)
+            .build()
+    }
+
+    /// Puts a game that hasn't started yet on a different clock.
+    pub fn with_time_control(self, time_control: impl Into<TimeControl>) -> Self {
+        let time_control = time_control.into();
+        self.with_time_controls(time_control.clone(), time_control)
+    }
+
+    /// Like `with_time_control`, but gives each player their own time, for
+    /// handicap games such as 5 minutes against 2.
+    pub fn with_time_controls(
+        mut self,
+        white: impl Into<TimeControl>,
+        black: impl Into<TimeControl>,
+    ) -> Self {
+        let clock = self.timer.get_clock();
+        self.timer =
+            Timer::with_time_controls([white.into(), black.into()], self.get_real_player());
+        self.timer.set_clock(clock);
+        self
+    }
+
+    /// Makes a draw count as a win for Black. White usually gets more time to
+    /// make up for it.
+    pub fn with_armageddon(mut self) -> Self {
+        self.armageddon = true;
+        self
+    }
+
+    pub fn is_armageddon(&self) -> bool {
+        self.armageddon
+    }
+
+    /// Reads the time from `clock` instead of the system clock, so that a
+    /// `ManualClock` can decide exactly how long each move took.
+    pub fn with_clock(mut self, clock: impl Clock + 'static) -> Self {
+        self.timer.set_clock(Arc::new(clock));
+        self
     }
 
-    fn with_state(state: BoardState) -> Self {
-        Self::builder().state(state).build()
+    /// Starts a Chess960 game from the start position with the given number, from 0 to 959.
+    pub fn chess960(index: usize) -> Result<Self, ChessError> {
+        if index >= CHESS960_POSITIONS {
+            return Err(ChessError::InvalidChess960Index(index));
+        }
+        Ok(Self::builder().state(BoardState::chess960(index)).build())
+    }
+
+    /// Calls `observer` with each event of the game, right after it happens.
+    /// Clones of the game don't keep their observers.
+    pub fn subscribe(
+        &mut self,
+        observer: impl FnMut(&GameEvent) + Send + Sync + 'static,
+    ) -> SubscriptionId {
+        self.observers.subscribe(Box::new(observer))
+    }
+
+    /// Sends each event of the game to the returned receiver, for observers
+    /// on other threads.
+    pub fn subscribe_channel(&mut self) -> (SubscriptionId, mpsc::Receiver<GameEvent>) {
+        let (sender, receiver) = mpsc::channel();
+        (self.observers.subscribe_channel(sender), receiver)
+    }
+
+    /// Stops sending events to an observer, returning whether it was subscribed.
+    pub fn unsubscribe(&mut self, id: SubscriptionId) -> bool {
+        self.observers.unsubscribe(id)
+    }
+
+    pub fn is_chess960(&self) -> bool {
+        self.get_current_state().castling_rights.is_chess960()
+    }
+
+    pub fn with_variant(variant: Variant) -> Self {
+        Self::builder().state(variant.get_start_state()).build()
+    }
+
+    pub fn get_variant(&self) -> Variant {
+        self.get_current_state().variant
+    }
+
+    pub fn from_fen(fen: &str) -> Result<Self, FenError> {
+        Self::from_variant_fen(Variant::Standard, fen)
+    }
+
+    pub fn from_variant_fen(variant: Variant, fen: &str) -> Result<Self, FenError> {
+        let Fen {
+            state,
+            fifty_move_count,
+            move_number,
+        } = Fen::parse(fen, variant)?;
+
+        let mut opponent_state = state;
+        opponent_state.player = !opponent_state.player;
+        if variant.is_king_under_attack(&opponent_state) {
+            return Err(FenError::OpponentInCheck);
+        }
+
+        let mut game = Self::builder()
+            .state(state)
+            .fifty_move_count(fifty_move_count)
+            .move_number(move_number)
+            .build();
+        game.update_status();
+        Ok(game)
     }
 
     pub fn is_replaying(&self) -> bool {
@@ -67,44 +193,85 @@ impl Game {
         self.get_current_state().get_piece(position)
     }
 
-    fn get_current_state(&self) -> &BoardState {
+    pub(super) fn get_current_state(&self) -> &BoardState {
         self.history.get_current_state()
     }
 
-    pub fn get_current_player(&self) -> Color {
-        self.get_current_state().player
+    pub(super) fn get_halfmove_clock(&self) -> u8 {
+        self.history
+            .get_halfmove_clock(self.history.get_current_path())
     }
 
-    fn is_piece_some(&self, at: &Position) -> ChessResult {
-        self.get_current_state().is_piece_some(at)
+    pub(super) fn get_repetition_keys(&self) -> Vec<u64> {
+        self.history
+            .get_repetition_keys(self.history.get_current_path())
     }
 
-    pub(super) fn has_piece(&self, position: &Position) -> bool {
-        self.get_current_state().has_piece(position)
+    /// The moves leading to the position being looked at, each with the
+    /// position it was played from.
+    pub(super) fn get_played_moves(&self) -> Vec<(&BoardState, Move)> {
+        let turns = self.history.get_turns(self.history.get_current_path());
+        // each move is played from the position the one before it reached
+        let states = std::iter::once(self.history.get_board_state(&[]))
+            .chain(turns.iter().map(|turn| &turn.board_state));
+        states.zip(turns.iter().map(|turn| turn.mv)).collect()
     }
 
-    fn piece_can_snipe(&self, at: &Position) -> bool {
-        self.get_piece(at).map_or(false, |piece| piece.can_snipe())
+    /// The Zobrist key of the current position. Positions that are the same for
+    /// the repetition rule share a key.
+    pub fn get_zobrist_key(&self) -> u64 {
+        self.get_current_state().get_zobrist_key()
     }
 
-    fn has_castling_right(&self, right: CastlingRightsKind) -> bool {
-        self.get_current_state()
-            .castling_rights
-            .has_castling_right(right)
+    pub fn get_current_player(&self) -> Color {
+        self.get_current_state().player
+    }
+
+    fn is_piece_some(&self, at: &Position) -> ChessResult {
+        self.get_current_state().is_piece_some(at)
     }
 
     pub fn get_valid_destinations_for_piece(&self, position: &Position) -> Vec<Position> {
+        // promotions share a destination, so collect into a set first
         self.valid_moves
             .iter()
             .filter(|mv| mv.from == *position)
             .map(|mv| mv.to)
+            .collect::<HashSet<Position>>()
+            .into_iter()
             .collect()
     }
 
+    pub fn is_promotion_move(&self, mv: &Move) -> bool {
+        self.get_piece(&mv.from)
+            .is_some_and(|piece| piece.is_pawn() && (mv.to.y == 0 || mv.to.y == 7))
+    }
+
+    // Moves without a promotion piece promote to a queen
+    fn with_default_promotion(&self, mut mv: Move) -> Move {
+        if mv.promotion.is_none() && self.is_promotion_move(&mv) {
+            mv.promotion = Some(Piece::Queen(self.get_current_player()));
+        }
+        mv
+    }
+
     fn navigate_history(&mut self, navigate: impl FnOnce(&mut History)) {
-        navigate(&mut self.history);
+        self.try_navigate_history(|history| {
+            navigate(history);
+            true
+        });
+    }
+
+    // Observers only hear about it if `navigate` returns that it moved
+    fn try_navigate_history(&mut self, navigate: impl FnOnce(&mut History) -> bool) -> bool {
+        if !navigate(&mut self.history) {
+            return false;
+        }
         self.add_moves();
-        self.remove_self_checks();
+        self.observers.notify(GameEvent::HistoryNavigated {
+            current_move: self.get_current_move(),
+        });
+        true
     }
 
     pub fn go_back_a_move(&mut self) {
@@ -123,238 +290,315 @@ impl Game {
         self.navigate_history(|history| history.resume());
     }
 
-    pub fn move_piece(&mut self, from: Position, to: Position) -> ChessResult {
-        if let Some(piece) = self.get_piece(&from) {
-            let mv = Move::new(from, to);
-            self.is_move_valid(&mv)?;
-            let mut next_state = *self.get_current_state();
-            next_state.move_piece(&mv);
-            self.history.add_info(next_state, mv);
+    /// The moves played from the current position, the one continuing its line first.
+    pub fn get_next_moves(&self) -> Vec<Move> {
+        self.history
+            .get_children(self.history.get_current_path())
+            .iter()
+            .map(|node| node.turn.mv)
+            .collect()
+    }
 
-            log::info!("{} : {}", piece, mv);
-            self.update();
+    /// Follows the `index`th of the moves given by `get_next_moves`.
+    pub fn go_to_variation(&mut self, index: usize) -> ChessResult {
+        if !self.try_navigate_history(|history| history.go_to_variation(index)) {
+            return Err(ChessError::OutOfBounds);
         }
         Ok(())
     }
 
-    fn update(&mut self) {
-        self.add_moves();
-        self.remove_self_checks();
-        self.update_status();
-        self.history.update_status(self.status);
-        self.update_timer();
+    pub fn is_on_main_line(&self) -> bool {
+        self.history.is_on_main_line()
     }
 
-    fn update_timer(&mut self) {
-        if self.status.is_game_over() {
-            self.timer.stop();
-            return;
+    // The main line is the game's record, so it can only change once the game
+    // is over or before it started
+    fn change_main_line(&mut self, change: impl FnOnce(&mut History)) -> ChessResult {
+        if self.timer.is_active() {
+            return Err(ChessError::GameInProgress);
         }
-        if !self.timer.is_active() {
-            self.timer.start()
+        self.navigate_history(change);
+        if !self.status.is_decided_by_players() {
+            self.update_status();
         }
-        self.timer.print();
-        self.timer.next_player();
+        Ok(())
     }
 
-    fn check_for_draw(&mut self) -> bool {
-        if self.history.get_fifty_move_count() == 50 {
-            self.status
-                .update(GameStatus::Draw(DrawKind::FiftyMoveRule));
-            return true;
-        }
-        if self.history.get_real_state_repetition_count() == 3 {
-            self.status.update(GameStatus::Draw(DrawKind::Repetition));
-            return true;
+    /// Makes the line through the current position the main line.
+    pub fn promote_to_main_line(&mut self) -> ChessResult {
+        if self.is_on_main_line() {
+            return Ok(());
         }
-        if self.history.get_real_state().has_insufficient_material() {
-            self.status
-                .update(GameStatus::Draw(DrawKind::InsufficientMaterial));
-            return true;
+        self.change_main_line(|history| history.promote_to_main_line())
+    }
+
+    /// Deletes the current move and everything played after it. Variations can
+    /// be deleted at any time, the main line only when the game isn't running.
+    pub fn delete_current_move(&mut self) -> ChessResult {
+        if self.is_on_main_line() {
+            self.change_main_line(|history| history.delete_current_move())
+        } else {
+            self.navigate_history(|history| history.delete_current_move());
+            Ok(())
         }
-        false
     }
 
-    fn update_status(&mut self) {
-        if self.get_active_time().is_zero() {
-            self.status
-                .update(GameStatus::Timeout(self.get_real_player()));
-            return;
+    pub(super) fn get_current_path(&self) -> Vec<usize> {
+        self.history.get_current_path().to_vec()
+    }
+
+    pub(super) fn go_to_path(&mut self, path: Vec<usize>) {
+        self.navigate_history(|history| history.go_to_path(path));
+    }
+
+    pub fn move_piece(&mut self, from: Position, to: Position) -> ChessResult {
+        self.make_move(Move::new(from, to))
+    }
+
+    /// Plays a move from the current position. At the end of the main line the
+    /// move continues the game; anywhere else it starts or follows a variation,
+    /// which leaves the game, its status and its clocks alone.
+    pub fn make_move(&mut self, mv: Move) -> ChessResult {
+        let mv = self.with_default_promotion(mv);
+        if let Some(piece) = self.get_piece(&mv.from) {
+            self.is_move_valid(&mv)?;
+            let continues_game = !self.is_replaying();
+            // moving instead of accepting declines the opponent's offer
+            if continues_game && self.draw_offer == Some(!self.get_current_player()) {
+                self.draw_offer = None;
+            }
+            self.add_to_history(mv);
+            self.observers.notify(GameEvent::MoveMade {
+                piece,
+                mv,
+                continues_game,
+            });
+            if continues_game {
+                self.update_status();
+                self.update_timer();
+            }
         }
-        if self.check_for_draw() {
-            return;
+        Ok(())
+    }
+
+    /// Plays a move of a game being read, which only adds it to the history.
+    /// `finish_replay` settles the status once every move is in.
+    pub(super) fn replay_move(&mut self, mv: Move) -> ChessResult {
+        if !self.valid_moves.contains(&mv) {
+            return Err(ChessError::InvalidMove);
         }
-        let king_is_under_attack = Self::is_king_under_attack(
-            &self
-                .history
-                .get_real_turn()
-                .unwrap_or(&Turn::default())
-                .board_state,
-        );
-        let valid_moves_is_empty = self.valid_moves.is_empty();
+        self.add_to_history(mv);
+        Ok(())
+    }
 
-        if !king_is_under_attack && valid_moves_is_empty {
-            self.status.update(GameStatus::Draw(DrawKind::Stalemate))
-        } else if king_is_under_attack && valid_moves_is_empty {
-            self.status
-                .update(GameStatus::Checkmate(self.get_real_player()))
-        } else if king_is_under_attack {
-            self.status
-                .update(GameStatus::Check(self.get_real_player()))
-        } else if !self.history.turns.is_empty() {
-            self.status.update(GameStatus::Ongoing)
+    /// Gives a game read from a file the status of its final position, or else
+    /// `result`, the status it ended with according to the file.
+    pub(super) fn finish_replay(&mut self, result: Option<GameStatus>) {
+        self.update_status();
+        if let Some(status) = result.filter(|_| !self.status.is_game_over()) {
+            self.set_status(status);
+            self.end();
         }
     }
 
-    fn is_attacking_king(&self) -> bool {
-        self.valid_moves
-            .iter()
-            .any(|mv| self.get_piece(&mv.to) == Some(Piece::King(!self.get_current_player())))
+    // Plays a legal move on the board without touching the game's status or clocks
+    fn add_to_history(&mut self, mv: Move) {
+        let san = San::new(self.get_current_state(), &mv, &self.valid_moves);
+        let mut next_state = *self.get_current_state();
+        next_state.move_piece(&mv);
+        self.history.add_info(next_state, mv, san);
+        self.add_moves();
+        let status = self.get_position_status(self.history.get_current_path());
+        self.history.update_status(status);
     }
 
-    fn is_king_under_attack(board_state: &BoardState) -> bool {
-        let mut enemy_board = *board_state;
-        enemy_board.player = !enemy_board.player;
-        Self::with_state(enemy_board).is_attacking_king()
+    pub(super) fn to_saved(&self) -> SavedGame {
+        SavedGame::V1(SavedGameV1 {
+            variant: self.get_variant(),
+            initial_fen: self.history.get_initial_fen().to_string(),
+            moves: save_line(self.history.get_children(&[])),
+            current_path: self.get_current_path(),
+            status: self.status,
+            armageddon: self.armageddon,
+            draw_offer: self.draw_offer,
+            timer: self.timer.to_saved(),
+        })
     }
 
-    fn moves_into_check(mut board_state: BoardState, mv: &Move) -> bool {
-        board_state.move_piece(mv);
-        Self::with_state(board_state).is_attacking_king()
+    pub(super) fn from_saved(saved: SavedGame) -> Result<Self, SaveError> {
+        let SavedGame::V1(saved) = saved;
+        let mut game = Self::from_variant_fen(saved.variant, &saved.initial_fen)?;
+        game.armageddon = saved.armageddon;
+        game.load_line(&saved.moves)?;
+        if !game.history.contains_path(&saved.current_path) {
+            return Err(SaveError::InvalidPath);
+        }
+        game.go_to_path(saved.current_path);
+        game.status = saved.status;
+        game.draw_offer = saved.draw_offer;
+        game.timer = Timer::from_saved(saved.timer);
+        Ok(game)
     }
 
-    fn remove_self_checks(&mut self) {
-        let current_state = *self.get_current_state();
-        self.valid_moves
-            .retain(|mv| !Self::moves_into_check(current_state, mv))
+    // Replays a saved line and its variations from the current position
+    fn load_line(&mut self, line: &[SavedMove]) -> Result<(), SaveError> {
+        for saved in line {
+            let path = self.get_current_path();
+            if !self.valid_moves.contains(&saved.mv) {
+                return Err(SaveError::IllegalMove(saved.mv));
+            }
+            self.add_to_history(saved.mv);
+            *self.get_annotation_mut().unwrap() = saved.annotation.clone();
+            let next_path = self.get_current_path();
+            for variation in &saved.variations {
+                self.go_to_path(path.clone());
+                self.load_line(variation)?;
+            }
+            self.go_to_path(next_path);
+        }
+        Ok(())
     }
 
-    pub fn is_move_valid(&self, mv: &Move) -> ChessResult {
-        if self.get_active_time().is_zero() {
-            return Err(ChessError::Timeout);
+    fn update_timer(&mut self) {
+        if self.status.is_game_over() {
+            self.timer.stop();
+            return;
         }
-        if matches!(self.status, GameStatus::Draw(..)) {
-            return Err(ChessError::GameIsInDraw);
+        if !self.timer.is_active() {
+            self.timer.start()
         }
-        BoardState::is_in_bounds(&mv.from)?;
-        BoardState::is_in_bounds(&mv.to)?;
-        self.is_piece_some(&mv.from)?;
-        if self.valid_moves.contains(mv) {
-            Ok(())
+        let move_number = self
+            .history
+            .get_move_number(self.history.get_current_turn_index() - 1);
+        self.timer.next_player(move_number);
+    }
+
+    fn get_draw_status(&self, kind: DrawKind) -> GameStatus {
+        if self.armageddon {
+            GameStatus::Armageddon(kind)
         } else {
-            Err(ChessError::InvalidMove)
+            GameStatus::Draw(kind)
         }
     }
 
-    fn add_pawn_advance_moves(&mut self, from: &Position) {
-        let v = Displacement::get_pawn_advance_vector(self.get_current_player());
-        let mut to = *from + v;
-        if BoardState::is_in_bounds(&to).is_ok() && self.get_piece(&to).is_none() {
-            self.valid_moves.insert(Move::new(*from, to));
-            to += v;
-            if self.can_double_move(from) && self.get_piece(&to).is_none() {
-                self.valid_moves.insert(Move::new(*from, to));
-            }
-        }
+    fn draw(&mut self, kind: DrawKind) {
+        self.set_status(self.get_draw_status(kind))
     }
 
-    fn can_double_move(&self, from: &Position) -> bool {
-        if let Some(Piece::Pawn(player)) = self.get_piece(from) {
-            return match player {
-                Color::White => from.y == 1,
-                Color::Black => from.y == 6,
-            };
+    // Draws that end the game at the end of `path` without either player claiming them
+    fn get_automatic_draw(&self, path: &[usize]) -> Option<DrawKind> {
+        let state = self.history.get_board_state(path);
+        if self.history.get_fifty_move_count(path) >= 75 {
+            Some(DrawKind::SeventyFiveMoveRule)
+        } else if self.history.get_repetition_count(path) >= 5 {
+            Some(DrawKind::FivefoldRepetition)
+        } else if state.variant.has_insufficient_material(state) {
+            Some(DrawKind::InsufficientMaterial)
+        } else {
+            None
         }
-        false
     }
 
-    fn add_pawn_capture_moves(&mut self, from: &Position) {
-        for &v in Displacement::get_pawn_capture_vectors(self.get_current_player()) {
-            let to = *from + v;
-            if BoardState::is_in_bounds(&to).is_ok() {
-                if let Some(piece) = self.get_piece(&to) {
-                    if piece.get_player() != self.get_current_player() {
-                        self.valid_moves.insert(Move::new(*from, to));
-                    }
-                }
-                if Some(to) == self.get_current_state().en_passant_position {
-                    self.valid_moves.insert(Move::new(*from, to));
-                }
-            }
+    // The status the rules give the position at the end of `path`, leaving out
+    // the clocks and anything the players decide
+    fn get_position_status(&self, path: &[usize]) -> GameStatus {
+        let state = self.history.get_board_state(path);
+        let player = state.player;
+        // a variant's own ending decides the game before any draw can
+        if let Some(loser) = state.variant.get_loser(state) {
+            return GameStatus::VariantEnd(loser);
+        }
+        let king_is_under_attack = state.variant.is_king_under_attack(state);
+        let valid_moves_is_empty = movegen::get_legal_moves(state).is_empty();
+        // a checkmate on the move that reached an automatic draw still counts
+        if king_is_under_attack && valid_moves_is_empty {
+            return GameStatus::Checkmate(player);
+        }
+        if let Some(kind) = self.get_automatic_draw(path) {
+            return self.get_draw_status(kind);
         }
-    }
 
-    fn add_moves_for_piece(&mut self, from: &Position) {
-        if let Some(piece) = self.get_piece(from) {
-            if piece.get_player() == self.get_current_player() {
-                if piece.is_pawn() {
-                    self.add_pawn_advance_moves(from);
-                    self.add_pawn_capture_moves(from);
-                } else {
-                    for &v in piece.get_vectors() {
-                        let mut to = *from + v;
-                        while BoardState::is_in_bounds(&to).is_ok() {
-                            if let Some(piece) = self.get_piece(&to) {
-                                if piece.get_player() != self.get_current_player() {
-                                    self.valid_moves.insert(Move::new(*from, to));
-                                }
-                                break;
-                            }
-                            self.valid_moves.insert(Move::new(*from, to));
-                            if !self.piece_can_snipe(from) {
-                                break;
-                            }
-                            to += v;
-                        }
-                    }
-                }
-            }
+        if !king_is_under_attack && valid_moves_is_empty && state.variant.stalemate_loses() {
+            GameStatus::VariantEnd(!player)
+        } else if valid_moves_is_empty {
+            self.get_draw_status(DrawKind::Stalemate)
+        } else if king_is_under_attack {
+            GameStatus::Check(player)
+        } else if !path.is_empty() {
+            GameStatus::Ongoing
+        } else {
+            GameStatus::NotStarted
         }
     }
 
-    fn add_moves(&mut self) {
-        self.valid_moves.clear();
-        for y in 0..8 {
-            for x in 0..8 {
-                self.add_moves_for_piece(&Position::new(x, y))
+    fn update_status(&mut self) {
+        let state = *self.history.get_real_state();
+        if self.get_active_time().is_zero() {
+            let player = self.get_real_player();
+            let status = if state.variant.has_insufficient_material_for(&state, !player) {
+                self.get_draw_status(DrawKind::TimeoutVsInsufficientMaterial)
+            } else {
+                GameStatus::Timeout(player)
+            };
+            if self.status != status {
+                self.observers.notify(GameEvent::Flagged(player));
+                self.set_status(status);
             }
+            return;
         }
-        self.add_castling_moves()
+        let status = self.get_position_status(&self.history.get_real_path());
+        self.set_status(status);
     }
 
-    fn add_castling_moves(&mut self) {
-        let (king_square, kingside, queenside) =
-            CastlingRights::get_castling_info(self.get_current_player());
-
-        if self.has_castling_right(kingside)
-            && !Self::moves_into_check(
-                *self.get_current_state(),
-                &Move::new(king_square, king_square + Displacement::RIGHT),
-            )
-            && !(1..=2).any(|i| self.has_piece(&(king_square + Displacement::RIGHT * i)))
-        {
-            self.valid_moves.insert(Move::new(
-                king_square,
-                king_square + Displacement::RIGHT * 2,
-            ));
+    fn set_status(&mut self, status: GameStatus) {
+        if self.status != status {
+            let from = std::mem::replace(&mut self.status, status);
+            self.observers
+                .notify(GameEvent::StatusChanged { from, to: status });
         }
+    }
 
-        if self.has_castling_right(queenside)
-            && !Self::moves_into_check(
-                *self.get_current_state(),
-                &Move::new(king_square, king_square + Displacement::LEFT),
-            )
-            && !(1..=3).any(|i| self.has_piece(&(king_square + Displacement::LEFT * i)))
-        {
-            self.valid_moves
-                .insert(Move::new(king_square, king_square + Displacement::LEFT * 2));
+    /// Checks that `mv` can be played from the current position. Only moves that
+    /// continue the game need it to be still going.
+    pub fn is_move_valid(&self, mv: &Move) -> ChessResult {
+        if !self.is_replaying() {
+            if self.get_active_time().is_zero() {
+                return Err(ChessError::Timeout);
+            }
+            if matches!(
+                self.status,
+                GameStatus::Draw(..) | GameStatus::Armageddon(..)
+            ) {
+                return Err(ChessError::GameIsInDraw);
+            }
+            if self.status.is_game_over() {
+                return Err(ChessError::GameIsOver);
+            }
+        }
+        BoardState::is_in_bounds(&mv.from)?;
+        BoardState::is_in_bounds(&mv.to)?;
+        self.is_piece_some(&mv.from)?;
+        if self.valid_moves.contains(&self.with_default_promotion(*mv)) {
+            Ok(())
+        } else {
+            Err(ChessError::InvalidMove)
         }
     }
 
+    fn add_moves(&mut self) {
+        self.valid_moves = movegen::get_legal_moves(self.get_current_state())
+            .into_iter()
+            .collect();
+    }
+
     pub fn get_active_time(&self) -> Duration {
         self.timer.get_active_time()
     }
 
+    pub fn get_time_control(&self, player: Color) -> &TimeControl {
+        self.timer.get_time_control(player)
+    }
+
     pub fn is_timer_active(&self) -> bool {
         self.timer.is_active()
     }
@@ -376,15 +620,24 @@ impl Game {
         pieces
     }
 
-    pub fn get_rounds_info(&self) -> Vec<RoundInfo> {
-        self.history
-            .turns
+    pub fn get_rounds_info(&self, notation: Notation) -> Vec<RoundInfo> {
+        // If black moved first, the first round has no white turn
+        let turns: Vec<Option<&Turn>> = (0..self.history.get_first_round_offset())
+            .map(|_| None)
+            .chain(self.history.get_current_line().into_iter().map(Some))
+            .collect();
+        let turn_string = |turn: Option<&Option<&Turn>>| {
+            turn.copied()
+                .flatten()
+                .map_or("...".to_string(), |turn| turn.get_san_str(notation))
+        };
+        turns
             .chunks(2)
-            .map(|turns| RoundInfo {
-                white_string: format!("{}", turns[0]),
-                black_string: turns
-                    .get(1)
-                    .map_or("...".to_string(), |black_turn| format!("{black_turn}")),
+            .enumerate()
+            .map(|(i, turns)| RoundInfo {
+                number: self.history.get_initial_move_number() + i,
+                white_string: turn_string(turns.first()),
+                black_string: turn_string(turns.get(1)),
             })
             .collect()
     }
@@ -393,6 +646,11 @@ impl Game {
         self.history.get_current_round()
     }
 
+    /// The number of the round the turn at `index` of the current line is played in.
+    pub(super) fn get_move_number(&self, index: usize) -> usize {
+        self.history.get_move_number(index)
+    }
+
     pub(super) fn get_current_turn_index(&self) -> usize {
         self.history.get_current_turn_index()
     }
@@ -401,49 +659,139 @@ impl Game {
         self.history.get_real_state().player
     }
 
-    pub fn trigger_timeout(&mut self) {
+    fn check_not_over(&self) -> ChessResult {
+        if self.status.is_game_over() || matches!(self.status, GameStatus::Timeout(..)) {
+            Err(ChessError::GameIsOver)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn end(&mut self) {
+        self.draw_offer = None;
         self.timer.stop();
-        self.update_status();
+    }
+
+    pub fn resign(&mut self, player: Color) -> ChessResult {
+        self.check_not_over()?;
+        self.set_status(GameStatus::Resignation(player));
+        self.end();
+        Ok(())
+    }
+
+    /// Offers the opponent a draw, which stands until they accept it or make a move.
+    pub fn offer_draw(&mut self, player: Color) -> ChessResult {
+        self.check_not_over()?;
+        self.draw_offer = Some(player);
+        self.observers.notify(GameEvent::DrawOffered(player));
+        Ok(())
+    }
+
+    pub fn get_draw_offer(&self) -> Option<Color> {
+        self.draw_offer
+    }
+
+    pub fn accept_draw(&mut self, player: Color) -> ChessResult {
+        self.check_not_over()?;
+        if self.draw_offer != Some(!player) {
+            return Err(ChessError::NoDrawOffer);
+        }
+        self.draw(DrawKind::Agreement);
+        self.end();
+        Ok(())
+    }
+
+    /// Claims a draw by threefold repetition or the fifty-move rule, which,
+    /// unlike their fivefold and seventy-five move versions, don't end the game
+    /// on their own.
+    pub fn claim_draw(&mut self) -> ChessResult {
+        self.check_not_over()?;
+        let path = self.history.get_real_path();
+        if self.history.get_repetition_count(&path) >= 3 {
+            self.draw(DrawKind::Repetition);
+        } else if self.history.get_fifty_move_count(&path) >= 50 {
+            self.draw(DrawKind::FiftyMoveRule);
+        } else {
+            return Err(ChessError::NoDrawToClaim);
+        }
+        self.end();
+        Ok(())
+    }
+
+    pub fn trigger_timeout(&mut self) {
+        if self.check_not_over().is_ok() {
+            self.timer.stop();
+            self.update_status();
+        }
     }
 
     pub fn get_fen_str(&self) -> String {
-        let mut fen = String::with_capacity(MAX_FEN_STR);
-        let mut empty_count = 0;
-        for y in (0..8).rev() {
-            for x in 0..8 {
-                if let Some(piece) = self.get_piece(&Position { x, y }) {
-                    if empty_count > 0 {
-                        fen.push_str(&empty_count.to_string());
-                        empty_count = 0;
-                    }
-                    fen.push(piece.get_fen_char());
-                } else {
-                    empty_count += 1;
-                }
-            }
-            if empty_count > 0 {
-                fen.push_str(&empty_count.to_string());
-                empty_count = 0;
-            }
-            fen.push('/');
-        }
-        fen.push_str(&format!(
-            " {} {} {} {} {}",
-            self.get_current_player().get_fen_char(),
-            self.get_current_state().castling_rights.get_fen_str(),
-            self.get_current_state()
-                .en_passant_position
-                .map_or("-".to_string(), |pos| pos.to_string()),
-            self.history.fifty_move_count,
-            self.get_current_turn_index() / 2 + 1
-        ));
-        fen
+        Fen {
+            state: *self.get_current_state(),
+            fifty_move_count: self.get_halfmove_clock(),
+            move_number: self.history.get_move_number(self.get_current_turn_index()),
+        }
+        .to_string()
+    }
+
+    pub fn get_pgn_str(&self, tags: &PgnTags) -> String {
+        pgn::write_pgn(
+            tags,
+            &self.history,
+            self.status,
+            [Color::White, Color::Black].map(|player| self.get_time_control(player)),
+        )
+    }
+
+    pub fn from_pgn(pgn: &str) -> Result<(Self, PgnTags), PgnError> {
+        pgn::read_pgn(pgn)
+    }
+
+    pub fn parse_san(&self, san: &str) -> Result<Move, SanError> {
+        San::parse_move(san, self.get_current_state(), &self.valid_moves)
+    }
+
+    /// The SAN of a legal move in the current position, without the check mark,
+    /// for showing moves that haven't been played.
+    pub fn get_san_str(&self, mv: &Move, notation: Notation) -> Option<String> {
+        self.valid_moves.contains(mv).then(|| {
+            San::new(self.get_current_state(), mv, &self.valid_moves).to_notation_string(notation)
+        })
+    }
+
+    /// Counts the positions reachable in exactly `depth` moves from the current one.
+    pub fn perft(&self, depth: usize) -> u64 {
+        perft::perft(self.get_current_state(), depth)
+    }
+
+    /// The perft count after each legal move in the current position.
+    pub fn divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        perft::divide(self.get_current_state(), depth)
     }
 
     pub fn get_current_move(&self) -> Option<Move> {
         self.history.get_current_move()
     }
 
+    /// The comments, NAGs, clock time and evaluation of the current move.
+    pub fn get_annotation(&self) -> Option<&Annotation> {
+        self.history.get_current_turn().map(|turn| &turn.annotation)
+    }
+
+    pub fn get_annotation_mut(&mut self) -> Option<&mut Annotation> {
+        self.history
+            .get_current_turn_mut()
+            .map(|turn| &mut turn.annotation)
+    }
+
+    /// Replaces the annotation of the current move, which can be in a variation.
+    pub fn set_annotation(&mut self, annotation: Annotation) -> ChessResult {
+        *self
+            .get_annotation_mut()
+            .ok_or(ChessError::NoMoveToAnnotate)? = annotation;
+        Ok(())
+    }
+
     pub fn get_highlighted_squares_info(&self) -> Vec<(Position, String)> {
         const MOVED_CLASS: &str = "moved-square";
         const CHECK_CLASS: &str = "check-square";
@@ -482,20 +830,38 @@ impl Game {
         info
     }
 
+    pub fn get_status(&self) -> GameStatus {
+        self.status
+    }
+
+    /// A read-only copy of the position being looked at.
+    pub fn get_snapshot(&self) -> PositionSnapshot {
+        let status = if self.is_replaying() {
+            self.get_position_status(self.history.get_current_path())
+        } else {
+            self.status
+        };
+        PositionSnapshot::new(*self.get_current_state(), status)
+    }
+
     pub fn game_over(&self) -> bool {
         self.status.is_game_over()
     }
 }
 struct GameBuilder {
-    start_time: Duration,
+    time_controls: [TimeControl; 2],
     state: BoardState,
+    fifty_move_count: u8,
+    move_number: usize,
 }
 
 impl Default for GameBuilder {
     fn default() -> Self {
         Self {
-            start_time: DEFAULT_DURATION,
+            time_controls: Default::default(),
             state: BoardState::default(),
+            fifty_move_count: 0,
+            move_number: 1,
         }
     }
 }
@@ -506,18 +872,23 @@ impl GameBuilder {
     }
 
     fn build(self) -> Game {
+        let mut history = History::with_state(self.state);
+        history.set_initial_counts(self.fifty_move_count, self.move_number);
         let mut game = Game {
             valid_moves: HashSet::default(),
-            history: History::with_state(self.state),
-            timer: Timer::with_duration(self.start_time),
+            history,
+            timer: Timer::with_time_controls(self.time_controls, self.state.player),
             status: GameStatus::default(),
+            armageddon: false,
+            draw_offer: None,
+            observers: Observers::default(),
         };
         game.add_moves();
         game
     }
 
-    fn start_time(mut self, start_time: Duration) -> Self {
-        self.start_time = start_time;
+    fn time_controls(mut self, white: TimeControl, black: TimeControl) -> Self {
+        self.time_controls = [white, black];
         self
     }
 
@@ -525,4 +896,624 @@ impl GameBuilder {
         self.state = state;
         self
     }
+
+    fn fifty_move_count(mut self, fifty_move_count: u8) -> Self {
+        self.fifty_move_count = fifty_move_count;
+        self
+    }
+
+    fn move_number(mut self, move_number: usize) -> Self {
+        self.move_number = move_number;
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        clock::ManualClock,
+        test_helpers::play,
+        time_control::{TimeBonus, TimePeriod},
+    };
+
+    #[test]
+    fn test_fen_round_trip() {
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2",
+            "r3k2r/8/8/8/8/8/8/R3K2R b Kq - 12 40",
+            "8/8/4k3/8/8/4K3/8/8 w - - 99 70",
+        ] {
+            assert_eq!(Game::from_fen(fen).unwrap().get_fen_str(), fen);
+        }
+    }
+
+    #[test]
+    fn test_underpromotion() {
+        let mut game = Game::from_fen("8/4P3/8/8/8/8/k6r/4K3 w - - 0 1").unwrap();
+        let (from, to) = (Position::new(4, 6), Position::new(4, 7));
+        assert_eq!(game.get_valid_destinations_for_piece(&from), vec![to]);
+        for piece in Piece::get_promotion_options(Color::White) {
+            assert!(game
+                .is_move_valid(&Move::with_promotion(from, to, piece))
+                .is_ok());
+        }
+
+        game.make_move(Move::with_promotion(from, to, Piece::Knight(Color::White)))
+            .unwrap();
+        assert_eq!(game.get_piece(&to), Some(Piece::Knight(Color::White)));
+
+        // omitting the promotion piece promotes to a queen
+        let mut game = Game::from_fen("8/4P3/8/8/8/8/k6r/4K3 w - - 0 1").unwrap();
+        game.move_piece(from, to).unwrap();
+        assert_eq!(game.get_piece(&to), Some(Piece::Queen(Color::White)));
+    }
+
+    #[test]
+    fn test_rounds_info() {
+        let mut game =
+            Game::from_fen("rnbqkb1r/pppppppp/5n2/8/3P4/8/PPP1PPPP/RNBQKBNR b KQkq - 1 7").unwrap();
+        for lan in ["e7e5", "d4e5", "f8c5", "g1f3", "e8g8"] {
+            game.make_move(Move::from_lan(lan).unwrap()).unwrap();
+        }
+        let rounds: Vec<(usize, String, String)> = game
+            .get_rounds_info(Notation::Figurine)
+            .into_iter()
+            .map(|info| (info.number, info.white_string, info.black_string))
+            .collect();
+        assert_eq!(
+            rounds,
+            vec![
+                (7, "...".into(), "e5".into()),
+                (8, "dxe5".into(), "♗c5".into()),
+                (9, "♞f3".into(), "O-O".into()),
+            ]
+        );
+        assert_eq!(
+            game.get_rounds_info(Notation::Letter)[1].black_string,
+            "Bc5"
+        );
+        assert_eq!(game.get_current_round(), 3);
+    }
+
+    #[test]
+    fn test_threefold_repetition() {
+        let mut game = Game::new();
+        // after 1. e4 the en passant square can't be used, so the position
+        // repeats when the knights return
+        for lan in [
+            "e2e4", "g8f6", "g1f3", "f6g8", "f3g1", "g8f6", "g1f3", "f6g8", "f3g1",
+        ] {
+            assert!(!game.game_over());
+            game.make_move(Move::from_lan(lan).unwrap()).unwrap();
+        }
+        // a threefold repetition has to be claimed
+        assert!(!game.game_over());
+        let mut claimed = game.clone();
+        claimed.claim_draw().unwrap();
+        assert_eq!(claimed.status, GameStatus::Draw(DrawKind::Repetition));
+
+        // but a fivefold one ends the game
+        play(&mut game, &["g8f6", "g1f3", "f6g8", "f3g1", "g8f6", "g1f3"]);
+        assert!(!game.game_over());
+        play(&mut game, &["f6g8", "f3g1"]);
+        assert_eq!(game.status, GameStatus::Draw(DrawKind::FivefoldRepetition));
+    }
+
+    #[test]
+    fn test_variations() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5", "g1f3"]);
+        game.go_back_a_move();
+        game.go_back_a_move();
+        // a different move starts a variation and leaves the game alone
+        play(&mut game, &["c7c5", "g1f3"]);
+        assert!(!game.is_on_main_line());
+        assert_eq!(game.history.get_real_state().player, Color::Black);
+        game.resume();
+        assert_eq!(game.get_current_move(), Move::from_lan("g1f3"));
+
+        // playing a move again follows it instead of adding it twice
+        game.go_to_start();
+        play(&mut game, &["e2e4"]);
+        assert!(game.is_on_main_line());
+        assert_eq!(
+            game.get_next_moves(),
+            vec![
+                Move::from_lan("e7e5").unwrap(),
+                Move::from_lan("c7c5").unwrap()
+            ]
+        );
+        game.go_to_variation(1).unwrap();
+        assert!(matches!(
+            game.go_to_variation(1),
+            Err(ChessError::OutOfBounds)
+        ));
+
+        // the main line can't change while the game is running
+        assert!(matches!(
+            game.promote_to_main_line(),
+            Err(ChessError::GameInProgress)
+        ));
+        game.resign(Color::Black).unwrap();
+        game.go_to_start();
+        play(&mut game, &["e2e4", "c7c5"]);
+        game.promote_to_main_line().unwrap();
+        assert!(game.is_on_main_line());
+        assert_eq!(game.get_rounds_info(Notation::Letter).len(), 2);
+        // the resignation still stands
+        assert_eq!(game.status, GameStatus::Resignation(Color::Black));
+
+        game.go_back_a_move();
+        game.go_to_variation(1).unwrap();
+        game.delete_current_move().unwrap();
+        assert_eq!(game.get_next_moves(), vec![Move::from_lan("c7c5").unwrap()]);
+    }
+
+    #[test]
+    fn test_annotations() {
+        let mut game = Game::new();
+        assert!(matches!(
+            game.set_annotation(Annotation::default()),
+            Err(ChessError::NoMoveToAnnotate)
+        ));
+        play(&mut game, &["e2e4", "e7e5"]);
+        let annotation = Annotation {
+            comments: vec!["Symmetrical".into()],
+            nags: vec![5],
+            ..Default::default()
+        };
+        game.set_annotation(annotation.clone()).unwrap();
+        game.go_back_a_move();
+        assert!(game.get_annotation().unwrap().is_empty());
+        game.go_forward_a_move();
+        assert_eq!(game.get_annotation(), Some(&annotation));
+        assert!(game
+            .get_pgn_str(&PgnTags::default())
+            .ends_with("1. e4 e5 $5 {Symmetrical} *\n"));
+    }
+
+    #[test]
+    fn test_repetition_along_path() {
+        let mut game = Game::new();
+        play(&mut game, &["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6"]);
+        game.go_to_start();
+        // the same moves in a variation start counting from scratch
+        play(&mut game, &["b1c3", "g8f6", "c3b1", "f6g8"]);
+        assert_eq!(game.history.get_repetition_count(&[1, 0, 0, 0]), 2);
+        assert_eq!(
+            game.history
+                .get_repetition_count(&game.history.get_real_path()),
+            2
+        );
+        assert_eq!(game.history.get_fifty_move_count(&[1, 0, 0, 0]), 2);
+    }
+
+    #[test]
+    fn test_move_rules() {
+        let mut game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 99 80").unwrap();
+        assert!(matches!(game.claim_draw(), Err(ChessError::NoDrawToClaim)));
+        play(&mut game, &["a1a2"]);
+        let mut claimed = game.clone();
+        claimed.claim_draw().unwrap();
+        assert_eq!(claimed.status, GameStatus::Draw(DrawKind::FiftyMoveRule));
+
+        let mut game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 149 80").unwrap();
+        play(&mut game, &["a1a2"]);
+        assert_eq!(game.status, GameStatus::Draw(DrawKind::SeventyFiveMoveRule));
+
+        // checkmate on the seventy-fifth move still wins
+        let mut game = Game::from_fen("7k/8/6K1/8/8/8/8/R7 w - - 149 80").unwrap();
+        play(&mut game, &["a1a8"]);
+        assert_eq!(game.status, GameStatus::Checkmate(Color::Black));
+    }
+
+    #[test]
+    fn test_resign_and_draw_offers() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4"]);
+        game.resign(Color::Black).unwrap();
+        assert_eq!(game.status, GameStatus::Resignation(Color::Black));
+        assert_eq!(game.status.get_pgn_result(), "1-0");
+        assert!(matches!(
+            game.make_move(Move::from_lan("e7e5").unwrap()),
+            Err(ChessError::GameIsOver)
+        ));
+        assert!(matches!(
+            game.resign(Color::White),
+            Err(ChessError::GameIsOver)
+        ));
+
+        let mut game = Game::new();
+        assert!(matches!(
+            game.accept_draw(Color::Black),
+            Err(ChessError::NoDrawOffer)
+        ));
+        game.offer_draw(Color::White).unwrap();
+        // the offer stands through the offering player's own move
+        play(&mut game, &["e2e4"]);
+        assert_eq!(game.get_draw_offer(), Some(Color::White));
+        // a player can't accept their own offer
+        assert!(matches!(
+            game.accept_draw(Color::White),
+            Err(ChessError::NoDrawOffer)
+        ));
+        // and moving declines it
+        play(&mut game, &["e7e5"]);
+        assert_eq!(game.get_draw_offer(), None);
+
+        game.offer_draw(Color::White).unwrap();
+        game.accept_draw(Color::Black).unwrap();
+        assert_eq!(game.status, GameStatus::Draw(DrawKind::Agreement));
+        assert!(game.game_over());
+    }
+
+    #[test]
+    fn test_events() {
+        let clock = ManualClock::new();
+        let mut game = Game::with_start_time(Duration::from_secs(60)).with_clock(clock.clone());
+        let (id, events) = game.subscribe_channel();
+        let moves = Arc::new(std::sync::Mutex::new(0));
+        let counter = moves.clone();
+        game.subscribe(move |event| {
+            if matches!(event, GameEvent::MoveMade { .. }) {
+                *counter.lock().unwrap() += 1;
+            }
+        });
+        play(&mut game, &["e2e4"]);
+        game.go_back_a_move();
+        play(&mut game, &["d2d4"]);
+        game.offer_draw(Color::Black).unwrap();
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                GameEvent::MoveMade {
+                    piece: Piece::Pawn(Color::White),
+                    mv: Move::from_lan("e2e4").unwrap(),
+                    continues_game: true,
+                },
+                GameEvent::StatusChanged {
+                    from: GameStatus::NotStarted,
+                    to: GameStatus::Ongoing,
+                },
+                GameEvent::HistoryNavigated { current_move: None },
+                GameEvent::MoveMade {
+                    piece: Piece::Pawn(Color::White),
+                    mv: Move::from_lan("d2d4").unwrap(),
+                    continues_game: false,
+                },
+                GameEvent::DrawOffered(Color::Black),
+            ]
+        );
+
+        // going into a variation navigates too, unless it doesn't exist
+        game.go_back_a_move();
+        game.go_to_variation(1).unwrap();
+        assert!(game.go_to_variation(1).is_err());
+        assert_eq!(
+            events.try_iter().collect::<Vec<_>>(),
+            [
+                GameEvent::HistoryNavigated { current_move: None },
+                GameEvent::HistoryNavigated {
+                    current_move: Some(Move::from_lan("d2d4").unwrap()),
+                },
+            ]
+        );
+
+        // a copy of the game doesn't notify anyone
+        let mut copy = game.clone();
+        copy.resume();
+        play(&mut copy, &["e7e5"]);
+        assert_eq!(events.try_iter().count(), 0);
+
+        game.resume();
+        clock.advance(Duration::from_secs(61));
+        game.trigger_timeout();
+        assert_eq!(
+            events.try_iter().skip(1).collect::<Vec<_>>(),
+            [
+                GameEvent::Flagged(Color::Black),
+                GameEvent::StatusChanged {
+                    from: GameStatus::Ongoing,
+                    to: GameStatus::Timeout(Color::Black),
+                },
+            ]
+        );
+        assert!(game.unsubscribe(id));
+        assert!(!game.unsubscribe(id));
+        assert_eq!(*moves.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn test_end_before_clock_starts() {
+        let minute = Duration::from_secs(60);
+        let mut game = Game::with_start_time(minute);
+        game.resign(Color::White).unwrap();
+        assert!(game.game_over());
+        assert!(!game.is_timer_active());
+        assert_eq!(game.get_time(Color::White), minute);
+
+        let game = Game::from_fen("R3k3/8/4K3/8/8/8/8/8 b - - 0 1").unwrap();
+        assert!(game.game_over());
+        assert!(!game.is_timer_active());
+    }
+
+    #[test]
+    fn test_time_control() {
+        let minute = Duration::from_secs(60);
+        let game = Game::with_start_time(minute);
+        assert_eq!(
+            game.get_time_control(Color::Black),
+            &TimeControl::from(minute)
+        );
+        let game = Game::chess960(0).unwrap().with_time_control(minute);
+        assert_eq!(game.get_time(Color::Black), minute);
+
+        let second = Duration::from_secs(1);
+        let clock = ManualClock::new();
+        let blitz = TimeControl::fischer(3 * minute, 2 * second);
+        let mut game = Game::with_start_time(blitz).with_clock(clock.clone());
+        // the first move starts the clock and earns the increment
+        play(&mut game, &["e2e4"]);
+        assert_eq!(game.get_time(Color::White), 3 * minute + 2 * second);
+        clock.advance(10 * second);
+        assert_eq!(game.get_time(Color::Black), 3 * minute - 10 * second);
+        play(&mut game, &["e7e5"]);
+        assert_eq!(game.get_time(Color::Black), 3 * minute - 8 * second);
+
+        // the second period's time is added once its last move is made
+        let periods = TimeControl::from(TimePeriod::moves(2, minute, TimeBonus::None))
+            .then(TimePeriod::sudden_death(minute, TimeBonus::None));
+        let mut game = Game::with_start_time(periods).with_clock(clock.clone());
+        play(&mut game, &["e2e4", "e7e5"]);
+        clock.advance(5 * second);
+        play(&mut game, &["g1f3"]);
+        assert_eq!(game.get_time(Color::White), 2 * minute - 5 * second);
+        assert_eq!(game.get_time(Color::Black), minute);
+    }
+
+    #[test]
+    fn test_timeout() {
+        let clock = ManualClock::new();
+        let mut game = Game::with_start_time(Duration::from_secs(60)).with_clock(clock.clone());
+        play(&mut game, &["e2e4"]);
+        clock.advance(Duration::from_secs(59));
+        assert_eq!(game.get_time(Color::Black), Duration::from_secs(1));
+
+        clock.advance(Duration::from_secs(1));
+        assert!(matches!(
+            game.make_move(Move::from_lan("e7e5").unwrap()),
+            Err(ChessError::Timeout)
+        ));
+        game.trigger_timeout();
+        assert_eq!(game.status, GameStatus::Timeout(Color::Black));
+        assert_eq!(game.get_time(Color::Black), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_timeout_vs_insufficient_material() {
+        let clock = ManualClock::new();
+        let timeout = |fen, lan| {
+            let mut game = Game::from_fen(fen)
+                .unwrap()
+                .with_time_control(Duration::from_secs(60))
+                .with_clock(clock.clone());
+            play(&mut game, &[lan]);
+            clock.advance(Duration::from_secs(60));
+            game.trigger_timeout();
+            game.status
+        };
+        // Black runs out of time against a lone king
+        assert_eq!(
+            timeout("8/8/4k3/8/8/3K4/8/7q w - - 0 1", "d3d4"),
+            GameStatus::Draw(DrawKind::TimeoutVsInsufficientMaterial)
+        );
+        // White runs out of time against a queen
+        assert_eq!(
+            timeout("8/8/4k3/8/8/3K4/8/7q b - - 0 1", "h1h2"),
+            GameStatus::Timeout(Color::White)
+        );
+    }
+
+    #[test]
+    fn test_time_odds() {
+        let (minute, second) = (Duration::from_secs(60), Duration::from_secs(1));
+        let clock = ManualClock::new();
+        let mut game = Game::new()
+            .with_time_controls(TimeControl::fischer(5 * minute, 3 * second), 2 * minute)
+            .with_clock(clock.clone());
+        assert_eq!(game.get_time(Color::White), 5 * minute);
+        assert_eq!(game.get_time(Color::Black), 2 * minute);
+
+        play(&mut game, &["e2e4"]);
+        clock.advance(10 * second);
+        play(&mut game, &["e7e5"]);
+        assert_eq!(game.get_time(Color::White), 5 * minute + 3 * second);
+        assert_eq!(game.get_time(Color::Black), 2 * minute - 10 * second);
+    }
+
+    #[test]
+    fn test_armageddon() {
+        let mut game = Game::new().with_armageddon();
+        game.offer_draw(Color::White).unwrap();
+        game.accept_draw(Color::Black).unwrap();
+        assert_eq!(game.status, GameStatus::Armageddon(DrawKind::Agreement));
+        assert_eq!(game.status.get_pgn_result(), "0-1");
+        assert!(game.game_over());
+        assert!(matches!(
+            game.make_move(Move::from_lan("g1f3").unwrap()),
+            Err(ChessError::GameIsInDraw)
+        ));
+
+        // a decisive game is decided as usual
+        let mut game = Game::new().with_armageddon();
+        play(&mut game, &["f2f3", "e7e5", "g2g4", "d8h4"]);
+        assert_eq!(game.status, GameStatus::Checkmate(Color::White));
+    }
+
+    #[test]
+    fn test_chess960_start_positions() {
+        let board = |game: Game| game.get_fen_str().split(' ').next().unwrap().to_string();
+        assert_eq!(
+            Game::chess960(0).unwrap().get_fen_str(),
+            "bbqnnrkr/pppppppp/8/8/8/8/PPPPPPPP/BBQNNRKR w HFhf - 0 1"
+        );
+        assert_eq!(board(Game::chess960(518).unwrap()), board(Game::new()));
+        assert_eq!(
+            board(Game::chess960(959).unwrap()),
+            "rkrnnqbb/pppppppp/8/8/8/8/PPPPPPPP/RKRNNQBB"
+        );
+        assert!(Game::chess960(0).unwrap().is_chess960());
+        assert!(!Game::new().is_chess960());
+        assert!(matches!(
+            Game::chess960(CHESS960_POSITIONS),
+            Err(ChessError::InvalidChess960Index(CHESS960_POSITIONS))
+        ));
+    }
+
+    #[test]
+    fn test_chess960_castling() {
+        // the king castles by moving onto its own rook
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1").unwrap();
+        let castle = game.parse_san("O-O").unwrap();
+        assert_eq!(castle, Move::from_lan("f1g1").unwrap());
+        game.make_move(castle).unwrap();
+        assert_eq!(game.get_fen_str(), "4k3/8/8/8/8/8/8/1R3RK1 b - - 1 1");
+
+        let mut game = Game::from_fen("4k3/8/8/8/8/8/8/1R3KR1 w GB - 0 1").unwrap();
+        game.make_move(game.parse_san("O-O-O").unwrap()).unwrap();
+        assert_eq!(game.get_fen_str(), "4k3/8/8/8/8/8/8/2KR2R1 b - - 1 1");
+
+        // the king may not pass through an attacked square, even one the rook crosses
+        let castle = Move::from_lan("b1a1").unwrap();
+        let game = Game::from_fen("4k3/8/8/8/8/8/8/RK6 w A - 0 1").unwrap();
+        assert!(game.is_move_valid(&castle).is_ok());
+        let game = Game::from_fen("2r1k3/8/8/8/8/8/8/RK6 w A - 0 1").unwrap();
+        assert!(game.is_move_valid(&castle).is_err());
+    }
+
+    #[test]
+    fn test_san_of_unplayed_moves() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "d7d5"]);
+        let capture = Move::from_lan("e4d5").unwrap();
+        assert_eq!(
+            game.get_san_str(&capture, Notation::Letter).as_deref(),
+            Some("exd5")
+        );
+        let knight = Move::from_lan("g1f3").unwrap();
+        assert_eq!(
+            game.get_san_str(&knight, Notation::Letter).as_deref(),
+            Some("Nf3")
+        );
+        let illegal = Move::from_lan("e4e6").unwrap();
+        assert_eq!(game.get_san_str(&illegal, Notation::Letter), None);
+    }
+
+    #[test]
+    fn test_king_of_the_hill() {
+        let mut game = Game::with_variant(Variant::KingOfTheHill);
+        play(
+            &mut game,
+            &["e2e3", "a7a6", "e1e2", "a6a5", "e2d3", "a5a4", "d3d4"],
+        );
+        assert_eq!(game.status, GameStatus::VariantEnd(Color::Black));
+        assert!(game.game_over());
+    }
+
+    #[test]
+    fn test_three_check() {
+        let mut game = Game::with_variant(Variant::ThreeCheck);
+        play(&mut game, &["e2e4", "d7d5", "f1b5", "c7c6", "b5c6"]);
+        assert!(game.is_in_check());
+        assert!(game.get_fen_str().contains(" 1+3 "));
+        play(&mut game, &["b8c6", "d1h5", "g8f6", "h5f7"]);
+        assert_eq!(game.status, GameStatus::VariantEnd(Color::Black));
+        // the check that ends the game is still written as one
+        assert!(game
+            .get_pgn_str(&PgnTags::default())
+            .contains("5. Qxf7+ 1-0"));
+    }
+
+    #[test]
+    fn test_antichess() {
+        let mut game = Game::with_variant(Variant::Antichess);
+        play(&mut game, &["e2e4", "d7d5"]);
+        // capturing is compulsory
+        assert!(game
+            .is_move_valid(&Move::from_lan("g1f3").unwrap())
+            .is_err());
+        play(&mut game, &["e4d5"]);
+        // and the king is an ordinary piece
+        assert!(!game.is_in_check());
+
+        // a player without pieces wins
+        let mut game =
+            Game::from_variant_fen(Variant::Antichess, "8/8/8/8/8/8/1p6/R7 b - - 0 1").unwrap();
+        play(&mut game, &["b2a1q"]);
+        assert_eq!(game.status, GameStatus::VariantEnd(Color::Black));
+
+        // and so does a player who is stalemated
+        let game =
+            Game::from_variant_fen(Variant::Antichess, "8/8/8/8/8/p7/P7/8 w - - 0 1").unwrap();
+        assert_eq!(game.status, GameStatus::VariantEnd(Color::Black));
+    }
+
+    #[test]
+    fn test_atomic() {
+        let mut game =
+            Game::from_variant_fen(Variant::Atomic, "4k3/8/2p5/3nb3/4P3/8/8/4K3 w - - 0 1")
+                .unwrap();
+        // the capture blows up both pieces and the bishop next to them, but not the pawn
+        play(&mut game, &["e4d5"]);
+        assert_eq!(game.get_piece(&Position::new(3, 4)), None);
+        assert_eq!(game.get_piece(&Position::new(4, 4)), None);
+        assert_eq!(
+            game.get_piece(&Position::new(2, 5)),
+            Some(Piece::Pawn(Color::Black))
+        );
+
+        // exploding the king wins
+        let mut game =
+            Game::from_variant_fen(Variant::Atomic, "4k3/3p4/8/8/8/8/8/3RK3 w - - 0 1").unwrap();
+        play(&mut game, &["d1d7"]);
+        assert_eq!(game.status, GameStatus::VariantEnd(Color::Black));
+
+        // kings can't capture, and touching kings can't be checked
+        let game =
+            Game::from_variant_fen(Variant::Atomic, "8/8/8/8/8/3k4/3qK3/8 w - - 0 1").unwrap();
+        assert!(!game.is_in_check());
+        assert!(game
+            .is_move_valid(&Move::from_lan("e2d2").unwrap())
+            .is_err());
+    }
+
+    #[test]
+    fn test_horde() {
+        assert_eq!(Game::with_variant(Variant::Horde).valid_moves.len(), 8);
+        // pawns on the first rank can move two squares
+        let game = Game::from_variant_fen(Variant::Horde, "4k3/8/8/8/8/8/8/P7 w - - 0 1").unwrap();
+        assert!(game.is_move_valid(&Move::from_lan("a1a3").unwrap()).is_ok());
+
+        let mut game =
+            Game::from_variant_fen(Variant::Horde, "4k3/8/8/8/8/8/3P4/4q3 b - - 0 1").unwrap();
+        play(&mut game, &["e1d2"]);
+        assert_eq!(game.status, GameStatus::VariantEnd(Color::White));
+    }
+
+    #[test]
+    fn test_from_fen_status() {
+        let game = Game::from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.status, GameStatus::Checkmate(Color::Black));
+        assert_eq!(game.get_current_player(), Color::Black);
+
+        let game = Game::from_fen("7k/8/6QK/8/8/8/8/8 b - - 0 1").unwrap();
+        assert_eq!(game.status, GameStatus::Draw(DrawKind::Stalemate));
+
+        assert_eq!(
+            Game::from_fen("7k/6Q1/8/8/8/8/8/K7 w - - 0 1").err(),
+            Some(FenError::OpponentInCheck)
+        );
+    }
 }
@@ -1,33 +1,89 @@
+use serde::{Deserialize, Serialize};
+
 use crate::color::Color;
 
-#[derive(Clone, Copy, Default, PartialEq, Debug)]
-pub(super) enum GameStatus {
+#[derive(Clone, Copy, Default, PartialEq, Debug, Serialize, Deserialize)]
+pub enum GameStatus {
     #[default]
     NotStarted,
     Ongoing,
     Check(Color),
     Checkmate(Color),
     Timeout(Color),
+    /// The player resigned.
+    Resignation(Color),
+    /// The player lost by a rule of the game's variant.
+    VariantEnd(Color),
     Draw(DrawKind),
+    /// A drawn Armageddon game, which Black wins.
+    Armageddon(DrawKind),
 }
 
 impl GameStatus {
-    pub(super) fn update(&mut self, status: GameStatus) {
-        if *self != status {
-            log::info!("GameStatus changing from {:?} to {:?}", *self, status);
-            *self = status
+    pub(super) fn get_pgn_result(&self) -> &'static str {
+        match self {
+            GameStatus::Checkmate(Color::White)
+            | GameStatus::Timeout(Color::White)
+            | GameStatus::VariantEnd(Color::White)
+            | GameStatus::Resignation(Color::White)
+            | GameStatus::Armageddon(..) => "0-1",
+            GameStatus::Checkmate(Color::Black)
+            | GameStatus::Timeout(Color::Black)
+            | GameStatus::VariantEnd(Color::Black)
+            | GameStatus::Resignation(Color::Black) => "1-0",
+            GameStatus::Draw(..) => "1/2-1/2",
+            _ => "*",
         }
     }
 
+    /// Whether the result came from the players or the clock rather than the
+    /// position on the board.
+    pub(super) fn is_decided_by_players(&self) -> bool {
+        matches!(
+            self,
+            GameStatus::Resignation(..)
+                | GameStatus::Timeout(..)
+                | GameStatus::Draw(
+                    DrawKind::Agreement
+                        | DrawKind::Repetition
+                        | DrawKind::FiftyMoveRule
+                        | DrawKind::TimeoutVsInsufficientMaterial
+                )
+                | GameStatus::Armageddon(
+                    DrawKind::Agreement
+                        | DrawKind::Repetition
+                        | DrawKind::FiftyMoveRule
+                        | DrawKind::TimeoutVsInsufficientMaterial
+                )
+        )
+    }
+
     pub(super) fn is_game_over(&self) -> bool {
-        matches!(self, GameStatus::Draw(..) | GameStatus::Checkmate(..))
+        matches!(
+            self,
+            GameStatus::Draw(..)
+                | GameStatus::Armageddon(..)
+                | GameStatus::Checkmate(..)
+                | GameStatus::VariantEnd(..)
+                | GameStatus::Resignation(..)
+        )
     }
 }
 
-#[derive(Clone, Copy, PartialEq, Debug)]
-pub(super) enum DrawKind {
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub enum DrawKind {
+    /// Claimed after fifty moves without a capture or pawn move.
     FiftyMoveRule,
+    /// Claimed after the same position occurred three times.
     Repetition,
+    /// Automatic after seventy-five moves without a capture or pawn move.
+    SeventyFiveMoveRule,
+    /// Automatic after the same position occurred five times.
+    FivefoldRepetition,
     Stalemate,
     InsufficientMaterial,
+    /// A player ran out of time, but their opponent couldn't have won.
+    TimeoutVsInsufficientMaterial,
+    /// One player offered a draw and the other accepted it.
+    Agreement,
 }
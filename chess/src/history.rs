@@ -1,148 +1,307 @@
-use std::collections::HashMap;
+use crate::{
+    board_state::BoardState, color::Color, fen::Fen, game_status::GameStatus, moves::Move,
+    san::San, turn::Turn,
+};
 
-use crate::{board_state::BoardState, game_status::GameStatus, moves::Move, turn::Turn};
+/// A turn and the turns played after it. The first one continues the line, the
+/// others are variations.
+#[derive(Clone)]
+pub(super) struct Node {
+    pub(super) turn: Turn,
+    pub(super) children: Vec<Node>,
+}
 
+/// The moves of a game as a tree of variations, with the main line always
+/// taking the first branch. A path is the index of the branch taken after each
+/// turn, so the main line's path is all zeros.
 #[derive(Clone)]
 pub(super) struct History {
-    pub(super) turns: Vec<Turn>,
-    pub(super) repetition_counter: HashMap<BoardState, usize>,
-    current_turn_index: usize,
-    pub(super) fifty_move_count: u8,
+    variations: Vec<Node>,
+    current_path: Vec<usize>,
     initial_state: BoardState,
+    initial_fifty_move_count: u8,
+    initial_move_number: usize,
 }
 
 impl History {
     pub(super) fn with_state(initial_state: BoardState) -> Self {
         Self {
             initial_state,
-            repetition_counter: vec![(initial_state, 1)].into_iter().collect(),
             ..Default::default()
         }
     }
 
-    pub(super) fn update_status(&mut self, status: GameStatus) {
-        self.get_real_turn_mut().unwrap().status = status;
+    pub(super) fn set_initial_counts(&mut self, fifty_move_count: u8, move_number: usize) {
+        self.initial_fifty_move_count = fifty_move_count;
+        self.initial_move_number = move_number;
+    }
+
+    pub(super) fn get_initial_fen(&self) -> Fen {
+        Fen {
+            state: self.initial_state,
+            fifty_move_count: self.initial_fifty_move_count,
+            move_number: self.initial_move_number,
+        }
     }
 
-    pub(super) fn get_real_turn(&self) -> Option<&Turn> {
-        self.turns.last()
+    pub(super) fn starts_from_initial_position(&self) -> bool {
+        self.initial_state == self.initial_state.variant.get_start_state()
+            && self.initial_fifty_move_count == 0
+            && self.initial_move_number == 1
     }
 
-    pub(super) fn get_real_turn_mut(&mut self) -> Option<&mut Turn> {
-        self.turns.last_mut()
+    pub(super) fn get_initial_move_number(&self) -> usize {
+        self.initial_move_number
     }
 
-    pub(super) fn get_current_move(&self) -> Option<Move> {
-        if self.get_current_turn_index() == 0 {
-            None
-        } else {
-            Some(self.turns[self.get_current_turn_index() - 1].mv)
+    // The number of turns to skip at the start of the first round
+    pub(super) fn get_first_round_offset(&self) -> usize {
+        match self.initial_state.player {
+            Color::White => 0,
+            Color::Black => 1,
         }
     }
 
-    pub(super) fn get_current_turn(&self) -> Option<Turn> {
-        if self.get_current_turn_index() == 0 {
-            None
-        } else {
-            Some(self.turns[self.get_current_turn_index() - 1])
+    pub(super) fn get_move_number(&self, turn: usize) -> usize {
+        self.initial_move_number + (turn + self.get_first_round_offset()) / 2
+    }
+
+    pub(super) fn get_children(&self, path: &[usize]) -> &[Node] {
+        path.iter()
+            .fold(&self.variations, |children, &i| &children[i].children)
+    }
+
+    fn get_children_mut(&mut self, path: &[usize]) -> &mut Vec<Node> {
+        path.iter().fold(&mut self.variations, |children, &i| {
+            &mut children[i].children
+        })
+    }
+
+    /// The turns leading to the end of `path`.
+    pub(super) fn get_turns(&self, path: &[usize]) -> Vec<&Turn> {
+        let mut children = &self.variations;
+        path.iter()
+            .map(|&i| {
+                let node = &children[i];
+                children = &node.children;
+                &node.turn
+            })
+            .collect()
+    }
+
+    pub(super) fn contains_path(&self, path: &[usize]) -> bool {
+        let mut children = &self.variations;
+        path.iter().all(|&i| match children.get(i) {
+            Some(node) => {
+                children = &node.children;
+                true
+            }
+            None => false,
+        })
+    }
+
+    fn get_turn(&self, path: &[usize]) -> Option<&Turn> {
+        let (&last, parent) = path.split_last()?;
+        Some(&self.get_children(parent)[last].turn)
+    }
+
+    /// The path of the main line, followed from `path` to its end.
+    fn get_line_end(&self, path: &[usize]) -> Vec<usize> {
+        let mut path = path.to_vec();
+        while !self.get_children(&path).is_empty() {
+            path.push(0);
         }
+        path
+    }
+
+    pub(super) fn get_current_path(&self) -> &[usize] {
+        &self.current_path
+    }
+
+    /// The path of the game itself, the end of the main line.
+    pub(super) fn get_real_path(&self) -> Vec<usize> {
+        self.get_line_end(&[])
+    }
+
+    pub(super) fn update_status(&mut self, status: GameStatus) {
+        self.get_current_turn_mut().unwrap().status = status;
+    }
+
+    pub(super) fn get_current_move(&self) -> Option<Move> {
+        self.get_current_turn().map(|turn| turn.mv)
+    }
+
+    pub(super) fn get_current_turn(&self) -> Option<&Turn> {
+        self.get_turn(&self.current_path)
+    }
+
+    pub(super) fn get_current_turn_mut(&mut self) -> Option<&mut Turn> {
+        let (&last, parent) = self.current_path.split_last()?;
+        let parent = parent.to_vec();
+        Some(&mut self.get_children_mut(&parent)[last].turn)
     }
 
     pub(super) fn get_current_turn_index(&self) -> usize {
-        self.current_turn_index
+        self.current_path.len()
     }
 
-    pub(super) fn get_board_state(&self, turn: usize) -> &BoardState {
-        if turn == 0 {
-            &self.initial_state
-        } else {
-            &self.turns[turn - 1].board_state
-        }
+    pub(super) fn get_board_state(&self, path: &[usize]) -> &BoardState {
+        self.get_turn(path)
+            .map_or(&self.initial_state, |turn| &turn.board_state)
     }
 
-    pub(super) fn get_real_state_repetition_count(&self) -> usize {
-        *self.repetition_counter.get(self.get_real_state()).unwrap()
+    /// The halfmove clock of the fifty-move rule at the end of `path`.
+    pub(super) fn get_halfmove_clock(&self, path: &[usize]) -> u8 {
+        self.get_turn(path)
+            .map_or(self.initial_fifty_move_count, |turn| turn.fifty_move_count)
     }
 
-    fn update_fifty_move_info(&mut self, piece_captured: bool, pawn_moved: bool) {
-        if piece_captured || pawn_moved {
-            self.fifty_move_count = 0
-        } else {
-            self.fifty_move_count += 1;
-        }
+    pub(super) fn get_fifty_move_count(&self, path: &[usize]) -> u8 {
+        self.get_halfmove_clock(path) / 2
     }
 
-    fn update_repetition_info(&mut self, next_state: BoardState) {
-        *self.repetition_counter.entry(next_state).or_insert(0) += 1;
+    /// The Zobrist keys of the positions along `path` that can still repeat,
+    /// oldest first and ending with the position at its end. Positions before the
+    /// last capture or pawn move can't come back.
+    pub(super) fn get_repetition_keys(&self, path: &[usize]) -> Vec<u64> {
+        let turns = self.get_turns(path);
+        let states =
+            std::iter::once(&self.initial_state).chain(turns.iter().map(|turn| &turn.board_state));
+        let mut keys: Vec<u64> = states
+            .rev()
+            .take(self.get_halfmove_clock(path) as usize + 1)
+            .map(|state| state.get_zobrist_key())
+            .collect();
+        keys.reverse();
+        keys
     }
 
-    fn add_turn(&mut self, turn: Turn) {
-        self.turns.drain(self.current_turn_index..self.turns.len());
-        self.turns.push(turn);
-        self.current_turn_index += 1
+    /// How often the position at the end of `path` occurred along it.
+    pub(super) fn get_repetition_count(&self, path: &[usize]) -> usize {
+        let key = self.get_board_state(path).get_zobrist_key();
+        self.get_repetition_keys(path)
+            .into_iter()
+            .filter(|&other| other == key)
+            .count()
     }
 
-    pub(super) fn add_info(&mut self, next_state: BoardState, mv: Move) {
+    /// Plays a move from the current position. A move that was already played
+    /// from here is followed rather than added again; a new one starts a
+    /// variation unless the position is the end of its line.
+    pub(super) fn add_info(&mut self, next_state: BoardState, mv: Move, san: San) {
         let current_state = self.get_current_state();
         let is_pawn = current_state.get_piece(&mv.from).unwrap().is_pawn();
-        let is_capture_move =
-            current_state.get_piece(&mv.to).is_some() || (is_pawn && mv.from.x != mv.to.x);
-
-        self.update_fifty_move_info(is_capture_move, is_pawn);
-        self.update_repetition_info(next_state);
-        self.add_turn(Turn::new(next_state, mv, is_capture_move));
-    }
+        // a Chess960 king castles onto its own rook, which isn't a capture
+        let is_capture_move = current_state
+            .get_piece(&mv.to)
+            .is_some_and(|piece| piece.get_player() != current_state.player)
+            || (is_pawn && mv.from.x != mv.to.x);
+        let fifty_move_count = if is_capture_move || is_pawn {
+            0
+        } else {
+            self.get_halfmove_clock(&self.current_path) + 1
+        };
 
-    pub(super) fn get_fifty_move_count(&self) -> u8 {
-        self.fifty_move_count / 2
+        let path = self.current_path.clone();
+        let children = self.get_children_mut(&path);
+        let index = match children.iter().position(|node| node.turn.mv == mv) {
+            Some(index) => index,
+            None => {
+                children.push(Node {
+                    turn: Turn::new(next_state, mv, san, fifty_move_count),
+                    children: Vec::new(),
+                });
+                children.len() - 1
+            }
+        };
+        self.current_path.push(index);
     }
 
     pub(super) fn get_current_state(&self) -> &BoardState {
-        self.get_board_state(self.current_turn_index)
+        self.get_board_state(&self.current_path)
     }
 
     pub(super) fn get_real_state(&self) -> &BoardState {
-        self.get_board_state(self.turns.len())
+        self.get_board_state(&self.get_real_path())
     }
 
     pub(super) fn resume(&mut self) {
-        self.current_turn_index = self.turns.len()
+        self.current_path = self.get_real_path()
     }
 
     pub(super) fn previous_move(&mut self) {
-        if self.current_turn_index > 0 {
-            self.current_turn_index -= 1
-        }
+        self.current_path.pop();
     }
 
     pub(super) fn next_move(&mut self) {
-        if self.current_turn_index < self.turns.len() {
-            self.current_turn_index += 1
+        if !self.get_children(&self.current_path).is_empty() {
+            self.current_path.push(0)
         }
     }
 
+    /// Plays the `index`th of the moves played from the current position.
+    pub(super) fn go_to_variation(&mut self, index: usize) -> bool {
+        let exists = index < self.get_children(&self.current_path).len();
+        if exists {
+            self.current_path.push(index);
+        }
+        exists
+    }
+
+    pub(super) fn go_to_path(&mut self, path: Vec<usize>) {
+        self.current_path = path
+    }
+
     pub(super) fn go_to_start(&mut self) {
-        self.current_turn_index = 0
+        self.current_path.clear()
     }
 
     pub(super) fn is_replaying(&self) -> bool {
-        self.current_turn_index != self.turns.len()
+        self.current_path != self.get_real_path()
+    }
+
+    pub(super) fn is_on_main_line(&self) -> bool {
+        self.current_path.iter().all(|&i| i == 0)
+    }
+
+    /// The turns of the line through the current position, up to its end.
+    pub(super) fn get_current_line(&self) -> Vec<&Turn> {
+        self.get_turns(&self.get_line_end(&self.current_path))
+    }
+
+    /// Makes the line through the current position the main line.
+    pub(super) fn promote_to_main_line(&mut self) {
+        for depth in 0..self.current_path.len() {
+            let index = self.current_path[depth];
+            let parent = self.current_path[..depth].to_vec();
+            let children = self.get_children_mut(&parent);
+            let node = children.remove(index);
+            children.insert(0, node);
+            self.current_path[depth] = 0;
+        }
+    }
+
+    /// Removes the current move and every move after it, going back a move.
+    pub(super) fn delete_current_move(&mut self) {
+        if let Some(index) = self.current_path.pop() {
+            let path = self.current_path.clone();
+            self.get_children_mut(&path).remove(index);
+        }
     }
 
     pub(super) fn get_current_round(&self) -> usize {
-        (self.current_turn_index + 1) / 2
+        (self.current_path.len() + self.get_first_round_offset()).div_ceil(2)
     }
 }
 
 impl Default for History {
     fn default() -> Self {
-        let (turns, current_turn_index, fifty_move_count, initial_state) = Default::default();
         Self {
-            turns,
-            current_turn_index,
-            fifty_move_count,
-            initial_state,
-            repetition_counter: vec![(BoardState::default(), 1)].into_iter().collect(),
+            variations: Vec::new(),
+            current_path: Vec::new(),
+            initial_state: BoardState::default(),
+            initial_fifty_move_count: 0,
+            initial_move_number: 1,
         }
     }
 }
@@ -1,23 +1,62 @@
+mod annotation;
+mod bitboard;
 mod board;
 mod board_state;
 mod castling_rights;
+mod clock;
 mod color;
 mod displacement;
+mod engine;
+mod epd;
+mod evaluation;
+mod event;
+mod fen;
 mod game;
 mod game_status;
 mod history;
+mod movegen;
 mod moves;
+mod perft;
+mod pgn;
 mod piece;
 mod player;
+mod polyglot;
 mod position;
 mod result;
 mod round_info;
+mod san;
+mod save;
+mod snapshot;
+mod syzygy;
+mod tablebase;
+#[cfg(test)]
+mod test_helpers;
+mod time_control;
 mod timer;
+mod transposition_table;
 mod turn;
+mod variant;
+mod zobrist;
 
+pub use annotation::{Annotation, EngineEval};
+pub use board::CHESS960_POSITIONS;
+pub use castling_rights::CastlingSide;
+pub use clock::{Clock, ManualClock, RealClock};
 pub use color::*;
+pub use engine::{Engine, SearchIterations, SearchLimits, SearchResult};
+pub use epd::{read_epd, run_perft_suite, run_suite, EpdRecord, EpdResult, SuiteReport};
+pub use event::{GameEvent, SubscriptionId};
 pub use game::*;
+pub use game_status::{DrawKind, GameStatus};
 pub use moves::*;
+pub use pgn::*;
 pub use piece::*;
 pub use player::*;
+pub use polyglot::{BookMove, OpeningBook};
 pub use position::*;
+pub use result::*;
+pub use san::Notation;
+pub use snapshot::PositionSnapshot;
+pub use tablebase::{Tablebase, Wdl};
+pub use time_control::{TimeBonus, TimeControl, TimePeriod};
+pub use variant::Variant;
@@ -0,0 +1,514 @@
+use crate::{
+    bitboard::{self, Bitboard},
+    board::Board,
+    board_state::BoardState,
+    castling_rights::CastlingRights,
+    color::Color,
+    moves::Move,
+    piece::Piece,
+    position::Position,
+    variant::Variant,
+};
+
+/// Returns the squares of `player`'s pieces attacking `square`, with
+/// `occupied` as the pieces that block sliding attacks.
+pub(super) fn get_attackers(
+    board: &Board,
+    square: usize,
+    player: Color,
+    occupied: Bitboard,
+) -> Bitboard {
+    let queens = board.get_bitboard(Piece::Queen(player));
+    let rooks = board.get_bitboard(Piece::Rook(player)) | queens;
+    let bishops = board.get_bitboard(Piece::Bishop(player)) | queens;
+
+    (bitboard::knight_attacks(square) & board.get_bitboard(Piece::Knight(player)))
+        | (bitboard::king_attacks(square) & board.get_bitboard(Piece::King(player)))
+        | (bitboard::pawn_attacks(!player, square) & board.get_bitboard(Piece::Pawn(player)))
+        | (bitboard::rook_attacks(square, occupied) & rooks)
+        | (bitboard::bishop_attacks(square, occupied) & bishops)
+}
+
+pub(super) fn is_square_attacked(board: &Board, square: usize, player: Color) -> bool {
+    !get_attackers(board, square, player, board.get_occupied()).is_empty()
+}
+
+/// Whether the king of the player to move is in check.
+pub(super) fn is_king_under_attack(state: &BoardState) -> bool {
+    state
+        .board
+        .get_bitboard(Piece::King(state.player))
+        .first()
+        .is_some_and(|king| is_square_attacked(&state.board, king, !state.player))
+}
+
+// A king next to the enemy king can't be attacked, since capturing it would
+// explode the capturing king as well
+fn get_atomic_attackers(
+    board: &Board,
+    square: usize,
+    player: Color,
+    occupied: Bitboard,
+) -> Bitboard {
+    let kings = board.get_bitboard(Piece::King(player));
+    if kings.is_empty() || !(bitboard::king_attacks(square) & kings).is_empty() {
+        Bitboard::EMPTY
+    } else {
+        get_attackers(board, square, player, occupied)
+    }
+}
+
+/// Whether the king of the player to move is in check under Atomic's rules.
+pub(super) fn is_atomic_king_under_attack(state: &BoardState) -> bool {
+    let board = &state.board;
+    board
+        .get_bitboard(Piece::King(state.player))
+        .first()
+        .is_some_and(|king| {
+            !get_atomic_attackers(board, king, !state.player, board.get_occupied()).is_empty()
+        })
+}
+
+/// Whether the player to move has a legal en passant capture.
+pub(super) fn can_capture_en_passant(state: &BoardState) -> bool {
+    let Some(en_passant) = state.en_passant_position else {
+        return false;
+    };
+    let mut generator = MoveGenerator::new(state);
+    let pawns = state.board.get_bitboard(Piece::Pawn(state.player))
+        & bitboard::pawn_attacks(!state.player, en_passant.get_square());
+    for from in pawns {
+        generator.add_en_passant_move(from);
+    }
+    !generator.moves.is_empty()
+}
+
+/// The piece `mv` captures, if any.
+pub(super) fn get_captured_piece(state: &BoardState, mv: &Move) -> Option<Piece> {
+    let mover = state.get_piece(&mv.from)?;
+    match state.get_piece(&mv.to) {
+        Some(piece) if piece.get_player() != state.player => Some(piece),
+        // a castling king lands on its own rook in Chess960
+        Some(_) => None,
+        None if mover.is_pawn() && mv.from.x != mv.to.x => Some(Piece::Pawn(!state.player)),
+        None => None,
+    }
+}
+
+/// Generates the legal moves for the player to move under the rules of the
+/// position's variant. A game the variant has already decided has none.
+pub(super) fn get_legal_moves(state: &BoardState) -> Vec<Move> {
+    if state.variant.get_loser(state).is_some() {
+        return Vec::new();
+    }
+    match state.variant {
+        Variant::Antichess => get_antichess_moves(state),
+        Variant::Atomic => get_atomic_moves(state),
+        _ => get_standard_moves(state),
+    }
+}
+
+// Pins and checks are resolved up front, so no move has to be played out to test for check
+fn get_standard_moves(state: &BoardState) -> Vec<Move> {
+    let mut generator = MoveGenerator::new(state);
+    generator.add_king_moves();
+    // only the king can get out of a double check
+    if !generator.checkers.has_many() {
+        generator.add_castling_moves(|square, occupied| {
+            !get_attackers(&state.board, square, !state.player, occupied).is_empty()
+        });
+        generator.add_piece_moves();
+        generator.add_pawn_moves();
+    }
+    generator.moves
+}
+
+// The king is an ordinary piece, and a player who can capture must
+fn get_antichess_moves(state: &BoardState) -> Vec<Move> {
+    let mut generator = MoveGenerator::unchecked(state);
+    generator.add_unchecked_king_moves(!generator.own);
+    generator.add_piece_moves();
+    generator.add_pawn_moves();
+
+    let enemy = generator.enemy;
+    let is_capture = |mv: &Move| {
+        enemy.contains(mv.to.get_square())
+            || (state.en_passant_position == Some(mv.to)
+                && state.get_piece(&mv.from).is_some_and(Piece::is_pawn))
+    };
+    if generator.moves.iter().any(is_capture) {
+        generator.moves.retain(is_capture);
+    }
+    generator.moves
+}
+
+// Explosions can remove any piece around the capture, including pinned
+// pieces and checkers, so each move is played out and the result checked
+fn get_atomic_moves(state: &BoardState) -> Vec<Move> {
+    let board = &state.board;
+    let player = state.player;
+    let mut generator = MoveGenerator::unchecked(state);
+    // kings can't capture, since they would explode themselves
+    generator.add_unchecked_king_moves(!generator.occupied);
+    if !is_atomic_king_under_attack(state) {
+        generator.add_castling_moves(|square, occupied| {
+            !get_atomic_attackers(board, square, !player, occupied).is_empty()
+        });
+    }
+    generator.add_piece_moves();
+    generator.add_pawn_moves();
+
+    generator.moves.retain(|mv| {
+        let mut next_state = *state;
+        next_state.move_piece(mv);
+        let next_board = &next_state.board;
+        match next_board.get_bitboard(Piece::King(player)).first() {
+            None => false,
+            Some(_) if next_board.get_bitboard(Piece::King(!player)).is_empty() => true,
+            Some(king) => {
+                get_atomic_attackers(next_board, king, !player, next_board.get_occupied())
+                    .is_empty()
+            }
+        }
+    });
+    generator.moves
+}
+
+struct MoveGenerator<'a> {
+    state: &'a BoardState,
+    player: Color,
+    own: Bitboard,
+    enemy: Bitboard,
+    occupied: Bitboard,
+    king: Option<usize>,
+    checkers: Bitboard,
+    // squares that block or capture a single checker, every square otherwise
+    check_mask: Bitboard,
+    pinned: Bitboard,
+    moves: Vec<Move>,
+}
+
+impl<'a> MoveGenerator<'a> {
+    fn new(state: &'a BoardState) -> Self {
+        let board = &state.board;
+        let player = state.player;
+        let own = board.get_color_bitboard(player);
+        let enemy = board.get_color_bitboard(!player);
+        let occupied = own | enemy;
+        let king = board.get_bitboard(Piece::King(player)).first();
+        let checkers = king.map_or(Bitboard::EMPTY, |king| {
+            get_attackers(board, king, !player, occupied)
+        });
+        let check_mask = match (king, checkers.first()) {
+            (Some(king), Some(checker)) => {
+                bitboard::between(king, checker) | Bitboard::from_square(checker)
+            }
+            _ => Bitboard::FULL,
+        };
+
+        let mut generator = Self {
+            state,
+            player,
+            own,
+            enemy,
+            occupied,
+            king,
+            checkers,
+            check_mask,
+            pinned: Bitboard::EMPTY,
+            moves: Vec::with_capacity(64),
+        };
+        generator.pinned = generator.get_pinned();
+        generator
+    }
+
+    // Generates moves without regard for the king's safety
+    fn unchecked(state: &'a BoardState) -> Self {
+        let own = state.board.get_color_bitboard(state.player);
+        let enemy = state.board.get_color_bitboard(!state.player);
+        Self {
+            state,
+            player: state.player,
+            own,
+            enemy,
+            occupied: own | enemy,
+            king: None,
+            checkers: Bitboard::EMPTY,
+            check_mask: Bitboard::FULL,
+            pinned: Bitboard::EMPTY,
+            moves: Vec::with_capacity(64),
+        }
+    }
+
+    fn get_pinned(&self) -> Bitboard {
+        let Some(king) = self.king else {
+            return Bitboard::EMPTY;
+        };
+        let board = &self.state.board;
+        let enemy = !self.player;
+        let queens = board.get_bitboard(Piece::Queen(enemy));
+        let snipers = (bitboard::rook_attacks(king, self.enemy)
+            & (board.get_bitboard(Piece::Rook(enemy)) | queens))
+            | (bitboard::bishop_attacks(king, self.enemy)
+                & (board.get_bitboard(Piece::Bishop(enemy)) | queens));
+
+        snipers.fold(Bitboard::EMPTY, |pinned, sniper| {
+            let blockers = bitboard::between(king, sniper) & self.occupied;
+            if !blockers.has_many() && !(blockers & self.own).is_empty() {
+                pinned | blockers
+            } else {
+                pinned
+            }
+        })
+    }
+
+    // the squares a piece may move to without exposing its king
+    fn get_pin_mask(&self, from: usize) -> Bitboard {
+        match self.king {
+            Some(king) if self.pinned.contains(from) => bitboard::line(king, from),
+            _ => Bitboard::FULL,
+        }
+    }
+
+    fn add_moves(&mut self, from: usize, destinations: Bitboard) {
+        let from = Position::from_square(from);
+        self.moves
+            .extend(destinations.map(|to| Move::new(from, Position::from_square(to))));
+    }
+
+    fn add_king_moves(&mut self) {
+        let Some(king) = self.king else {
+            return;
+        };
+        // the king must not be able to hide behind itself from a slider
+        let occupied = self.occupied & !Bitboard::from_square(king);
+        let destinations = (bitboard::king_attacks(king) & !self.own)
+            .filter(|&to| get_attackers(&self.state.board, to, !self.player, occupied).is_empty());
+        let from = Position::from_square(king);
+        self.moves
+            .extend(destinations.map(|to| Move::new(from, Position::from_square(to))));
+    }
+
+    fn add_unchecked_king_moves(&mut self, targets: Bitboard) {
+        for king in self.state.board.get_bitboard(Piece::King(self.player)) {
+            self.add_moves(king, bitboard::king_attacks(king) & targets);
+        }
+    }
+
+    // `is_attacked` tells whether a square would be attacked with the given
+    // pieces blocking, since what counts as an attack depends on the variant
+    fn add_castling_moves(&mut self, is_attacked: impl Fn(usize, Bitboard) -> bool) {
+        let Some(king) = self
+            .state
+            .board
+            .get_bitboard(Piece::King(self.player))
+            .first()
+        else {
+            return;
+        };
+        if !self.checkers.is_empty() {
+            return;
+        }
+        let castling_rights = &self.state.castling_rights;
+        let king_position = Position::from_square(king);
+
+        for right in CastlingRights::get_castling_rights(self.player) {
+            let Some(rook_position) = castling_rights.get_rook_position(right) else {
+                continue;
+            };
+            let (king_to, rook_to) = CastlingRights::get_destinations(right);
+            let (rook, king_to, rook_to) = (
+                rook_position.get_square(),
+                king_to.get_square(),
+                rook_to.get_square(),
+            );
+            let king_path = bitboard::between(king, king_to) | Bitboard::from_square(king_to);
+            let rook_path = bitboard::between(rook, rook_to) | Bitboard::from_square(rook_to);
+            // In Chess960 the king and rook may start on each other's paths
+            let occupied =
+                self.occupied & !Bitboard::from_square(king) & !Bitboard::from_square(rook);
+            // The rook still stands where it started while the king passes, but
+            // has moved by the time the king lands
+            let passing = self.occupied & !Bitboard::from_square(king);
+            let landed = occupied | Bitboard::from_square(rook_to);
+
+            if ((king_path | rook_path) & occupied).is_empty()
+                && (king_path & !Bitboard::from_square(king_to))
+                    .into_iter()
+                    .all(|square| !is_attacked(square, passing))
+                && !is_attacked(king_to, landed)
+            {
+                self.moves
+                    .push(castling_rights.get_castling_move(right, king_position));
+            }
+        }
+    }
+
+    fn add_piece_moves(&mut self) {
+        let board = &self.state.board;
+        let player = self.player;
+        for (piece, get_attacks) in [
+            (
+                Piece::Knight(player),
+                knight_attacks as fn(usize, Bitboard) -> Bitboard,
+            ),
+            (Piece::Bishop(player), bitboard::bishop_attacks),
+            (Piece::Rook(player), bitboard::rook_attacks),
+            (Piece::Queen(player), queen_attacks),
+        ] {
+            for from in board.get_bitboard(piece) {
+                let destinations = get_attacks(from, self.occupied)
+                    & !self.own
+                    & self.check_mask
+                    & self.get_pin_mask(from);
+                self.add_moves(from, destinations);
+            }
+        }
+    }
+
+    fn add_pawn_moves(&mut self) {
+        // Horde's pawns on the first rank may also move two squares
+        let (forward, start_ranks, last_rank): (isize, [usize; 2], usize) = match self.player {
+            Color::White => (8, [0, 1], 7),
+            Color::Black => (-8, [6, 7], 0),
+        };
+
+        for from in self.state.board.get_bitboard(Piece::Pawn(self.player)) {
+            let mask = self.check_mask & self.get_pin_mask(from);
+            let mut destinations = bitboard::pawn_attacks(self.player, from) & self.enemy;
+
+            let single = from.wrapping_add_signed(forward);
+            if !self.occupied.contains(single) {
+                destinations |= Bitboard::from_square(single);
+                let double = single.wrapping_add_signed(forward);
+                if start_ranks.contains(&(from / 8)) && !self.occupied.contains(double) {
+                    destinations |= Bitboard::from_square(double);
+                }
+            }
+
+            for to in destinations & mask {
+                self.add_pawn_move(from, to, to / 8 == last_rank);
+            }
+            self.add_en_passant_move(from);
+        }
+    }
+
+    fn add_pawn_move(&mut self, from: usize, to: usize, promotes: bool) {
+        let (from, to) = (Position::from_square(from), Position::from_square(to));
+        if promotes {
+            for piece in self.state.variant.get_promotion_options(self.player) {
+                self.moves.push(Move::with_promotion(from, to, piece));
+            }
+        } else {
+            self.moves.push(Move::new(from, to));
+        }
+    }
+
+    fn add_en_passant_move(&mut self, from: usize) {
+        let Some(en_passant) = self.state.en_passant_position else {
+            return;
+        };
+        let to = en_passant.get_square();
+        if !bitboard::pawn_attacks(self.player, from).contains(to) {
+            return;
+        }
+
+        // En passant removes two pieces from a rank at once, which pin and
+        // check masks can't describe, so test the resulting position directly.
+        let captured = Bitboard::from_square(Position::new(en_passant.x, from / 8).get_square());
+        let occupied =
+            (self.occupied ^ Bitboard::from_square(from) ^ captured) | Bitboard::from_square(to);
+        let is_legal = self.king.is_none_or(|king| {
+            (get_attackers(&self.state.board, king, !self.player, occupied) & !captured).is_empty()
+        });
+        if is_legal {
+            self.add_moves(from, Bitboard::from_square(to));
+        }
+    }
+}
+
+fn knight_attacks(square: usize, _occupied: Bitboard) -> Bitboard {
+    bitboard::knight_attacks(square)
+}
+
+fn queen_attacks(square: usize, occupied: Bitboard) -> Bitboard {
+    bitboard::rook_attacks(square, occupied) | bitboard::bishop_attacks(square, occupied)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+    use crate::fen::Fen;
+
+    fn get_moves(fen: &str) -> HashSet<String> {
+        let fen: Fen = fen.parse().unwrap();
+        get_legal_moves(&fen.state)
+            .into_iter()
+            .map(|mv| format!("{}{}", mv.from, mv.to))
+            .collect()
+    }
+
+    fn lan_set(moves: &[&str]) -> HashSet<String> {
+        moves.iter().map(|mv| mv.to_string()).collect()
+    }
+
+    #[test]
+    fn test_initial_position() {
+        assert_eq!(get_legal_moves(&BoardState::default()).len(), 20);
+    }
+
+    #[test]
+    fn test_pinned_pieces() {
+        // the knight can't move, the rook can only slide along the pin
+        let moves = get_moves("4r2k/8/8/b7/4R3/8/3N4/4K3 w - - 0 1");
+        assert!(moves.iter().all(|mv| !mv.starts_with("d2")));
+        let rook_moves: HashSet<String> = moves
+            .into_iter()
+            .filter(|mv| mv.starts_with("e4"))
+            .collect();
+        assert_eq!(
+            rook_moves,
+            lan_set(&["e4e2", "e4e3", "e4e5", "e4e6", "e4e7", "e4e8"])
+        );
+    }
+
+    #[test]
+    fn test_check_evasions() {
+        // single check: capture the checker or step aside, not along the rank
+        assert_eq!(
+            get_moves("4k3/8/8/8/8/8/8/R2rK3 w - - 0 1"),
+            lan_set(&["a1d1", "e1d1", "e1e2", "e1f2"])
+        );
+        // double check: only the king moves
+        let moves = get_moves("4k3/8/8/8/1b6/8/8/R3K2r w - - 0 1");
+        assert!(!moves.is_empty());
+        assert!(moves.iter().all(|mv| mv.starts_with("e1")));
+    }
+
+    #[test]
+    fn test_castling() {
+        assert!(get_moves("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1")
+            .is_superset(&lan_set(&["e1g1", "e1c1"])));
+        // the king can't pass through an attacked square
+        let moves = get_moves("r3k2r/8/8/8/8/8/5r2/R3K2R w KQkq - 0 1");
+        assert!(!moves.contains("e1g1") && moves.contains("e1c1"));
+        // or castle out of check
+        let moves = get_moves("r3k2r/8/8/8/8/8/4r3/R3K2R w KQkq - 0 1");
+        assert!(!moves.contains("e1g1") && !moves.contains("e1c1"));
+        let moves = get_moves("r3k2r/8/8/8/8/8/8/R3K2R b KQkq - 0 1");
+        assert!(moves.is_superset(&lan_set(&["e8g8", "e8c8"])));
+        let moves = get_moves("r3k2r/8/8/8/8/8/4R3/R3K2R b KQkq - 0 1");
+        assert!(!moves.contains("e8g8") && !moves.contains("e8c8"));
+    }
+
+    #[test]
+    fn test_en_passant() {
+        assert!(get_moves("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1").contains("e5d6"));
+        // capturing would expose the king along the rank
+        assert!(!get_moves("8/8/8/K2pP2r/8/8/8/4k3 w - d6 0 1").contains("e5d6"));
+        // capturing the checking pawn en passant is allowed
+        assert!(get_moves("8/8/8/3pP3/4K3/8/8/4k3 w - d6 0 1").contains("e5d6"));
+    }
+}
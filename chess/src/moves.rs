@@ -2,17 +2,30 @@ use std::fmt;
 
 use serde::{Deserialize, Serialize};
 
-use crate::{piece::Piece, position::Position};
+use crate::{color::Color, piece::Piece, position::Position};
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
 pub struct Move {
     pub from: Position,
     pub to: Position,
+    pub promotion: Option<Piece>,
 }
 
 impl Move {
     pub fn new(from: Position, to: Position) -> Self {
-        Self { from, to }
+        Self {
+            from,
+            to,
+            promotion: None,
+        }
+    }
+
+    pub fn with_promotion(from: Position, to: Position, piece: Piece) -> Self {
+        Self {
+            from,
+            to,
+            promotion: Some(piece),
+        }
     }
 
     pub fn get_positions(&self) -> [Position; 2] {
@@ -21,7 +34,7 @@ impl Move {
 
     // creates move from the "long algebraic notation" that stockfish uses
     pub fn from_lan(lan: &str) -> Option<Self> {
-        Some(Self::new(
+        let mut mv = Self::new(
             Position::new(
                 lan.chars().nth(0)? as usize - 'a' as usize,
                 lan.chars().nth(1)? as usize - '1' as usize,
@@ -30,7 +43,21 @@ impl Move {
                 lan.chars().nth(2)? as usize - 'a' as usize,
                 lan.chars().nth(3)? as usize - '1' as usize,
             ),
-        ))
+        );
+        if let Some(c) = lan.chars().nth(4) {
+            // the promoting player can be inferred from the rank the pawn lands on
+            let player = if mv.to.y == 7 {
+                Color::White
+            } else {
+                Color::Black
+            };
+            mv.promotion = Some(
+                Piece::get_promotion_options(player)
+                    .into_iter()
+                    .find(|piece| piece.get_fen_char().to_ascii_lowercase() == c)?,
+            );
+        }
+        Some(mv)
     }
 
     pub fn to_str(&self, piece: Piece) -> String {
@@ -40,6 +67,40 @@ impl Move {
 
 impl fmt::Display for Move {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{} -> {}", self.from, self.to)
+        write!(f, "{} -> {}", self.from, self.to)?;
+        if let Some(piece) = self.promotion {
+            write!(f, "={piece}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_lan() {
+        assert_eq!(
+            Move::from_lan("e2e4"),
+            Some(Move::new(Position::new(4, 1), Position::new(4, 3)))
+        );
+        assert_eq!(
+            Move::from_lan("e7e8n"),
+            Some(Move::with_promotion(
+                Position::new(4, 6),
+                Position::new(4, 7),
+                Piece::Knight(Color::White)
+            ))
+        );
+        assert_eq!(
+            Move::from_lan("a2b1r"),
+            Some(Move::with_promotion(
+                Position::new(0, 1),
+                Position::new(1, 0),
+                Piece::Rook(Color::Black)
+            ))
+        );
+        assert_eq!(Move::from_lan("e7e8k"), None);
     }
 }
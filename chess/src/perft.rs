@@ -0,0 +1,172 @@
+use crate::{board_state::BoardState, movegen, moves::Move};
+
+/// Counts the leaf nodes of the legal move tree `depth` plies deep.
+pub(super) fn perft(state: &BoardState, depth: usize) -> u64 {
+    if depth == 0 {
+        return 1;
+    }
+    let moves = movegen::get_legal_moves(state);
+    if depth == 1 {
+        return moves.len() as u64;
+    }
+    moves
+        .iter()
+        .map(|mv| {
+            let mut next_state = *state;
+            next_state.move_piece(mv);
+            perft(&next_state, depth - 1)
+        })
+        .sum()
+}
+
+/// Splits the perft count by the first move, to narrow down where two move
+/// generators disagree.
+pub(super) fn divide(state: &BoardState, depth: usize) -> Vec<(Move, u64)> {
+    if depth == 0 {
+        return Vec::new();
+    }
+    movegen::get_legal_moves(state)
+        .into_iter()
+        .map(|mv| {
+            let mut next_state = *state;
+            next_state.move_piece(&mv);
+            (mv, perft(&next_state, depth - 1))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fen::Fen, variant::Variant};
+
+    // https://www.chessprogramming.org/Perft_Results
+    const INITIAL: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+    const KIWIPETE: &str = "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1";
+    const POSITION_3: &str = "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1";
+    const POSITION_4: &str = "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1";
+    const POSITION_4_MIRRORED: &str =
+        "r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1";
+    const POSITION_5: &str = "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8";
+    const POSITION_6: &str =
+        "r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10";
+    // https://www.chessprogramming.org/Chess960_Perft_Results
+    const CHESS960: [(&str, [u64; 4]); 4] = [
+        (
+            "bqnb1rkr/pp3ppp/3ppn2/2p5/5P2/P2P4/NPP1P1PP/BQ1BNRKR w HFhf - 2 9",
+            [21, 528, 12189, 326672],
+        ),
+        (
+            "2nnrbkr/p1qppppp/8/1ppb4/6PP/3PP3/PPP2P2/BQNNRBKR w HEhe - 1 9",
+            [21, 807, 18002, 667366],
+        ),
+        (
+            "b1q1rrkb/pppppppp/3nn3/8/P7/1PPP4/4PPPP/BQNNRKRB w GE - 1 9",
+            [20, 479, 10471, 273318],
+        ),
+        (
+            "qbbnnrkr/2pp2pp/p7/1p2pp2/8/P3PP2/1PPP1KPP/QBBNNR1R w hf - 0 9",
+            [22, 593, 13440, 382958],
+        ),
+    ];
+
+    fn assert_perft(fen: &str, expected: &[u64]) {
+        assert_variant_perft(Variant::Standard, fen, expected);
+    }
+
+    fn assert_variant_perft(variant: Variant, fen: &str, expected: &[u64]) {
+        let Fen { state, .. } = Fen::parse(fen, variant).unwrap();
+        for (depth, &nodes) in expected.iter().enumerate() {
+            assert_eq!(
+                perft(&state, depth + 1),
+                nodes,
+                "{fen} at depth {}",
+                depth + 1
+            );
+        }
+    }
+
+    #[test]
+    fn test_initial_position() {
+        assert_perft(INITIAL, &[20, 400, 8902, 197281]);
+    }
+
+    #[test]
+    fn test_kiwipete() {
+        assert_perft(KIWIPETE, &[48, 2039, 97862]);
+    }
+
+    #[test]
+    fn test_position_3() {
+        assert_perft(POSITION_3, &[14, 191, 2812, 43238, 674624]);
+    }
+
+    #[test]
+    fn test_position_4() {
+        assert_perft(POSITION_4, &[6, 264, 9467]);
+        assert_perft(POSITION_4_MIRRORED, &[6, 264, 9467]);
+    }
+
+    #[test]
+    fn test_position_5() {
+        assert_perft(POSITION_5, &[44, 1486, 62379]);
+    }
+
+    #[test]
+    fn test_position_6() {
+        assert_perft(POSITION_6, &[46, 2079, 89890]);
+    }
+
+    #[test]
+    fn test_chess960() {
+        for (fen, expected) in CHESS960 {
+            assert_perft(fen, &expected);
+        }
+    }
+
+    // https://github.com/niklasf/shakmaty/tree/master/tests
+    #[test]
+    fn test_variants() {
+        assert_variant_perft(
+            Variant::ThreeCheck,
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 1+1",
+            &[48, 2039, 97848],
+        );
+        assert_variant_perft(Variant::Antichess, INITIAL, &[20, 400, 8067]);
+        assert_variant_perft(
+            Variant::Antichess,
+            "8/1p6/8/8/8/8/P7/8 w - -",
+            &[2, 4, 4, 3, 1, 0],
+        );
+        assert_variant_perft(
+            Variant::Atomic,
+            "rn2kb1r/1pp1p2p/p2q1pp1/3P4/2P3b1/4PN2/PP3PPP/R2QKB1R b KQkq -",
+            &[40, 1238, 45237],
+        );
+        // the castling rook shields the king's path from the rook behind it
+        assert_variant_perft(
+            Variant::Atomic,
+            "8/8/8/8/8/8/2k5/rR4KR w KQ -",
+            &[18, 180, 4364],
+        );
+        assert_variant_perft(
+            Variant::Horde,
+            "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq -",
+            &[8, 128, 1274, 23310],
+        );
+    }
+
+    #[test]
+    fn test_divide() {
+        let Fen { state, .. } = KIWIPETE.parse().unwrap();
+        let divide = divide(&state, 2);
+        assert_eq!(divide.len(), 48);
+        assert_eq!(divide.iter().map(|(_, nodes)| nodes).sum::<u64>(), 2039);
+        // castling both ways is part of Kiwipete's first ply
+        for lan in ["e1g1", "e1c1"] {
+            assert!(divide
+                .iter()
+                .any(|(mv, _)| *mv == Move::from_lan(lan).unwrap()));
+        }
+    }
+}
@@ -0,0 +1,786 @@
+use std::{iter::Peekable, str::Chars};
+
+use crate::{
+    annotation::get_assessment_nag,
+    color::Color,
+    game::Game,
+    game_status::{DrawKind, GameStatus},
+    history::{History, Node},
+    result::{PgnError, PgnErrorKind, SanError},
+    time_control::TimeControl,
+    variant::Variant,
+};
+
+// Export format lines should stay under 80 characters
+const MAX_LINE_LENGTH: usize = 79;
+
+/// The tags written at the top of a PGN file. Unknown values are written as "?".
+#[derive(Clone, Debug, PartialEq)]
+pub struct PgnTags {
+    pub event: String,
+    pub site: String,
+    pub date: String,
+    pub round: String,
+    pub white: String,
+    pub black: String,
+}
+
+impl Default for PgnTags {
+    fn default() -> Self {
+        Self {
+            event: "?".into(),
+            site: "?".into(),
+            date: "????.??.??".into(),
+            round: "?".into(),
+            white: "?".into(),
+            black: "?".into(),
+        }
+    }
+}
+
+impl PgnTags {
+    fn set(&mut self, name: &str, value: String) {
+        match name {
+            "Event" => self.event = value,
+            "Site" => self.site = value,
+            "Date" => self.date = value,
+            "Round" => self.round = value,
+            "White" => self.white = value,
+            "Black" => self.black = value,
+            _ => (),
+        }
+    }
+}
+
+fn write_tag(pgn: &mut String, name: &str, value: &str) {
+    let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+    pgn.push_str(&format!("[{name} \"{value}\"]\n"));
+}
+
+/// Writes the line starting with the first of `nodes`, the `i`th turn of the
+/// game, with the other nodes as variations in parentheses after its first move.
+fn write_line(history: &History, mut nodes: &[Node], mut i: usize, tokens: &mut Vec<String>) {
+    // Black's moves are numbered at the start of a line and after a variation
+    let mut needs_number = true;
+    while let Some((node, variations)) = nodes.split_first() {
+        let move_number = history.get_move_number(i);
+        let is_white_turn = (i + history.get_first_round_offset()).is_multiple_of(2);
+        if is_white_turn {
+            tokens.push(format!("{move_number}."));
+        } else if needs_number {
+            tokens.push(format!("{move_number}..."));
+        }
+        tokens.push(node.turn.to_string());
+        tokens.extend(node.turn.annotation.get_pgn_tokens());
+        for variation in variations {
+            let start = tokens.len();
+            write_line(history, std::slice::from_ref(variation), i, tokens);
+            tokens[start].insert(0, '(');
+            tokens.last_mut().unwrap().push(')');
+        }
+        needs_number = !variations.is_empty() || node.turn.annotation.has_pgn_comments();
+        nodes = &node.children;
+        i += 1;
+    }
+}
+
+fn get_move_text_tokens(history: &History) -> Vec<String> {
+    let mut tokens = vec![];
+    write_line(history, history.get_children(&[]), 0, &mut tokens);
+    tokens
+}
+
+pub(super) fn write_pgn(
+    tags: &PgnTags,
+    history: &History,
+    status: GameStatus,
+    [white_time_control, black_time_control]: [&TimeControl; 2],
+) -> String {
+    let result = status.get_pgn_result();
+    let mut pgn = String::new();
+    write_tag(&mut pgn, "Event", &tags.event);
+    write_tag(&mut pgn, "Site", &tags.site);
+    write_tag(&mut pgn, "Date", &tags.date);
+    write_tag(&mut pgn, "Round", &tags.round);
+    write_tag(&mut pgn, "White", &tags.white);
+    write_tag(&mut pgn, "Black", &tags.black);
+    write_tag(&mut pgn, "Result", result);
+    if matches!(
+        status,
+        GameStatus::Timeout(..)
+            | GameStatus::Draw(DrawKind::TimeoutVsInsufficientMaterial)
+            | GameStatus::Armageddon(DrawKind::TimeoutVsInsufficientMaterial)
+    ) {
+        write_tag(&mut pgn, "Termination", "time forfeit");
+    }
+    // PGN has no tag for time odds, so each player's control gets its own
+    if white_time_control != black_time_control {
+        for (name, time_control) in [
+            ("WhiteTimeControl", white_time_control),
+            ("BlackTimeControl", black_time_control),
+        ] {
+            if let Some(value) = time_control.to_pgn() {
+                write_tag(&mut pgn, name, &value);
+            }
+        }
+    } else if *white_time_control != TimeControl::default() {
+        if let Some(value) = white_time_control.to_pgn() {
+            write_tag(&mut pgn, "TimeControl", &value);
+        }
+    }
+    let initial_state = history.get_initial_fen().state;
+    match initial_state.variant {
+        Variant::Standard if initial_state.castling_rights.is_chess960() => {
+            write_tag(&mut pgn, "Variant", "Chess960")
+        }
+        Variant::Standard => (),
+        variant => write_tag(&mut pgn, "Variant", &variant.to_string()),
+    }
+    if !history.starts_from_initial_position() {
+        write_tag(&mut pgn, "SetUp", "1");
+        write_tag(&mut pgn, "FEN", &history.get_initial_fen().to_string());
+    }
+    pgn.push('\n');
+
+    let mut line = String::new();
+    for token in get_move_text_tokens(history)
+        .into_iter()
+        .chain([result.to_string()])
+    {
+        if !line.is_empty() && line.len() + 1 + token.len() > MAX_LINE_LENGTH {
+            pgn.push_str(&line);
+            pgn.push('\n');
+            line.clear();
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line.push_str(&token);
+    }
+    pgn.push_str(&line);
+    pgn.push('\n');
+    pgn
+}
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    VariationStart,
+    VariationEnd,
+    MoveNumber,
+    San(String),
+    Result(String),
+}
+
+/// Splits PGN text into tokens, keeping track of where each one starts for error reporting.
+struct PgnReader<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl<'a> PgnReader<'a> {
+    fn new(pgn: &'a str) -> Self {
+        Self {
+            chars: pgn.chars().peekable(),
+            line: 1,
+            column: 1,
+        }
+    }
+
+    fn error(&self, kind: PgnErrorKind) -> PgnError {
+        PgnError {
+            line: self.line,
+            column: self.column,
+            kind,
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), PgnError> {
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.next_char();
+                Ok(())
+            }
+            Some(c) => Err(self.error(PgnErrorKind::UnexpectedCharacter(c))),
+            None => Err(self.error(PgnErrorKind::UnexpectedEnd)),
+        }
+    }
+
+    fn take_while(&mut self, predicate: impl Fn(char) -> bool) -> String {
+        let mut s = String::new();
+        while let Some(c) = self.peek().filter(|&c| predicate(c)) {
+            s.push(c);
+            self.next_char();
+        }
+        s
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.take_while(char::is_whitespace);
+    }
+
+    fn read_until(&mut self, end: char) -> Result<String, PgnError> {
+        let s = self.take_while(|c| c != end);
+        self.expect(end)?;
+        Ok(s)
+    }
+
+    fn read_tag(&mut self) -> Result<Token, PgnError> {
+        self.expect('[')?;
+        self.skip_whitespace();
+        let name = self.take_while(|c| c.is_ascii_alphanumeric() || c == '_');
+        self.skip_whitespace();
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => break,
+                Some('\\') => value.extend(self.next_char()),
+                Some(c) => value.push(c),
+                None => return Err(self.error(PgnErrorKind::UnexpectedEnd)),
+            }
+        }
+        self.skip_whitespace();
+        self.expect(']')?;
+        Ok(Token::Tag(name, value))
+    }
+
+    fn read_symbol(&mut self) -> Result<Token, PgnError> {
+        let symbol = self.take_while(|c| c.is_ascii_alphanumeric() || "_+#=:-/".contains(c));
+        if matches!(symbol.as_str(), "1-0" | "0-1" | "1/2-1/2") {
+            return Ok(Token::Result(symbol));
+        }
+        if symbol.chars().all(|c| c.is_ascii_digit()) {
+            if self.take_while(|c| c == '.').is_empty() {
+                return Err(self.error(PgnErrorKind::InvalidMoveNumber));
+            }
+            return Ok(Token::MoveNumber);
+        }
+        Ok(Token::San(symbol))
+    }
+
+    /// Reads the next token, along with the line and column it starts at.
+    fn next_token(&mut self) -> Result<Option<(Token, usize, usize)>, PgnError> {
+        loop {
+            self.skip_whitespace();
+            let (line, column) = (self.line, self.column);
+            let token = match self.peek() {
+                None => return Ok(None),
+                Some('%') if column == 1 => {
+                    self.take_while(|c| c != '\n');
+                    continue;
+                }
+                Some(';') => {
+                    self.take_while(|c| c != '\n');
+                    continue;
+                }
+                Some('(') => {
+                    self.next_char();
+                    Token::VariationStart
+                }
+                Some(')') => {
+                    self.next_char();
+                    Token::VariationEnd
+                }
+                Some('[') => self.read_tag()?,
+                Some('{') => {
+                    self.next_char();
+                    Token::Comment(self.read_until('}')?.trim().to_string())
+                }
+                Some('$') => {
+                    self.next_char();
+                    let nag = self.take_while(|c| c.is_ascii_digit());
+                    Token::Nag(
+                        nag.parse()
+                            .map_err(|_| self.error(PgnErrorKind::UnexpectedCharacter('$')))?,
+                    )
+                }
+                // Move assessments like "!?" are short for NAGs
+                Some('!' | '?') => {
+                    let symbol = self.take_while(|c| c == '!' || c == '?');
+                    Token::Nag(
+                        get_assessment_nag(&symbol)
+                            .ok_or_else(|| self.error(PgnErrorKind::UnexpectedCharacter('!')))?,
+                    )
+                }
+                Some('*') => {
+                    self.next_char();
+                    Token::Result("*".into())
+                }
+                Some(c) if c.is_ascii_alphanumeric() => self.read_symbol()?,
+                Some(c) => return Err(self.error(PgnErrorKind::UnexpectedCharacter(c))),
+            };
+            return Ok(Some((token, line, column)));
+        }
+    }
+}
+
+pub(super) fn read_pgn(pgn: &str) -> Result<(Game, PgnTags), PgnError> {
+    let mut reader = PgnReader::new(pgn);
+    let mut tags = PgnTags::default();
+    let mut fen = None;
+    let mut variant = Variant::Standard;
+    let mut time_controls: [TimeControl; 2] = Default::default();
+    let mut game: Option<Game> = None;
+    let mut result = None;
+    let mut termination = String::new();
+    // The positions to return to at the end of each open variation
+    let mut variation_paths = vec![];
+    // Comments and NAGs belong to the move before them, unless a variation
+    // started since
+    let mut can_annotate = false;
+
+    while let Some((token, line, column)) = reader.next_token()? {
+        let at = |kind: PgnErrorKind| PgnError { line, column, kind };
+        match token {
+            Token::Tag(name, value) if game.is_none() => match name.as_str() {
+                "FEN" => fen = Some(value),
+                "Result" => result = Some(value),
+                "Termination" => termination = value,
+                "Variant" => {
+                    variant = value
+                        .parse()
+                        .map_err(|_| at(PgnErrorKind::UnknownVariant(value)))?
+                }
+                "TimeControl" | "WhiteTimeControl" | "BlackTimeControl"
+                    if value != "?" && value != "-" =>
+                {
+                    let time_control = TimeControl::from_pgn(&value)
+                        .ok_or_else(|| at(PgnErrorKind::InvalidTimeControl(value)))?;
+                    match name.as_str() {
+                        "WhiteTimeControl" => time_controls[Color::White as usize] = time_control,
+                        "BlackTimeControl" => time_controls[Color::Black as usize] = time_control,
+                        _ => time_controls = [time_control.clone(), time_control],
+                    }
+                }
+                _ => tags.set(&name, value),
+            },
+            Token::Tag(..) => break,
+            Token::Comment(comment) => {
+                let game = game.as_mut().filter(|_| can_annotate);
+                if let Some(annotation) = game.and_then(Game::get_annotation_mut) {
+                    annotation.add_pgn_comment(&comment);
+                }
+            }
+            Token::Nag(nag) => {
+                let game = game.as_mut().filter(|_| can_annotate);
+                if let Some(annotation) = game.and_then(Game::get_annotation_mut) {
+                    annotation.nags.push(nag);
+                }
+            }
+            Token::MoveNumber => (),
+            // A variation replaces the move before it
+            Token::VariationStart => {
+                let game = game
+                    .as_mut()
+                    .ok_or_else(|| at(PgnErrorKind::UnbalancedVariation))?;
+                let path = game.get_current_path();
+                let mut variation_path = path.clone();
+                variation_path.pop();
+                variation_paths.push(path);
+                game.go_to_path(variation_path);
+                can_annotate = false;
+            }
+            Token::VariationEnd => {
+                let path = variation_paths
+                    .pop()
+                    .ok_or_else(|| at(PgnErrorKind::UnbalancedVariation))?;
+                game.as_mut().unwrap().go_to_path(path);
+            }
+            Token::San(san) => {
+                let game = match &mut game {
+                    Some(game) => game,
+                    None => game.insert(match &fen {
+                        Some(fen) => {
+                            Game::from_variant_fen(variant, fen).map_err(|err| at(err.into()))?
+                        }
+                        None => Game::with_variant(variant),
+                    }),
+                };
+                let mv = game.parse_san(&san).map_err(|err| at(err.into()))?;
+                game.replay_move(mv)
+                    .map_err(|_| at(SanError::Illegal(san).into()))?;
+                can_annotate = true;
+            }
+            // the result after the moves is the one that counts
+            Token::Result(value) => {
+                result = Some(value);
+                break;
+            }
+        }
+    }
+    if !variation_paths.is_empty() {
+        return Err(reader.error(PgnErrorKind::UnbalancedVariation));
+    }
+
+    let [white_time_control, black_time_control] = time_controls;
+    let game = match (game, fen) {
+        (Some(game), _) => game,
+        (None, Some(fen)) => {
+            Game::from_variant_fen(variant, &fen).map_err(|err| reader.error(err.into()))?
+        }
+        (None, None) => Game::with_variant(variant),
+    };
+    // the clocks start again from the position after the last move
+    let mut game = game.with_time_controls(white_time_control, black_time_control);
+    game.finish_replay(
+        result
+            .as_deref()
+            .and_then(|result| get_result_status(result, &termination)),
+    );
+    Ok((game, tags))
+}
+
+// The status a result gives a game whose final position doesn't decide it.
+// PGN doesn't say how a game was won, so a win counts as a resignation unless
+// the termination says the loser ran out of time.
+fn get_result_status(result: &str, termination: &str) -> Option<GameStatus> {
+    let is_time_forfeit = termination.eq_ignore_ascii_case("time forfeit");
+    let loser = match result {
+        "1-0" => Color::Black,
+        "0-1" => Color::White,
+        "1/2-1/2" if is_time_forfeit => {
+            return Some(GameStatus::Draw(DrawKind::TimeoutVsInsufficientMaterial))
+        }
+        "1/2-1/2" => return Some(GameStatus::Draw(DrawKind::Agreement)),
+        _ => return None,
+    };
+    if is_time_forfeit {
+        Some(GameStatus::Timeout(loser))
+    } else {
+        Some(GameStatus::Resignation(loser))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use web_time::Duration;
+
+    use super::*;
+    use crate::{
+        annotation::{Annotation, EngineEval},
+        game::Game,
+        test_helpers::play,
+        time_control::{TimeBonus, TimePeriod},
+    };
+
+    #[test]
+    fn test_write_pgn() {
+        let mut game = Game::new();
+        play(
+            &mut game,
+            &["e2e4", "e7e5", "f1c4", "b8c6", "d1h5", "g8f6", "h5f7"],
+        );
+        let tags = PgnTags {
+            white: "Alice".into(),
+            black: "Bob \"the Builder\"".into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            game.get_pgn_str(&tags),
+            "[Event \"?\"]\n\
+             [Site \"?\"]\n\
+             [Date \"????.??.??\"]\n\
+             [Round \"?\"]\n\
+             [White \"Alice\"]\n\
+             [Black \"Bob \\\"the Builder\\\"\"]\n\
+             [Result \"1-0\"]\n\
+             \n\
+             1. e4 e5 2. Bc4 Nc6 3. Qh5 Nf6 4. Qxf7# 1-0\n"
+        );
+    }
+
+    #[test]
+    fn test_write_pgn_from_position() {
+        let fen = "r3k2r/8/8/3p4/4P3/8/1p6/R3K1NR b KQkq - 0 20";
+        let mut game = Game::from_fen(fen).unwrap();
+        play(
+            &mut game,
+            &["e8c8", "e4d5", "h8h1", "e1e2", "b2b1n", "d5d6"],
+        );
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.contains(&format!("[SetUp \"1\"]\n[FEN \"{fen}\"]\n")));
+        assert!(pgn.ends_with("\n20... O-O-O 21. exd5 Rxh1 22. Ke2 b1=N 23. d6 *\n"));
+    }
+
+    #[test]
+    fn test_disambiguation() {
+        let fen = "4k3/8/8/R7/1N3N2/8/8/R3K3 w - - 0 1";
+        let mut game = Game::from_fen(fen).unwrap();
+        play(&mut game, &["b4d5", "e8f7", "a1a3", "f7e8"]);
+        assert!(game
+            .get_pgn_str(&PgnTags::default())
+            .ends_with("1. Nbd5 Kf7 2. R1a3 Ke8 *\n"));
+
+        let mut game = Game::from_fen(fen).unwrap();
+        play(&mut game, &["a5a3", "e8f7", "f4d3"]);
+        assert!(game
+            .get_pgn_str(&PgnTags::default())
+            .ends_with("1. R5a3 Kf7 2. Nfd3 *\n"));
+    }
+
+    #[test]
+    fn test_read_pgn_round_trip() {
+        let mut game = Game::from_fen("r3k2r/8/8/3p4/4P3/8/1p6/R3K1NR b KQkq - 0 20").unwrap();
+        play(
+            &mut game,
+            &["e8c8", "e4d5", "h8h1", "e1e2", "b2b1n", "d5d6"],
+        );
+        let tags = PgnTags {
+            event: "Club \\ \"Championship\"".into(),
+            ..Default::default()
+        };
+        let pgn = game.get_pgn_str(&tags);
+        let (read_game, read_tags) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read_tags, tags);
+        assert_eq!(read_game.get_pgn_str(&read_tags), pgn);
+        assert_eq!(read_game.get_fen_str(), game.get_fen_str());
+    }
+
+    #[test]
+    fn test_variation_round_trip() {
+        let mut game = Game::new();
+        play(&mut game, &["e2e4", "e7e5", "g1f3", "b8c6"]);
+        game.go_to_start();
+        play(&mut game, &["e2e4", "c7c5", "g1f3"]);
+        game.go_back_a_move();
+        play(&mut game, &["b1c3", "b8c6"]);
+        game.go_to_start();
+        play(&mut game, &["d2d4"]);
+        game.resume();
+
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.ends_with("1. e4 (1. d4) 1... e5 (1... c5 2. Nf3 (2. Nc3 Nc6)) 2. Nf3 Nc6 *\n"));
+        let (read_game, _) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read_game.get_pgn_str(&PgnTags::default()), pgn);
+        assert_eq!(read_game.get_fen_str(), game.get_fen_str());
+
+        for pgn in ["1. e4 (1. d4 *", "1. e4 e5) *", "(1. e4) *"] {
+            assert_eq!(
+                Game::from_pgn(pgn).err().unwrap().kind,
+                PgnErrorKind::UnbalancedVariation
+            );
+        }
+    }
+
+    #[test]
+    fn test_annotation_round_trip() {
+        let pgn = "1. e4 {[%clk 0:05:00]} 1... e5 $2 {[%eval 0.3] [%clk 0:04:58]} {Too\n\
+                   passive [%csl Ge5]} 2. Nf3 !? (2. f4 ! {The gambit}) 2... Nc6 *";
+        let (mut game, _) = Game::from_pgn(pgn).unwrap();
+        game.go_to_start();
+        game.go_forward_a_move();
+        assert_eq!(
+            game.get_annotation().unwrap().clock,
+            Some(Duration::from_secs(300))
+        );
+        game.go_forward_a_move();
+        assert_eq!(
+            game.get_annotation(),
+            Some(&Annotation {
+                comments: vec!["Too passive [%csl Ge5]".into()],
+                nags: vec![2],
+                clock: Some(Duration::from_secs(298)),
+                eval: Some(EngineEval::Centipawns(30)),
+            })
+        );
+        game.go_to_variation(1).unwrap();
+        assert_eq!(game.get_annotation().unwrap().comments, ["The gambit"]);
+
+        let written = game.get_pgn_str(&PgnTags::default());
+        assert!(written.ends_with(
+            "1. e4 {[%clk 0:05:00]} 1... e5 $2 {[%eval 0.30] [%clk 0:04:58]} {Too passive\n\
+             [%csl Ge5]} 2. Nf3 $5 (2. f4 $1 {The gambit}) 2... Nc6 *\n"
+        ));
+        let (read_game, _) = Game::from_pgn(&written).unwrap();
+        assert_eq!(read_game.get_pgn_str(&PgnTags::default()), written);
+
+        // a comment at the start of a variation isn't about the move before it
+        let (game, _) = Game::from_pgn("1. e4 ({Instead} 1. d4) *").unwrap();
+        assert!(game.get_annotation().unwrap().is_empty());
+        let error = Game::from_pgn("1. e4 !!! *").err().unwrap();
+        assert_eq!(error.kind, PgnErrorKind::UnexpectedCharacter('!'));
+    }
+
+    #[test]
+    fn test_result_round_trip() {
+        let (game, _) = Game::from_pgn("1. e4 e5 2. Nf3 1-0").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Resignation(Color::Black));
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.contains("[Result \"1-0\"]"));
+        assert!(pgn.ends_with("2. Nf3 1-0\n"));
+        assert_eq!(
+            Game::from_pgn(&pgn).unwrap().0.get_status(),
+            game.get_status()
+        );
+
+        let (game, _) = Game::from_pgn("1. d4 d5 1/2-1/2").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Draw(DrawKind::Agreement));
+
+        // a timeout is told apart by the termination
+        let (game, _) = Game::from_pgn("[Termination \"time forfeit\"]\n\n1. e4 0-1").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Timeout(Color::White));
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.contains("[Result \"0-1\"]\n[Termination \"time forfeit\"]"));
+        assert_eq!(
+            Game::from_pgn(&pgn).unwrap().0.get_status(),
+            game.get_status()
+        );
+
+        // the final position beats the result, and games without moves get one too
+        let (game, _) = Game::from_pgn("1. f3 e5 2. g4 Qh4# 1/2-1/2").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Checkmate(Color::White));
+        let (game, _) = Game::from_pgn("[Result \"0-1\"]\n\n0-1").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Resignation(Color::White));
+        let (game, _) = Game::from_pgn("[Result \"0-1\"]\n\n1. e4 *").unwrap();
+        assert_eq!(game.get_status(), GameStatus::Ongoing);
+    }
+
+    #[test]
+    fn test_time_control_round_trip() {
+        let time_control = TimeControl::from(TimePeriod::moves(
+            40,
+            Duration::from_secs(5400),
+            TimeBonus::Fischer(Duration::from_secs(30)),
+        ))
+        .then(TimePeriod::sudden_death(
+            Duration::from_secs(1800),
+            TimeBonus::Fischer(Duration::from_secs(30)),
+        ));
+        let game = Game::with_start_time(time_control.clone());
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.contains("[TimeControl \"40/5400+30:1800+30\"]"));
+        let (read_game, _) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read_game.get_time_control(Color::Black), &time_control);
+
+        // replaying the moves doesn't start the clocks
+        let (read_game, _) = Game::from_pgn("[TimeControl \"60\"]\n\n1. e4 e5 2. Nf3 *").unwrap();
+        assert!(!read_game.is_timer_active());
+        let minute = Duration::from_secs(60);
+        for player in [Color::White, Color::Black] {
+            assert_eq!(read_game.get_time(player), minute);
+        }
+        assert_eq!(read_game.get_status(), GameStatus::Ongoing);
+
+        let pgn = Game::new().get_pgn_str(&PgnTags::default());
+        assert!(!pgn.contains("[TimeControl"));
+        let (read_game, _) = Game::from_pgn("[TimeControl \"?\"]\n\n*").unwrap();
+        assert_eq!(
+            read_game.get_time_control(Color::White),
+            &TimeControl::default()
+        );
+
+        let minute = Duration::from_secs(60);
+        let game = Game::new().with_time_controls(5 * minute, 2 * minute);
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.contains("[WhiteTimeControl \"300\"]\n[BlackTimeControl \"120\"]"));
+        let (read_game, _) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read_game.get_time(Color::White), 5 * minute);
+        assert_eq!(read_game.get_time(Color::Black), 2 * minute);
+
+        assert_eq!(
+            Game::from_pgn("[TimeControl \"*180\"]\n\n*")
+                .err()
+                .unwrap()
+                .kind,
+            PgnErrorKind::InvalidTimeControl("*180".into())
+        );
+    }
+
+    #[test]
+    fn test_variant_round_trip() {
+        let mut game = Game::with_variant(Variant::Horde);
+        play(&mut game, &["b5b6", "a7b6", "c5b6"]);
+        let pgn = game.get_pgn_str(&PgnTags::default());
+        assert!(pgn.contains("[Variant \"Horde\"]"));
+        assert!(!pgn.contains("[FEN"));
+        let (read_game, _) = Game::from_pgn(&pgn).unwrap();
+        assert_eq!(read_game.get_variant(), Variant::Horde);
+        assert_eq!(read_game.get_fen_str(), game.get_fen_str());
+
+        let error = Game::from_pgn("[Variant \"Crazyhouse\"]\n1. e4")
+            .err()
+            .unwrap();
+        assert_eq!(
+            error.kind,
+            PgnErrorKind::UnknownVariant("Crazyhouse".into())
+        );
+    }
+
+    #[test]
+    fn test_read_pgn() {
+        let pgn = r#"
+[Event "Casual Game"]
+[Site "Berlin GER"]
+[Date "1852.??.??"]
+[Round "?"]
+[White "Adolf Anderssen"]
+[Black "Jean Dufresne"]
+[Result "1-0"]
+% an escaped line
+1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.b4 Bxb4 5.c3 Ba5 6.d4 exd4 7.O-O
+d3 8.Qb3 Qf6 9.e5 Qg6 10.Re1 Nge7 11.Ba3 b5 12.Qxb5 Rb8 13.Qa4
+Bb6 14.Nbd2 Bb7 15.Ne4 Qf5 16.Bxd3 Qh5 17.Nf6+ gxf6 18.exf6
+Rg8 19.Rad1 $1 { the evergreen move } Qxf3 20.Rxe7+ Nxe7 ; a line comment
+21.Qxd7+ (21.Bxe7? Qxd1+) Kxd7 22.Bf5+ Ke8 23.Bd7+ Kf8 24.Bxe7# 1-0
+"#;
+        let (game, tags) = Game::from_pgn(pgn).unwrap();
+        assert_eq!(tags.white, "Adolf Anderssen");
+        assert_eq!(tags.date, "1852.??.??");
+        assert_eq!(
+            game.get_fen_str(),
+            "1r3kr1/pbpBBp1p/1b3P2/8/8/2P2q2/P4PPP/3R2K1 b - - 0 24"
+        );
+        assert!(game.game_over());
+    }
+
+    #[test]
+    fn test_read_pgn_errors() {
+        let error = Game::from_pgn("1. e4 e5\n2. Nf3 Nf3").err().unwrap();
+        assert_eq!(
+            error,
+            PgnError {
+                line: 2,
+                column: 8,
+                kind: SanError::Illegal("Nf3".into()).into(),
+            }
+        );
+
+        let error = Game::from_pgn("[FEN \"8/8/8/8/8/8/8/8 w - - 0 1\"]\n\n1. e4")
+            .err()
+            .unwrap();
+        assert_eq!((error.line, error.column), (3, 4));
+        assert!(matches!(error.kind, PgnErrorKind::Fen(..)));
+
+        let error = Game::from_pgn("1. e4 { unterminated").err().unwrap();
+        assert_eq!(error.kind, PgnErrorKind::UnexpectedEnd);
+
+        let error = Game::from_pgn("[White \"Alice\"]\n1. Nd2").err().unwrap();
+        assert_eq!(error.kind, SanError::Illegal("Nd2".into()).into());
+
+        let game = Game::from_fen("4k3/8/8/8/1N3N2/8/8/4K3 w - - 0 1").unwrap();
+        assert_eq!(
+            game.parse_san("Nd5"),
+            Err(SanError::Ambiguous("Nd5".into()))
+        );
+        assert_eq!(game.parse_san("N"), Err(SanError::Invalid("N".into())));
+    }
+}
@@ -1,8 +1,10 @@
 use std::fmt;
 
-use crate::{color::Color, displacement::Displacement};
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+use crate::color::Color;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash, Serialize, Deserialize)]
 pub enum Piece {
     Pawn(Color),
     Knight(Color),
@@ -13,6 +15,15 @@ pub enum Piece {
 }
 
 impl Piece {
+    pub fn get_promotion_options(player: Color) -> [Self; 4] {
+        [
+            Self::Queen(player),
+            Self::Rook(player),
+            Self::Bishop(player),
+            Self::Knight(player),
+        ]
+    }
+
     pub(super) fn is_pawn(self) -> bool {
         matches!(self, Piece::Pawn(..))
     }
@@ -28,21 +39,18 @@ impl Piece {
         }
     }
 
-    pub(super) fn get_vectors(self) -> &'static [Displacement] {
+    // the index of the piece kind, used to look up its bitboard
+    pub(super) fn get_index(self) -> usize {
         match self {
-            Self::Pawn(..) => panic!("Try calling `Displacement::get_pawn_*_vector()` instead"),
-            Self::Rook(..) => Displacement::get_rook_vectors(),
-            Self::Bishop(..) => Displacement::get_bishop_vectors(),
-            Self::Knight(..) => Displacement::get_knight_vectors(),
-            Self::Queen(..) => Displacement::get_queen_vectors(),
-            Self::King(..) => Displacement::get_king_vectors(),
+            Self::Pawn(..) => 0,
+            Self::Knight(..) => 1,
+            Self::Bishop(..) => 2,
+            Self::Rook(..) => 3,
+            Self::Queen(..) => 4,
+            Self::King(..) => 5,
         }
     }
 
-    pub(super) fn can_snipe(self) -> bool {
-        matches!(self, Self::Bishop(..) | Self::Rook(..) | Self::Queen(..))
-    }
-
     pub(super) fn get_fen_char(&self) -> char {
         let c = match self {
             Piece::Pawn(..) => 'p',
@@ -57,6 +65,36 @@ impl Piece {
             Color::Black => c,
         }
     }
+
+    pub(super) fn get_san_char(&self) -> char {
+        self.get_fen_char().to_ascii_uppercase()
+    }
+
+    // SAN always uses uppercase letters, whichever player is moving
+    pub(super) fn from_san_char(c: char, player: Color) -> Option<Self> {
+        match (c.is_ascii_uppercase(), player) {
+            (false, _) => None,
+            (true, Color::White) => Self::from_fen_char(c),
+            (true, Color::Black) => Self::from_fen_char(c.to_ascii_lowercase()),
+        }
+    }
+
+    pub(super) fn from_fen_char(c: char) -> Option<Self> {
+        let player = if c.is_ascii_uppercase() {
+            Color::White
+        } else {
+            Color::Black
+        };
+        match c.to_ascii_lowercase() {
+            'p' => Some(Piece::Pawn(player)),
+            'b' => Some(Piece::Bishop(player)),
+            'n' => Some(Piece::Knight(player)),
+            'r' => Some(Piece::Rook(player)),
+            'k' => Some(Piece::King(player)),
+            'q' => Some(Piece::Queen(player)),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Piece {
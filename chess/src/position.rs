@@ -18,15 +18,37 @@ pub struct Position {
 
 impl Position {
     pub(super) const BLACK_KING: Position = Position::new(4, 7);
-    pub(super) const BLACK_KINGSIDE_ROOK: Position = Position::new(7, 7);
-    pub(super) const BLACK_QUEENSIDE_ROOK: Position = Position::new(0, 7);
     pub(super) const WHITE_KING: Position = Position::new(4, 0);
-    pub(super) const WHITE_KINGSIDE_ROOK: Position = Position::new(7, 0);
-    pub(super) const WHITE_QUEENSIDE_ROOK: Position = Position::new(0, 0);
 
     pub const fn new(x: usize, y: usize) -> Self {
         Self { x, y }
     }
+
+    // the index of the square from a1 = 0 to h8 = 63
+    pub(super) const fn get_square(&self) -> usize {
+        self.y * 8 + self.x
+    }
+
+    pub(super) const fn from_square(square: usize) -> Self {
+        Self::new(square % 8, square / 8)
+    }
+
+    pub(super) fn get_file_char(&self) -> char {
+        FILES[self.x]
+    }
+
+    pub(super) fn get_rank_char(&self) -> char {
+        RANKS[self.y]
+    }
+
+    // parses a square in algebraic notation, e.g. "e4"
+    pub(super) fn parse(s: &str) -> Option<Self> {
+        let mut chars = s.chars();
+        let (file, rank) = (chars.next()?, chars.next()?);
+        let x = FILES.iter().position(|&c| c == file)?;
+        let y = RANKS.iter().position(|&c| c == rank)?;
+        chars.next().is_none().then_some(Self::new(x, y))
+    }
 }
 
 impl fmt::Display for Position {
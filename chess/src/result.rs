@@ -1,5 +1,7 @@
 use thiserror::Error;
 
+use crate::moves::Move;
+
 pub type ChessResult = Result<(), ChessError>;
 #[derive(Debug, Error)]
 pub enum ChessError {
@@ -13,4 +15,133 @@ pub enum ChessError {
     GameIsInDraw,
     #[error("Moved after time ran out")]
     Timeout,
+    #[error("The game is already over")]
+    GameIsOver,
+    #[error("There is no draw offer to accept")]
+    NoDrawOffer,
+    #[error("No draw can be claimed")]
+    NoDrawToClaim,
+    #[error("The main line of a game in progress can't be changed")]
+    GameInProgress,
+    #[error("There is no Chess960 start position {0}")]
+    InvalidChess960Index(usize),
+    #[error("There is no move to annotate at the start of the game")]
+    NoMoveToAnnotate,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum FenError {
+    #[error("Expected at least 4 fields, found {0}")]
+    MissingFields(usize),
+    #[error("Expected 8 ranks, found {0}")]
+    WrongRankCount(usize),
+    #[error("Rank {0} does not have 8 squares")]
+    WrongRankLength(usize),
+    #[error("Invalid piece '{0}'")]
+    InvalidPiece(char),
+    #[error("Invalid side to move")]
+    InvalidPlayer,
+    #[error("Invalid castling rights")]
+    InvalidCastlingRights,
+    #[error("Invalid en passant square")]
+    InvalidEnPassant,
+    #[error("Invalid halfmove clock")]
+    InvalidHalfmoveClock,
+    #[error("Invalid move number")]
+    InvalidMoveNumber,
+    #[error("The players don't have the kings the variant needs")]
+    InvalidKingCount,
+    #[error("Pawns can't be on the first or last rank")]
+    PawnOnBackRank,
+    #[error("Invalid remaining checks")]
+    InvalidRemainingChecks,
+    #[error("The player not to move is in check")]
+    OpponentInCheck,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SanError {
+    #[error("Invalid SAN \"{0}\"")]
+    Invalid(String),
+    #[error("Illegal move \"{0}\"")]
+    Illegal(String),
+    #[error("Ambiguous move \"{0}\"")]
+    Ambiguous(String),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SaveError {
+    #[error(transparent)]
+    Fen(#[from] FenError),
+    #[error("Illegal move {0}")]
+    IllegalMove(Move),
+    #[error("The current position isn't in the game")]
+    InvalidPath,
+    #[error("A time control needs at least one period")]
+    NoTimePeriods,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BookError {
+    #[error("A book is made of 16-byte entries, but has {0} bytes")]
+    InvalidLength(usize),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum EpdErrorKind {
+    #[error("Unterminated string")]
+    UnterminatedString,
+    #[error("Invalid operand for {0}")]
+    InvalidOperand(String),
+    #[error(transparent)]
+    Fen(#[from] FenError),
+    #[error(transparent)]
+    San(#[from] SanError),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("{kind} on line {line}")]
+pub struct EpdError {
+    pub line: usize,
+    pub kind: EpdErrorKind,
+}
+
+#[derive(Debug, Error)]
+pub enum TablebaseError {
+    #[error("Only standard positions without castling rights and with at most 7 pieces are in the tablebase")]
+    UnsupportedPosition,
+    #[error("The table {0} is missing")]
+    MissingTable(String),
+    #[error("The table {0} is corrupt")]
+    CorruptTable(String),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PgnErrorKind {
+    #[error("Unexpected character '{0}'")]
+    UnexpectedCharacter(char),
+    #[error("Unexpected end of input")]
+    UnexpectedEnd,
+    #[error("Invalid move number")]
+    InvalidMoveNumber,
+    #[error("Unbalanced variation")]
+    UnbalancedVariation,
+    #[error("Unknown variant \"{0}\"")]
+    UnknownVariant(String),
+    #[error("Invalid time control \"{0}\"")]
+    InvalidTimeControl(String),
+    #[error(transparent)]
+    Fen(#[from] FenError),
+    #[error(transparent)]
+    San(#[from] SanError),
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("{kind} at line {line}, column {column}")]
+pub struct PgnError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: PgnErrorKind,
 }
@@ -1,4 +1,5 @@
 pub struct RoundInfo {
+    pub number: usize,
     pub white_string: String,
     pub black_string: String,
 }
@@ -0,0 +1,205 @@
+use std::fmt;
+
+use crate::{
+    board_state::BoardState, castling_rights::CastlingSide, moves::Move, piece::Piece,
+    position::Position, result::SanError,
+};
+
+/// How pieces are written in SAN: as letters (Nf3) or as figurines (♞f3).
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub enum Notation {
+    #[default]
+    Letter,
+    Figurine,
+}
+
+impl Notation {
+    fn get_piece_str(self, piece: Piece) -> String {
+        match self {
+            Notation::Letter => piece.get_san_char().to_string(),
+            Notation::Figurine => piece.to_string(),
+        }
+    }
+}
+
+/// A move in Standard Algebraic Notation, without the check or checkmate suffix.
+///
+/// SAN depends on the position the move was played from, so it is worked out when the move is
+/// made and stored alongside the resulting `Turn`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) struct San {
+    piece: Piece,
+    mv: Move,
+    is_capture: bool,
+    show_file: bool,
+    show_rank: bool,
+    castling: Option<CastlingSide>,
+}
+
+impl San {
+    pub(super) fn new<'a>(
+        state: &BoardState,
+        mv: &Move,
+        valid_moves: impl IntoIterator<Item = &'a Move>,
+    ) -> Self {
+        let piece = state
+            .get_piece(&mv.from)
+            .expect("SAN is only defined for moves of a piece");
+        let castling = state
+            .castling_rights
+            .get_castling(mv, &state.board)
+            .map(|castling| castling.side);
+        let is_capture = castling.is_none()
+            && (state.get_piece(&mv.to).is_some() || (piece.is_pawn() && mv.from.x != mv.to.x));
+
+        let (show_file, show_rank) = if piece.is_pawn() {
+            (is_capture, false)
+        } else {
+            // Other pieces of the same kind that can also reach the destination
+            let others: Vec<Move> = valid_moves
+                .into_iter()
+                .filter(|other| {
+                    other.to == mv.to
+                        && other.from != mv.from
+                        && state.get_piece(&other.from) == Some(piece)
+                })
+                .copied()
+                .collect();
+            if others.is_empty() {
+                (false, false)
+            } else if others.iter().all(|other| other.from.x != mv.from.x) {
+                (true, false)
+            } else if others.iter().all(|other| other.from.y != mv.from.y) {
+                (false, true)
+            } else {
+                (true, true)
+            }
+        };
+
+        Self {
+            piece,
+            mv: *mv,
+            is_capture,
+            show_file,
+            show_rank,
+            castling,
+        }
+    }
+
+    fn parse_castling(san: &str) -> Option<CastlingSide> {
+        match san {
+            "O-O" | "0-0" => Some(CastlingSide::Kingside),
+            "O-O-O" | "0-0-0" => Some(CastlingSide::Queenside),
+            _ => None,
+        }
+    }
+
+    /// Finds the move in `valid_moves` that `san` describes.
+    pub(super) fn parse_move<'a>(
+        san: &str,
+        state: &BoardState,
+        valid_moves: impl IntoIterator<Item = &'a Move> + Clone,
+    ) -> Result<Move, SanError> {
+        let invalid = || SanError::Invalid(san.to_string());
+        // Check suffixes and move assessments don't affect which move is meant
+        let trimmed = san.trim_end_matches(['+', '#', '!', '?']);
+
+        let candidates: Vec<Move> = if let Some(side) = Self::parse_castling(trimmed) {
+            valid_moves
+                .clone()
+                .into_iter()
+                .filter(|mv| Self::new(state, mv, valid_moves.clone()).castling == Some(side))
+                .copied()
+                .collect()
+        } else {
+            let mut chars: Vec<char> = trimmed.chars().filter(|&c| c != 'x' && c != '-').collect();
+
+            let piece = match chars.first() {
+                Some(&c) if c.is_ascii_uppercase() => {
+                    chars.remove(0);
+                    Piece::from_san_char(c, state.player).ok_or_else(invalid)?
+                }
+                _ => Piece::Pawn(state.player),
+            };
+
+            let promotion = match chars.last() {
+                Some(&c) if piece.is_pawn() && c.is_ascii_uppercase() => {
+                    chars.pop();
+                    if chars.last() == Some(&'=') {
+                        chars.pop();
+                    }
+                    Some(Piece::from_san_char(c, state.player).ok_or_else(invalid)?)
+                }
+                _ => None,
+            };
+
+            if chars.len() < 2 {
+                return Err(invalid());
+            }
+            let to: String = chars.split_off(chars.len() - 2).into_iter().collect();
+            let to = Position::parse(&to).ok_or_else(invalid)?;
+
+            let (mut file, mut rank) = (None, None);
+            for c in chars {
+                match c {
+                    'a'..='h' if file.is_none() => file = Some(c as usize - 'a' as usize),
+                    '1'..='8' if rank.is_none() => rank = Some(c as usize - '1' as usize),
+                    _ => return Err(invalid()),
+                }
+            }
+
+            valid_moves
+                .into_iter()
+                .filter(|mv| {
+                    mv.to == to
+                        && mv.promotion == promotion
+                        && state.get_piece(&mv.from) == Some(piece)
+                        && file.is_none_or(|x| mv.from.x == x)
+                        && rank.is_none_or(|y| mv.from.y == y)
+                })
+                .copied()
+                .collect()
+        };
+
+        match candidates[..] {
+            [mv] => Ok(mv),
+            [] => Err(SanError::Illegal(san.to_string())),
+            _ => Err(SanError::Ambiguous(san.to_string())),
+        }
+    }
+}
+
+impl San {
+    pub(super) fn to_notation_string(self, notation: Notation) -> String {
+        match self.castling {
+            Some(CastlingSide::Kingside) => return "O-O".to_string(),
+            Some(CastlingSide::Queenside) => return "O-O-O".to_string(),
+            None => (),
+        }
+        let mut san = String::new();
+        if !self.piece.is_pawn() {
+            san.push_str(&notation.get_piece_str(self.piece));
+        }
+        if self.show_file {
+            san.push(self.mv.from.get_file_char());
+        }
+        if self.show_rank {
+            san.push(self.mv.from.get_rank_char());
+        }
+        if self.is_capture {
+            san.push('x');
+        }
+        san.push_str(&self.mv.to.to_string());
+        if let Some(piece) = self.mv.promotion {
+            san.push('=');
+            san.push_str(&notation.get_piece_str(piece));
+        }
+        san
+    }
+}
+
+impl fmt::Display for San {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_notation_string(Notation::Letter))
+    }
+}
@@ -0,0 +1,191 @@
+use serde::{Deserialize, Serialize};
+use web_time::Duration;
+
+use crate::{
+    annotation::Annotation, color::Color, game::Game, game_status::GameStatus, history::Node,
+    moves::Move, result::SaveError, time_control::TimeControl, variant::Variant,
+};
+
+/// The saved form of a `Game`. A change to the format adds a new version, and
+/// the old ones stay readable.
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "version")]
+pub(super) enum SavedGame {
+    #[serde(rename = "1")]
+    V1(SavedGameV1),
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct SavedGameV1 {
+    pub(super) variant: Variant,
+    pub(super) initial_fen: String,
+    pub(super) moves: Vec<SavedMove>,
+    pub(super) current_path: Vec<usize>,
+    pub(super) status: GameStatus,
+    pub(super) armageddon: bool,
+    pub(super) draw_offer: Option<Color>,
+    pub(super) timer: SavedTimer,
+}
+
+/// A move of a line, with the variations that replace it. Lines are stored as
+/// lists rather than nested nodes, so long games don't nest deeply.
+#[derive(Serialize, Deserialize)]
+pub(super) struct SavedMove {
+    pub(super) mv: Move,
+    #[serde(default, skip_serializing_if = "Annotation::is_empty")]
+    pub(super) annotation: Annotation,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) variations: Vec<Vec<SavedMove>>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub(super) struct SavedTimer {
+    pub(super) times: [Duration; 2],
+    pub(super) time_controls: [TimeControl; 2],
+    pub(super) periods: [usize; 2],
+    pub(super) current_player: Color,
+    pub(super) running: bool,
+}
+
+/// The line starting with the first of `nodes`, with the others as its variations.
+pub(super) fn save_line(mut nodes: &[Node]) -> Vec<SavedMove> {
+    let mut line = vec![];
+    while let Some((node, variations)) = nodes.split_first() {
+        line.push(SavedMove {
+            mv: node.turn.mv,
+            annotation: node.turn.annotation.clone(),
+            variations: variations
+                .iter()
+                .map(|variation| save_line(std::slice::from_ref(variation)))
+                .collect(),
+        });
+        nodes = &node.children;
+    }
+    line
+}
+
+impl From<Game> for SavedGame {
+    fn from(game: Game) -> Self {
+        game.to_saved()
+    }
+}
+
+impl TryFrom<SavedGame> for Game {
+    type Error = SaveError;
+
+    fn try_from(saved: SavedGame) -> Result<Self, Self::Error> {
+        Game::from_saved(saved)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{clock::ManualClock, pgn::PgnTags, test_helpers::play};
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn round_trip(game: &Game) -> Game {
+        let json = serde_json::to_string(game).unwrap();
+        serde_json::from_str(&json).unwrap()
+    }
+
+    fn assert_same(loaded: &Game, game: &Game) {
+        let tags = PgnTags::default();
+        assert_eq!(loaded.get_pgn_str(&tags), game.get_pgn_str(&tags));
+        assert_eq!(loaded.get_fen_str(), game.get_fen_str());
+        assert_eq!(loaded.status, game.status);
+        assert_eq!(loaded.get_next_moves(), game.get_next_moves());
+        for player in [Color::White, Color::Black] {
+            assert_eq!(
+                loaded.get_time_control(player),
+                game.get_time_control(player)
+            );
+        }
+    }
+
+    #[test]
+    fn test_round_trip_in_progress() {
+        let clock = ManualClock::new();
+        let mut game = Game::with_start_time(TimeControl::fischer(300 * SECOND, 2 * SECOND))
+            .with_clock(clock.clone());
+        play(&mut game, &["e2e4", "e7e5", "g1f3"]);
+        clock.advance(10 * SECOND);
+        game.offer_draw(Color::White).unwrap();
+        game.go_back_a_move();
+        game.go_back_a_move();
+        play(&mut game, &["c7c5", "g1f3"]);
+        game.go_back_a_move();
+        game.get_annotation_mut().unwrap().nags.push(1);
+
+        let loaded = round_trip(&game);
+        assert_same(&loaded, &game);
+        // the loaded clock runs on from the time Black had left
+        assert!(loaded.is_timer_active());
+        assert_eq!(loaded.get_time(Color::White), game.get_time(Color::White));
+        let black_time = loaded.get_time(Color::Black);
+        assert!(black_time <= game.get_time(Color::Black));
+        assert!(black_time > game.get_time(Color::Black) - SECOND);
+        assert!(!loaded.is_on_main_line());
+        assert_eq!(loaded.get_annotation().unwrap().nags, [1]);
+        assert_eq!(loaded.get_draw_offer(), Some(Color::White));
+        // the valid moves are rebuilt for the position being looked at
+        let mut loaded = loaded;
+        loaded.make_move(Move::from_lan("b1c3").unwrap()).unwrap();
+        loaded.resume();
+        loaded.make_move(Move::from_lan("b8c6").unwrap()).unwrap();
+        assert_eq!(loaded.get_draw_offer(), None);
+    }
+
+    #[test]
+    fn test_round_trip_finished() {
+        let mut game = Game::from_variant_fen(
+            Variant::ThreeCheck,
+            "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq - 3+2 0 2",
+        )
+        .unwrap()
+        .with_armageddon();
+        play(&mut game, &["f1c4"]);
+        game.resign(Color::White).unwrap();
+        let loaded = round_trip(&game);
+        assert_same(&loaded, &game);
+        assert!(loaded.is_armageddon());
+        assert!(loaded.game_over());
+        assert!(!loaded.is_timer_active());
+        for player in [Color::White, Color::Black] {
+            assert_eq!(loaded.get_time(player), game.get_time(player));
+        }
+
+        let game = Game::chess960(518).unwrap();
+        assert_same(&round_trip(&game), &game);
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let json = serde_json::to_string(&Game::new()).unwrap();
+        assert!(json.starts_with(r#"{"version":"1","#));
+
+        let unknown_version = json.replace(r#""version":"1""#, r#""version":"0""#);
+        assert!(serde_json::from_str::<Game>(&unknown_version).is_err());
+
+        let mut game = Game::new();
+        play(&mut game, &["e2e4"]);
+        let json = serde_json::to_string(&game).unwrap();
+        let illegal = json.replace(r#""y":1"#, r#""y":0"#);
+        let error = serde_json::from_str::<Game>(&illegal).err().unwrap();
+        assert!(error.to_string().starts_with("Illegal move"));
+
+        let invalid_path = json.replace(r#""current_path":[0]"#, r#""current_path":[1]"#);
+        let error = serde_json::from_str::<Game>(&invalid_path).err().unwrap();
+        assert_eq!(error.to_string(), SaveError::InvalidPath.to_string());
+
+        let mut no_periods: serde_json::Value = serde_json::from_str(&json).unwrap();
+        no_periods["timer"]["time_controls"][0]["periods"] = serde_json::json!([]);
+        let no_periods = no_periods.to_string();
+        let error = serde_json::from_str::<Game>(&no_periods).err().unwrap();
+        assert!(error
+            .to_string()
+            .starts_with(&SaveError::NoTimePeriods.to_string()));
+        assert!(serde_json::from_str::<TimeControl>(r#"{"periods":[]}"#).is_err());
+    }
+}
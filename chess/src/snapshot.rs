@@ -0,0 +1,150 @@
+use crate::{
+    board_state::BoardState,
+    castling_rights::{CastlingRights, CastlingSide},
+    color::Color,
+    game_status::GameStatus,
+    movegen,
+    moves::Move,
+    piece::Piece,
+    position::Position,
+    variant::Variant,
+};
+
+/// A read-only copy of a position, for code that needs to look at the board in
+/// more detail than `Game` offers. Taking a snapshot doesn't tie it to the game,
+/// so it stays the same while the game goes on.
+#[derive(Clone)]
+pub struct PositionSnapshot {
+    state: BoardState,
+    status: GameStatus,
+    legal_moves: Vec<Move>,
+}
+
+impl PositionSnapshot {
+    pub(super) fn new(state: BoardState, status: GameStatus) -> Self {
+        Self {
+            state,
+            status,
+            legal_moves: movegen::get_legal_moves(&state),
+        }
+    }
+
+    pub fn get_player(&self) -> Color {
+        self.state.player
+    }
+
+    pub fn get_variant(&self) -> Variant {
+        self.state.variant
+    }
+
+    /// The status of the game in this position. Results decided off the board,
+    /// such as a resignation, are only known for the position the game ended in.
+    pub fn get_status(&self) -> GameStatus {
+        self.status
+    }
+
+    pub fn get_piece(&self, position: &Position) -> Option<Piece> {
+        self.state.get_piece(position)
+    }
+
+    pub fn get_pieces(&self) -> Vec<(Piece, Position)> {
+        (0..64)
+            .map(Position::from_square)
+            .filter_map(|position| Some((self.get_piece(&position)?, position)))
+            .collect()
+    }
+
+    /// Whether `player` may still castle to `side`. Having the right doesn't
+    /// mean castling is legal right now; `get_legal_moves` tells that.
+    pub fn can_castle(&self, player: Color, side: CastlingSide) -> bool {
+        self.get_castling_rook(player, side).is_some()
+    }
+
+    /// The rook `player` would castle with on `side`, if they still have the right.
+    pub fn get_castling_rook(&self, player: Color, side: CastlingSide) -> Option<Position> {
+        let [kingside, queenside] = CastlingRights::get_castling_rights(player);
+        let right = match side {
+            CastlingSide::Kingside => kingside,
+            CastlingSide::Queenside => queenside,
+        };
+        self.state.castling_rights.get_rook_position(right)
+    }
+
+    /// The square a pawn can capture en passant on.
+    pub fn get_en_passant(&self) -> Option<Position> {
+        self.state.en_passant_position
+    }
+
+    /// Where `player`'s king is. Some variants play without one.
+    pub fn get_king_position(&self, player: Color) -> Option<Position> {
+        self.state.board.find_king(player)
+    }
+
+    /// The moves the player to move can make, whether or not the game is over.
+    pub fn get_legal_moves(&self) -> &[Move] {
+        &self.legal_moves
+    }
+
+    pub fn is_square_attacked(&self, square: Position, by: Color) -> bool {
+        movegen::is_square_attacked(&self.state.board, square.get_square(), by)
+    }
+
+    /// The squares of `by`'s pieces that attack `square`.
+    pub fn get_attackers(&self, square: Position, by: Color) -> Vec<Position> {
+        let board = &self.state.board;
+        movegen::get_attackers(board, square.get_square(), by, board.get_occupied())
+            .map(Position::from_square)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Game;
+
+    #[test]
+    fn test_snapshot() {
+        let game = Game::from_fen("r3k2r/8/8/3pP3/8/8/8/R3K1bR w Qk d6 0 1").unwrap();
+        let snapshot = game.get_snapshot();
+        assert_eq!(snapshot.get_player(), Color::White);
+        assert_eq!(snapshot.get_status(), GameStatus::NotStarted);
+        assert!(snapshot.can_castle(Color::White, CastlingSide::Queenside));
+        assert!(!snapshot.can_castle(Color::White, CastlingSide::Kingside));
+        assert_eq!(
+            snapshot.get_castling_rook(Color::Black, CastlingSide::Kingside),
+            Some(Position::new(7, 7))
+        );
+        assert_eq!(snapshot.get_en_passant(), Some(Position::new(3, 5)));
+        assert_eq!(
+            snapshot.get_king_position(Color::Black),
+            Some(Position::new(4, 7))
+        );
+        assert!(snapshot
+            .get_legal_moves()
+            .contains(&Move::new(Position::new(4, 4), Position::new(3, 5))));
+
+        assert!(snapshot.is_square_attacked(Position::new(5, 1), Color::Black));
+        assert!(!snapshot.is_square_attacked(Position::new(5, 2), Color::Black));
+        let mut attackers = snapshot.get_attackers(Position::new(6, 0), Color::White);
+        attackers.sort_by_key(|position| position.x);
+        assert_eq!(attackers, vec![Position::new(7, 0)]);
+    }
+
+    #[test]
+    fn test_snapshot_status() {
+        let mut game = Game::new();
+        for lan in ["f2f3", "e7e5", "g2g4", "d8h4"] {
+            game.make_move(Move::from_lan(lan).unwrap()).unwrap();
+        }
+        let snapshot = game.get_snapshot();
+        assert_eq!(snapshot.get_status(), GameStatus::Checkmate(Color::White));
+        assert!(snapshot.get_legal_moves().is_empty());
+
+        // a snapshot of an earlier position has that position's status
+        game.go_back_a_move();
+        let snapshot = game.get_snapshot();
+        assert_eq!(snapshot.get_status(), GameStatus::Ongoing);
+        assert_eq!(snapshot.get_player(), Color::Black);
+    }
+}
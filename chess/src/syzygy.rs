@@ -0,0 +1,1261 @@
+use std::{
+    fs::File,
+    io::{self, BufReader, Read, Seek, SeekFrom},
+    path::Path,
+    sync::{Mutex, OnceLock},
+};
+
+use crate::{
+    board_state::BoardState, color::Color, piece::Piece, position::Position, result::TablebaseError,
+};
+
+/// The most pieces any Syzygy table has.
+pub(super) const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// The flags at the start of a file
+const SPLIT: u8 = 1;
+const HAS_PAWNS: u8 = 2;
+
+// The flags of each of a table's value tables
+const STM: u8 = 1;
+const MAPPED: u8 = 2;
+const WIN_PLIES: u8 = 4;
+const LOSS_PLIES: u8 = 8;
+const WIDE: u8 = 16;
+const SINGLE_VALUE: u8 = 128;
+
+// The pieces of a table's name, in the order the name lists them
+const NAME_PIECES: [(char, usize); 6] =
+    [('K', 5), ('Q', 4), ('R', 3), ('B', 2), ('N', 1), ('P', 0)];
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub(super) enum TableKind {
+    /// Win, draw or loss, for either player to move.
+    Wdl,
+    /// The distance to the next capture or pawn move, for one player to move.
+    Dtz,
+}
+
+impl TableKind {
+    pub(super) fn get_extension(self) -> &'static str {
+        match self {
+            Self::Wdl => "rtbw",
+            Self::Dtz => "rtbz",
+        }
+    }
+
+    fn get_magic(self) -> [u8; 4] {
+        match self {
+            Self::Wdl => WDL_MAGIC,
+            Self::Dtz => DTZ_MAGIC,
+        }
+    }
+}
+
+// Pieces are numbered as in the files: pawn to king from 1 for White and from
+// 9 for Black
+fn get_piece_code(piece: Piece) -> u8 {
+    piece.get_index() as u8 + 1 + 8 * (piece.get_player() == Color::Black) as u8
+}
+
+/// A player's pieces as a table name writes them, such as "KRP".
+pub(super) fn get_material_name(state: &BoardState, player: Color) -> String {
+    let mut counts = [0; 6];
+    for square in 0..64 {
+        if let Some(piece) = state.get_piece(&Position::from_square(square)) {
+            if piece.get_player() == player {
+                counts[piece.get_index()] += 1;
+            }
+        }
+    }
+    NAME_PIECES
+        .iter()
+        .flat_map(|&(c, index)| std::iter::repeat_n(c, counts[index]))
+        .collect()
+}
+
+// Squares below the a1-h8 diagonal are negative, and above it positive
+fn get_diagonal_offset(square: usize) -> i32 {
+    (square >> 3) as i32 - (square & 7) as i32
+}
+
+fn flip_file(square: usize) -> usize {
+    square ^ 7
+}
+
+fn flip_rank(square: usize) -> usize {
+    square ^ 56
+}
+
+fn flip_diagonal(square: usize) -> usize {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+/// The tables that turn the squares of a group of pieces into an index.
+struct IndexTables {
+    // a2-h7 to 0..47, largest for the squares nearest the edge and the first rank
+    map_pawns: [usize; 64],
+    // the squares below the a1-h8 diagonal to 0..27
+    map_b1h1h7: [usize; 64],
+    // the a1-d1-d4 triangle to 0..9, with the diagonal last
+    map_a1d1d4: [usize; 64],
+    // the 462 ways to place two kings with the first in the a1-d1-d4 triangle
+    map_kk: [[u64; 64]; 10],
+    // the ways to choose k of n squares, as binomial[k][n]
+    binomial: [[u64; 64]; MAX_PIECES - 1],
+    lead_pawn_index: [[u64; 64]; MAX_PIECES - 1],
+    lead_pawns_size: [[u64; 4]; MAX_PIECES - 1],
+}
+
+impl IndexTables {
+    fn new() -> Self {
+        let mut tables = Self {
+            map_pawns: [0; 64],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            binomial: [[0; 64]; MAX_PIECES - 1],
+            lead_pawn_index: [[0; 64]; MAX_PIECES - 1],
+            lead_pawns_size: [[0; 4]; MAX_PIECES - 1],
+        };
+
+        let below_diagonal = (0..64).filter(|&square| get_diagonal_offset(square) < 0);
+        for (code, square) in below_diagonal.enumerate() {
+            tables.map_b1h1h7[square] = code;
+        }
+
+        let triangle = (0..=27).filter(|&square| square & 7 <= 3);
+        let (diagonal, below): (Vec<_>, Vec<_>) = triangle
+            .filter(|&square| get_diagonal_offset(square) <= 0)
+            .partition(|&square| get_diagonal_offset(square) == 0);
+        for (code, square) in below.into_iter().chain(diagonal).enumerate() {
+            tables.map_a1d1d4[square] = code;
+        }
+
+        // Kings on the diagonal come last, and with the first king on the
+        // diagonal the second can't be above it
+        let mut code = 0;
+        let mut both_on_diagonal = vec![];
+        for index in 0..10 {
+            let first = (0..=27).find(|&square| {
+                square & 7 <= 3
+                    && get_diagonal_offset(square) <= 0
+                    && tables.map_a1d1d4[square] == index
+            });
+            let Some(first) = first else {
+                continue;
+            };
+            for second in 0..64 {
+                let touching = (first >> 3).abs_diff(second >> 3) <= 1
+                    && (first & 7).abs_diff(second & 7) <= 1;
+                let first_on_diagonal = get_diagonal_offset(first) == 0;
+                if touching || (first_on_diagonal && get_diagonal_offset(second) > 0) {
+                    continue;
+                }
+                if first_on_diagonal && get_diagonal_offset(second) == 0 {
+                    both_on_diagonal.push((index, second));
+                } else {
+                    tables.map_kk[index][second] = code;
+                    code += 1;
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            tables.map_kk[index][second] = code;
+            code += 1;
+        }
+
+        tables.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES - 1 {
+                let with = if k > 0 {
+                    tables.binomial[k - 1][n - 1]
+                } else {
+                    0
+                };
+                let without = if k < n { tables.binomial[k][n - 1] } else { 0 };
+                tables.binomial[k][n] = with + without;
+            }
+        }
+
+        // A leading pawn leaves 47 squares for the others on a2, two fewer for
+        // each rank it stands higher, and the others can't be nearer the edge
+        let mut available = 48;
+        for lead_pawns in 1..MAX_PIECES - 1 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = 8 * rank + file;
+                    if lead_pawns == 1 {
+                        tables.map_pawns[square] = available - 1;
+                        tables.map_pawns[flip_file(square)] = available - 2;
+                        available -= 2;
+                    }
+                    tables.lead_pawn_index[lead_pawns][square] = index;
+                    index += tables.binomial[lead_pawns - 1][tables.map_pawns[square]];
+                }
+                tables.lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+        tables
+    }
+
+    fn get() -> &'static Self {
+        static TABLES: OnceLock<IndexTables> = OnceLock::new();
+        TABLES.get_or_init(Self::new)
+    }
+}
+
+/// What the name of a table tells about how its positions are indexed.
+#[derive(Clone, Copy)]
+struct Material {
+    piece_count: usize,
+    has_pawns: bool,
+    // a player has exactly one of some piece besides the king
+    has_unique_pieces: bool,
+    has_pawns_on_both_sides: bool,
+    // both players have the same pieces, so only White to move is stored
+    symmetric: bool,
+}
+
+impl Material {
+    fn from_name(name: &str) -> Option<Self> {
+        let (white, black) = name.split_once('v')?;
+        let count = |side: &str, c: char| side.chars().filter(|&other| other == c).count();
+        let material = Self {
+            piece_count: white.len() + black.len(),
+            has_pawns: count(name, 'P') > 0,
+            has_unique_pieces: [white, black]
+                .iter()
+                .any(|side| "QRBNP".chars().any(|c| count(side, c) == 1)),
+            has_pawns_on_both_sides: count(white, 'P') > 0 && count(black, 'P') > 0,
+            symmetric: white == black,
+        };
+        (material.piece_count <= MAX_PIECES).then_some(material)
+    }
+
+    fn get_file_count(&self) -> usize {
+        if self.has_pawns {
+            4
+        } else {
+            1
+        }
+    }
+
+    // Splits the pieces into the groups that are indexed together: the leading
+    // pawns or pieces first, then runs of the same piece
+    fn set_groups(&self, data: &mut PairsData, order: [u8; 2], file: usize) -> Option<()> {
+        let tables = IndexTables::get();
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut n = 0;
+        data.group_len[0] = 1;
+        for i in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || data.pieces[i] == data.pieces[i - 1] {
+                data.group_len[n] += 1;
+            } else {
+                n += 1;
+                data.group_len[n] = 1;
+            }
+        }
+        n += 1;
+        data.group_len[n] = 0;
+
+        // The groups are combined in the order the table gives, so the
+        // leading group isn't necessarily the most significant
+        let both_pawns = self.has_pawns_on_both_sides;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares =
+            64 - data.group_len[0] - if both_pawns { data.group_len[1] } else { 0 };
+        let mut index: u64 = 1;
+        let mut k = 0;
+        while next < n || k == order[0] as usize || k == order[1] as usize {
+            if k == order[0] as usize {
+                data.group_index[0] = index;
+                index *= if self.has_pawns {
+                    *tables.lead_pawns_size.get(data.group_len[0])?.get(file)?
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] as usize {
+                data.group_index[1] = index;
+                index *= tables.binomial.get(data.group_len[1])?[48 - data.group_len[0]];
+            } else {
+                data.group_index[next] = index;
+                index *= tables.binomial.get(data.group_len[next])?[free_squares];
+                free_squares = free_squares.checked_sub(data.group_len[next])?;
+                next += 1;
+            }
+            k += 1;
+            if k > MAX_PIECES + 1 {
+                return None;
+            }
+        }
+        data.group_index[n] = index;
+        Some(())
+    }
+}
+
+/// The values of a table for one player to move and, in tables with pawns, one
+/// file of the leading pawn. They are compressed by recursive pairing, which
+/// replaces frequent pairs of symbols by new ones, and then Huffman coding.
+#[derive(Default)]
+struct PairsData {
+    flags: u8,
+    // the pieces in the order they are indexed in, and how they are grouped
+    pieces: [u8; MAX_PIECES],
+    group_len: [usize; MAX_PIECES + 1],
+    group_index: [u64; MAX_PIECES + 1],
+    block_size: u64,
+    // there is an entry in the sparse index for about every `span` values
+    span: u64,
+    num_blocks: u32,
+    min_sym_len: u8,
+    // the lowest symbol of each code length, and the lowest code of each
+    // length padded to 64 bits
+    lowest_sym: Vec<u16>,
+    base64: Vec<u64>,
+    // how many values each symbol stands for, minus one
+    symlen: Vec<u8>,
+    // the pair each symbol stands for, or for a value its value on the left
+    btree: Vec<[u8; 3]>,
+    sparse_index_size: u64,
+    sparse_index: Vec<(u32, u16)>,
+    block_length_size: u64,
+    // how many values each block holds, minus one
+    block_lengths: Vec<u16>,
+    data_offset: u64,
+    // where the values of each result start in the DTZ map
+    map_index: [u16; 4],
+}
+
+impl PairsData {
+    fn get_table_size(&self) -> u64 {
+        let groups = self.group_len.iter().position(|&len| len == 0).unwrap();
+        self.group_index[groups]
+    }
+
+    fn get_left(&self, sym: usize) -> usize {
+        let [a, b, _] = self.btree[sym];
+        ((b as usize & 0xf) << 8) | a as usize
+    }
+
+    fn get_right(&self, sym: usize) -> usize {
+        let [_, b, c] = self.btree[sym];
+        ((c as usize) << 4) | (b as usize >> 4)
+    }
+
+    fn set_symlen(&mut self, sym: usize, visited: &mut [bool]) -> Result<u8, ()> {
+        visited[sym] = true;
+        let right = self.get_right(sym);
+        if right == 0xfff {
+            return Ok(0);
+        }
+        let left = self.get_left(sym);
+        for child in [left, right] {
+            if child >= self.symlen.len() {
+                return Err(());
+            }
+            if !visited[child] {
+                self.symlen[child] = self.set_symlen(child, visited)?;
+            }
+        }
+        Ok(self.symlen[left]
+            .wrapping_add(self.symlen[right])
+            .wrapping_add(1))
+    }
+}
+
+// Reads a file's header front to back
+struct HeaderReader<'a> {
+    reader: BufReader<&'a File>,
+    offset: u64,
+}
+
+impl HeaderReader<'_> {
+    fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; len];
+        self.reader.read_exact(&mut bytes)?;
+        self.offset += len as u64;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> io::Result<u8> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u16(&mut self) -> io::Result<u16> {
+        let bytes = self.read_bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn read_u32(&mut self) -> io::Result<u32> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    }
+
+    fn skip(&mut self, len: u64) -> io::Result<()> {
+        self.reader.seek_relative(len as i64)?;
+        self.offset += len;
+        Ok(())
+    }
+
+    fn align(&mut self, alignment: u64) -> io::Result<()> {
+        self.skip(self.offset.next_multiple_of(alignment) - self.offset)
+    }
+}
+
+/// An open Syzygy table. Only the header is read up front; the compressed
+/// values are read from the file a block at a time as positions are probed.
+pub(super) struct Table {
+    name: String,
+    kind: TableKind,
+    file: Mutex<File>,
+    material: Material,
+    // by player to move and then file of the leading pawn
+    items: Vec<Vec<PairsData>>,
+    dtz_map: Vec<u8>,
+}
+
+impl Table {
+    /// Opens the table `name`, such as "KRvK", which has the stronger
+    /// player's pieces first.
+    pub(super) fn open(path: &Path, name: &str, kind: TableKind) -> Result<Self, TablebaseError> {
+        let corrupt = || TablebaseError::CorruptTable(format!("{name}.{}", kind.get_extension()));
+        let material = Material::from_name(name).ok_or_else(corrupt)?;
+        let mut table = Self {
+            name: name.to_string(),
+            kind,
+            file: Mutex::new(File::open(path)?),
+            material,
+            items: vec![],
+            dtz_map: vec![],
+        };
+        table.read_header().map_err(|error| match error.kind() {
+            io::ErrorKind::UnexpectedEof | io::ErrorKind::InvalidData => corrupt(),
+            _ => error.into(),
+        })?;
+        Ok(table)
+    }
+
+    fn corrupt(&self) -> TablebaseError {
+        TablebaseError::CorruptTable(format!("{}.{}", self.name, self.kind.get_extension()))
+    }
+
+    fn get(&self, player: usize, file: usize) -> &PairsData {
+        let file = if self.material.has_pawns { file } else { 0 };
+        &self.items[player % self.items.len()][file]
+    }
+
+    fn read_header(&mut self) -> io::Result<()> {
+        let file = self.file.get_mut().unwrap();
+        file.seek(SeekFrom::Start(0))?;
+        let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+        let mut reader = HeaderReader {
+            reader: BufReader::new(&*file),
+            offset: 0,
+        };
+        if reader.read_bytes(4)? != self.kind.get_magic() {
+            return Err(invalid());
+        }
+        let flags = reader.read_u8()?;
+        if (flags & HAS_PAWNS != 0) != self.material.has_pawns {
+            return Err(invalid());
+        }
+        let sides = if self.kind == TableKind::Wdl && flags & SPLIT != 0 {
+            2
+        } else {
+            1
+        };
+        let files = self.material.get_file_count();
+        let both_pawns = self.material.has_pawns_on_both_sides;
+        let mut items: Vec<Vec<PairsData>> = (0..sides)
+            .map(|_| (0..files).map(|_| PairsData::default()).collect())
+            .collect();
+
+        for file in 0..files {
+            let first = reader.read_u8()?;
+            let second = if both_pawns { reader.read_u8()? } else { 0xff };
+            let orders = [
+                [first & 0xf, if both_pawns { second & 0xf } else { 0xf }],
+                [first >> 4, if both_pawns { second >> 4 } else { 0xf }],
+            ];
+            for k in 0..self.material.piece_count {
+                let pieces = reader.read_u8()?;
+                for (side, item) in items.iter_mut().enumerate() {
+                    item[file].pieces[k] = if side == 0 { pieces & 0xf } else { pieces >> 4 };
+                }
+            }
+            for (side, item) in items.iter_mut().enumerate() {
+                self.material
+                    .set_groups(&mut item[file], orders[side], file)
+                    .ok_or_else(invalid)?;
+            }
+        }
+        reader.align(2)?;
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                Self::read_sizes(&mut item[file], &mut reader)?;
+            }
+        }
+
+        if self.kind == TableKind::Dtz {
+            let start = reader.offset;
+            for data in items[0].iter_mut() {
+                if data.flags & MAPPED == 0 {
+                    continue;
+                }
+                let wide = data.flags & WIDE != 0;
+                if wide {
+                    reader.align(2)?;
+                }
+                for map_index in data.map_index.iter_mut() {
+                    let (len, size) = if wide {
+                        (reader.read_u16()? as u64, 2)
+                    } else {
+                        (reader.read_u8()? as u64, 1)
+                    };
+                    // the index is counted in values from the start of the map
+                    let offset = reader.offset - start;
+                    *map_index = (offset / size) as u16;
+                    reader.skip(len * size)?;
+                }
+            }
+            reader.align(2)?;
+            let end = reader.offset;
+            reader.reader.seek(SeekFrom::Start(start))?;
+            reader.offset = start;
+            self.dtz_map = reader.read_bytes((end - start) as usize)?;
+        }
+
+        for file in 0..files {
+            for item in items.iter_mut() {
+                let data = &mut item[file];
+                let bytes = reader.read_bytes(data.sparse_index_size as usize * 6)?;
+                data.sparse_index = bytes
+                    .chunks_exact(6)
+                    .map(|entry| {
+                        (
+                            u32::from_le_bytes(entry[0..4].try_into().unwrap()),
+                            u16::from_le_bytes([entry[4], entry[5]]),
+                        )
+                    })
+                    .collect();
+            }
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                let data = &mut item[file];
+                let bytes = reader.read_bytes(data.block_length_size as usize * 2)?;
+                data.block_lengths = bytes
+                    .chunks_exact(2)
+                    .map(|length| u16::from_le_bytes([length[0], length[1]]))
+                    .collect();
+            }
+        }
+        for file in 0..files {
+            for item in items.iter_mut() {
+                let data = &mut item[file];
+                reader.align(64)?;
+                data.data_offset = reader.offset;
+                // the values are read a block at a time when probing
+                reader.skip(data.num_blocks as u64 * data.block_size)?;
+            }
+        }
+        self.items = items;
+        Ok(())
+    }
+
+    fn read_sizes(data: &mut PairsData, reader: &mut HeaderReader) -> io::Result<()> {
+        let invalid = || io::Error::from(io::ErrorKind::InvalidData);
+        data.flags = reader.read_u8()?;
+        if data.flags & SINGLE_VALUE != 0 {
+            data.min_sym_len = reader.read_u8()?;
+            return Ok(());
+        }
+
+        let table_size = data.get_table_size();
+        data.block_size = 1 << reader.read_u8()?.min(63);
+        data.span = 1 << reader.read_u8()?.min(63);
+        data.sparse_index_size = table_size.div_ceil(data.span);
+        let padding = reader.read_u8()?;
+        data.num_blocks = reader.read_u32()?;
+        data.block_length_size = data.num_blocks as u64 + padding as u64;
+        let max_sym_len = reader.read_u8()?;
+        data.min_sym_len = reader.read_u8()?;
+        if max_sym_len < data.min_sym_len || max_sym_len > 32 {
+            return Err(invalid());
+        }
+        let lengths = (max_sym_len - data.min_sym_len + 1) as usize;
+        data.lowest_sym = (0..lengths)
+            .map(|_| reader.read_u16())
+            .collect::<io::Result<_>>()?;
+
+        // Longer codes have lower values, so the lowest code of each length
+        // is found from the next longer one
+        data.base64 = vec![0; lengths];
+        for i in (0..lengths - 1).rev() {
+            data.base64[i] = (data.base64[i + 1] + data.lowest_sym[i] as u64)
+                .checked_sub(data.lowest_sym[i + 1] as u64)
+                .ok_or_else(invalid)?
+                / 2;
+        }
+        for (i, base) in data.base64.iter_mut().enumerate() {
+            let shift = 64 - i as u32 - data.min_sym_len as u32;
+            *base = base.checked_shl(shift).unwrap_or(0);
+        }
+
+        let symbols = reader.read_u16()? as usize;
+        data.symlen = vec![0; symbols];
+        let btree = reader.read_bytes(symbols * 3)?;
+        data.btree = btree
+            .chunks_exact(3)
+            .map(|pair| [pair[0], pair[1], pair[2]])
+            .collect();
+        let mut visited = vec![false; symbols];
+        for sym in 0..symbols {
+            if !visited[sym] {
+                data.symlen[sym] = data.set_symlen(sym, &mut visited).map_err(|_| invalid())?;
+            }
+        }
+        reader.skip(symbols as u64 & 1)
+    }
+
+    /// The stored value for `state` and the file of its leading pawn, or
+    /// `None` if a DTZ table only has the other player to move. `flipped`
+    /// means the stronger player is Black, so the colors are swapped.
+    fn get_value(
+        &self,
+        state: &BoardState,
+        flipped: bool,
+    ) -> Result<Option<(u64, usize)>, TablebaseError> {
+        let Some((index, player, file)) = self.get_index(state, flipped)? else {
+            return Ok(None);
+        };
+        let value = self.decompress(self.get(player, file), index)?;
+        Ok(Some((value, file)))
+    }
+
+    // The index of `state` in the table, with the player to move and the file
+    // of the leading pawn that choose the part of the table it is in
+    fn get_index(
+        &self,
+        state: &BoardState,
+        flipped: bool,
+    ) -> Result<Option<(u64, usize, usize)>, TablebaseError> {
+        let tables = IndexTables::get();
+        // with the same pieces on both sides only White to move is stored
+        let flip = flipped || (self.material.symmetric && state.player == Color::Black);
+        let flip_color = if flip { 8 } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let player = flip as usize ^ (state.player == Color::Black) as usize;
+
+        let occupied: Vec<(usize, u8)> = (0..64)
+            .filter_map(|square| {
+                let piece = state.get_piece(&Position::from_square(square))?;
+                Some((square, get_piece_code(piece)))
+            })
+            .collect();
+        if occupied.len() != self.material.piece_count {
+            return Err(self.corrupt());
+        }
+        let mut squares = [0; MAX_PIECES];
+        let mut pieces = [0; MAX_PIECES];
+        let mut size = 0;
+
+        // Tables with pawns are split by the file of the leading pawn, the one
+        // nearest the edge and the first rank
+        let mut lead_pawns = 0;
+        let mut lead_pawn_code = None;
+        let mut file = 0;
+        if self.material.has_pawns {
+            let code = self.items[0][0].pieces[0] ^ flip_color;
+            for &(square, piece) in occupied.iter().filter(|(_, piece)| *piece == code) {
+                squares[size] = square ^ flip_squares;
+                pieces[size] = piece ^ flip_color;
+                size += 1;
+            }
+            lead_pawns = size;
+            let lead = (0..lead_pawns)
+                .max_by_key(|&i| tables.map_pawns[squares[i]])
+                .ok_or_else(|| self.corrupt())?;
+            squares.swap(0, lead);
+            file = (squares[0] & 7).min(7 - (squares[0] & 7));
+            lead_pawn_code = Some(code);
+        }
+
+        if self.kind == TableKind::Dtz {
+            // without pawns, a table with the same pieces on both sides covers
+            // both players to move by swapping the colors
+            let stored_player = (self.get(0, file).flags & STM) as usize;
+            let has_both_players = self.material.symmetric && !self.material.has_pawns;
+            if stored_player != player && !has_both_players {
+                return Ok(None);
+            }
+        }
+
+        for &(square, piece) in &occupied {
+            if Some(piece) != lead_pawn_code {
+                squares[size] = square ^ flip_squares;
+                pieces[size] = piece ^ flip_color;
+                size += 1;
+            }
+        }
+
+        // Put the pieces in the order the table indexes them in
+        let data = self.get(player, file);
+        for i in lead_pawns..size - 1 {
+            if let Some(j) = (i + 1..size).find(|&j| pieces[j] == data.pieces[i]) {
+                pieces.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // Mirror the board so the leading piece is on files a-d...
+        if squares[0] & 7 > 3 {
+            for square in &mut squares[..size] {
+                *square = flip_file(*square);
+            }
+        }
+
+        let mut index;
+        if self.material.has_pawns {
+            index = tables.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|&square| tables.map_pawns[square]);
+            for (i, &square) in squares.iter().enumerate().take(lead_pawns).skip(1) {
+                index += tables.binomial[i][tables.map_pawns[square]];
+            }
+        } else {
+            // ...ranks 1-4...
+            if squares[0] >> 3 > 3 {
+                for square in &mut squares[..size] {
+                    *square = flip_rank(*square);
+                }
+            }
+            // ...and below the a1-h8 diagonal, for the first leading piece
+            // that isn't on it
+            for i in 0..data.group_len[0] {
+                let offset = get_diagonal_offset(squares[i]);
+                if offset == 0 {
+                    continue;
+                }
+                if offset > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = flip_diagonal(*square);
+                    }
+                }
+                break;
+            }
+            index = if self.material.has_unique_pieces {
+                Self::get_unique_pieces_index(&squares)
+            } else {
+                tables.map_kk[tables.map_a1d1d4[squares[0]]][squares[1]]
+            };
+        }
+        index *= data.group_index[0];
+
+        // The other groups choose their squares from those left, so each
+        // square is counted down by the earlier pieces below it
+        let mut start = data.group_len[0];
+        let mut remaining_pawns = self.material.has_pawns_on_both_sides;
+        let mut next = 1;
+        while data.group_len[next] != 0 {
+            let len = data.group_len[next];
+            squares[start..start + len].sort_unstable();
+            let mut group_index = 0;
+            for i in 0..len {
+                let square = squares[start + i];
+                let below = squares[..start]
+                    .iter()
+                    .filter(|&&other| square > other)
+                    .count();
+                let square = square - below - if remaining_pawns { 8 } else { 0 };
+                group_index += tables.binomial[i + 1][square];
+            }
+            remaining_pawns = false;
+            index += group_index * data.group_index[next];
+            start += len;
+            next += 1;
+        }
+
+        Ok(Some((index, player, file)))
+    }
+
+    // Three different leading pieces, with the first in the a1-d1-d4 triangle
+    // and any on the diagonal treated apart
+    fn get_unique_pieces_index(squares: &[usize; MAX_PIECES]) -> u64 {
+        let tables = IndexTables::get();
+        let [first, second, third] = [squares[0], squares[1], squares[2]];
+        let adjust1 = (second > first) as usize;
+        let adjust2 = (third > first) as usize + (third > second) as usize;
+        let rank = |square: usize| (square >> 3) as u64;
+        let index = if get_diagonal_offset(first) != 0 {
+            (tables.map_a1d1d4[first] * 63 + second - adjust1) * 62 + third - adjust2
+        } else if get_diagonal_offset(second) != 0 {
+            (6 * 63 + (first >> 3) * 28 + tables.map_b1h1h7[second]) * 62 + third - adjust2
+        } else if get_diagonal_offset(third) != 0 {
+            return 6 * 63 * 62
+                + 4 * 28 * 62
+                + rank(first) * 7 * 28
+                + (rank(second) - adjust1 as u64) * 28
+                + tables.map_b1h1h7[third] as u64;
+        } else {
+            return 6 * 63 * 62
+                + 4 * 28 * 62
+                + 4 * 7 * 28
+                + rank(first) * 7 * 6
+                + (rank(second) - adjust1 as u64) * 6
+                + (rank(third) - adjust2 as u64);
+        };
+        index as u64
+    }
+
+    fn read_block(&self, data: &PairsData, block: u32) -> io::Result<Vec<u8>> {
+        let mut bytes = vec![0; data.block_size as usize];
+        let mut file = self.file.lock().unwrap();
+        file.seek(SeekFrom::Start(
+            data.data_offset + block as u64 * data.block_size,
+        ))?;
+        // the last block may end before its full size
+        let mut read = 0;
+        while read < bytes.len() {
+            match file.read(&mut bytes[read..])? {
+                0 => break,
+                len => read += len,
+            }
+        }
+        Ok(bytes)
+    }
+
+    fn decompress(&self, data: &PairsData, index: u64) -> Result<u64, TablebaseError> {
+        if data.flags & SINGLE_VALUE != 0 {
+            return Ok(data.min_sym_len as u64);
+        }
+        let corrupt = || self.corrupt();
+
+        // The sparse index gives the block and the offset in it of every
+        // span-th value, counted from the middle of the span
+        let k = (index / data.span) as usize;
+        let &(mut block, offset) = data.sparse_index.get(k).ok_or_else(corrupt)?;
+        let mut offset = offset as i64 + (index % data.span) as i64 - (data.span / 2) as i64;
+        let block_length = |block: u32| -> Result<i64, TablebaseError> {
+            Ok(*data.block_lengths.get(block as usize).ok_or_else(corrupt)? as i64)
+        };
+        while offset < 0 {
+            block = block.checked_sub(1).ok_or_else(corrupt)?;
+            offset += block_length(block)? + 1;
+        }
+        while offset > block_length(block)? {
+            offset -= block_length(block)? + 1;
+            block += 1;
+        }
+
+        // Read symbols until the one that holds the value
+        let bytes = self.read_block(data, block)?;
+        let read_u32 = |at: usize| -> u64 {
+            let mut word = [0; 4];
+            for (i, byte) in word.iter_mut().enumerate() {
+                *byte = bytes.get(at + i).copied().unwrap_or(0);
+            }
+            u32::from_be_bytes(word) as u64
+        };
+        let mut next = 8;
+        let mut buffer = (read_u32(0) << 32) | read_u32(4);
+        let mut buffer_size = 64;
+        let min_sym_len = data.min_sym_len as u32;
+        let mut sym;
+        loop {
+            let mut len = 0;
+            while buffer < *data.base64.get(len).ok_or_else(corrupt)? {
+                len += 1;
+            }
+            let shift = 64 - len as u32 - min_sym_len;
+            sym = ((buffer - data.base64[len]) >> shift) as usize;
+            sym += data.lowest_sym[len] as usize;
+            let sym_values = *data.symlen.get(sym).ok_or_else(corrupt)? as i64 + 1;
+            if offset < sym_values {
+                break;
+            }
+            offset -= sym_values;
+            let len = len as u32 + min_sym_len;
+            buffer = buffer.checked_shl(len).unwrap_or(0);
+            buffer_size -= len as i32;
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= read_u32(next) << (64 - buffer_size);
+                next += 4;
+            }
+        }
+
+        // Each symbol stands for its pair of symbols until a single value
+        while data.symlen[sym] != 0 {
+            let left = data.get_left(sym);
+            let left_values = *data.symlen.get(left).ok_or_else(corrupt)? as i64 + 1;
+            if offset < left_values {
+                sym = left;
+            } else {
+                offset -= left_values;
+                sym = data.get_right(sym);
+            }
+            if sym >= data.symlen.len() {
+                return Err(corrupt());
+            }
+        }
+        Ok(data.get_left(sym) as u64)
+    }
+
+    /// The result for the player to move in `state`: -2 for a loss, -1 for a
+    /// loss saved by the fifty-move rule, 0 for a draw, 1 for a win spoiled by
+    /// it and 2 for a win. The result may be wrong where the best move is a
+    /// capture, which the table doesn't have to store.
+    pub(super) fn probe_wdl(
+        &self,
+        state: &BoardState,
+        flipped: bool,
+    ) -> Result<i32, TablebaseError> {
+        let (value, _) = self
+            .get_value(state, flipped)?
+            .ok_or_else(|| self.corrupt())?;
+        Ok(value as i32 - 2)
+    }
+
+    /// The number of plies to the next capture or pawn move, for the result
+    /// `wdl`, or `None` if the table only has the other player to move.
+    pub(super) fn probe_dtz(
+        &self,
+        state: &BoardState,
+        flipped: bool,
+        wdl: i32,
+    ) -> Result<Option<i32>, TablebaseError> {
+        let Some((value, file)) = self.get_value(state, flipped)? else {
+            return Ok(None);
+        };
+        let data = self.get(0, file);
+        let mut value = value as usize;
+        if data.flags & MAPPED != 0 {
+            // the results are listed as win, loss, spoiled win and saved loss
+            let map_index = data.map_index[match wdl {
+                2 => 0,
+                -2 => 1,
+                1 => 2,
+                _ => 3,
+            }] as usize;
+            let at = map_index + value;
+            value = if data.flags & WIDE != 0 {
+                let bytes = self
+                    .dtz_map
+                    .get(2 * at..2 * at + 2)
+                    .ok_or_else(|| self.corrupt())?;
+                u16::from_le_bytes([bytes[0], bytes[1]]) as usize
+            } else {
+                *self.dtz_map.get(at).ok_or_else(|| self.corrupt())? as usize
+            };
+        }
+        // some tables count in moves rather than plies
+        let in_moves = match wdl {
+            2 => data.flags & WIN_PLIES == 0,
+            -2 => data.flags & LOSS_PLIES == 0,
+            _ => true,
+        };
+        if in_moves {
+            value *= 2;
+        }
+        Ok(Some(value as i32 + 1))
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use std::{collections::HashMap, fs, io::Write, path::PathBuf};
+
+    use super::*;
+    use crate::{board::Board, castling_rights::CastlingRights, fen::Fen, variant::Variant};
+
+    /// A directory of its own for the tables of one test.
+    pub(crate) fn get_test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("syzygy-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Writes a table whose value at each index is given by `value` from the
+    /// side of the table and the index, with the pieces indexed in the order
+    /// of `pieces`. Every value is its own 3-bit symbol, except that a run of
+    /// two 4s shares one, so values are stored both ways.
+    pub(crate) fn write_table(
+        dir: &Path,
+        name: &str,
+        kind: TableKind,
+        pieces: &[Piece],
+        value: impl Fn(usize, u64) -> u8,
+    ) {
+        const VALUES_PER_BLOCK: u64 = 128;
+        const PAIR: u8 = 5;
+        let material = Material::from_name(name).unwrap();
+        let sides = if kind == TableKind::Wdl && !material.symmetric {
+            2
+        } else {
+            1
+        };
+        let files = material.get_file_count();
+        let mut bytes = kind.get_magic().to_vec();
+        bytes.push(
+            if sides == 2 { SPLIT } else { 0 } | if material.has_pawns { HAS_PAWNS } else { 0 },
+        );
+        let mut sizes = vec![];
+        for file in 0..files {
+            bytes.push(0);
+            let mut data = PairsData::default();
+            for (i, &piece) in pieces.iter().enumerate() {
+                data.pieces[i] = get_piece_code(piece);
+                bytes.push(data.pieces[i] | if sides == 2 { data.pieces[i] << 4 } else { 0 });
+            }
+            material.set_groups(&mut data, [0, 0xf], file).unwrap();
+            sizes.push(data.get_table_size());
+        }
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+
+        // The blocks and their lengths by file and then side
+        let mut blocks = vec![];
+        for &size in &sizes {
+            for side in 0..sides {
+                let mut side_blocks = vec![];
+                for first in (0..size).step_by(VALUES_PER_BLOCK as usize) {
+                    let last = (first + VALUES_PER_BLOCK).min(size);
+                    let mut symbols = vec![];
+                    let mut index = first;
+                    while index < last {
+                        if value(side, index) == 4
+                            && index + 1 < last
+                            && value(side, index + 1) == 4
+                        {
+                            symbols.push(PAIR);
+                            index += 2;
+                        } else {
+                            symbols.push(value(side, index));
+                            index += 1;
+                        }
+                    }
+                    let mut block = vec![0u8; 64];
+                    for (i, sym) in symbols.iter().enumerate() {
+                        for bit in 0..3 {
+                            if sym >> (2 - bit) & 1 == 1 {
+                                let at = i * 3 + bit;
+                                block[at / 8] |= 0x80 >> (at % 8);
+                            }
+                        }
+                    }
+                    side_blocks.push((block, (last - first - 1) as u16));
+                }
+                blocks.push(side_blocks);
+            }
+        }
+
+        for side_blocks in &blocks {
+            bytes.push(0); // flags
+            bytes.push(6); // 64-byte blocks
+            bytes.push(7); // a sparse index entry for every 128 values
+            bytes.push(0); // no padding
+            bytes.extend((side_blocks.len() as u32).to_le_bytes());
+            bytes.push(3); // the longest code
+            bytes.push(3); // the shortest code
+            bytes.extend(0u16.to_le_bytes());
+            bytes.extend(6u16.to_le_bytes());
+            for sym in 0..5 {
+                // a value, with nothing on the right
+                bytes.extend([sym, 0xf0, 0xff]);
+            }
+            // the pair of two 4s
+            bytes.extend([4, 0x40, 0x00]);
+        }
+        // Each entry points at the middle of its span, the start of a block
+        for side_blocks in &blocks {
+            for block in 0..side_blocks.len() as u32 {
+                bytes.extend(block.to_le_bytes());
+                bytes.extend((VALUES_PER_BLOCK as u16 / 2).to_le_bytes());
+            }
+        }
+        for side_blocks in &blocks {
+            for (_, length) in side_blocks {
+                bytes.extend(length.to_le_bytes());
+            }
+        }
+        for side_blocks in &blocks {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            for (block, _) in side_blocks {
+                bytes.extend(block);
+            }
+        }
+        let path = dir.join(format!("{name}.{}", kind.get_extension()));
+        File::create(path).unwrap().write_all(&bytes).unwrap();
+    }
+
+    /// Writes a DTZ table without pawns that stores `value` for every position
+    /// with White to move.
+    pub(crate) fn write_single_value_dtz(dir: &Path, name: &str, pieces: &[Piece], value: u8) {
+        let mut bytes = DTZ_MAGIC.to_vec();
+        bytes.push(0);
+        bytes.push(0);
+        bytes.extend(pieces.iter().map(|&piece| get_piece_code(piece)));
+        if bytes.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes.extend([SINGLE_VALUE, value]);
+        let path = dir.join(format!("{name}.rtbz"));
+        File::create(path).unwrap().write_all(&bytes).unwrap();
+    }
+
+    #[test]
+    fn test_index_tables() {
+        let tables = IndexTables::get();
+        let codes = tables.map_kk.iter().flatten();
+        assert_eq!(codes.max(), Some(&461));
+        assert_eq!(tables.map_a1d1d4[1], 0);
+        assert_eq!(tables.map_a1d1d4[27], 9);
+        assert_eq!(tables.map_b1h1h7[7], 6);
+        assert_eq!(tables.map_b1h1h7[55], 27);
+        assert_eq!(tables.binomial[2][5], 10);
+        assert_eq!(tables.binomial[3][62], 37820);
+        assert_eq!(tables.map_pawns[8], 47);
+        assert_eq!(tables.map_pawns[15], 46);
+        assert_eq!(tables.map_pawns[52], 0);
+        assert_eq!(tables.lead_pawns_size[1], [6; 4]);
+    }
+
+    #[test]
+    fn test_material_name() {
+        let state = Fen::parse("8/8/4k3/8/2R5/8/1P2K3/7n w - - 0 1", Variant::Standard)
+            .unwrap()
+            .state;
+        assert_eq!(get_material_name(&state, Color::White), "KRP");
+        assert_eq!(get_material_name(&state, Color::Black), "KN");
+    }
+
+    // Every placement of the pieces gets an index in the table, and placements
+    // share an index exactly when they are mirror images of each other
+    fn check_indices(name: &str, pieces: [Piece; 3], symmetries: &[fn(usize) -> usize]) {
+        let dir = get_test_dir(&format!("indices-{name}"));
+        write_table(&dir, name, TableKind::Wdl, &pieces, |_, _| 0);
+        let table = Table::open(&dir.join(format!("{name}.rtbw")), name, TableKind::Wdl).unwrap();
+        let is_placeable =
+            |piece: &Piece, square: usize| !piece.is_pawn() || (8..56).contains(&square);
+        let mut placements_by_index = HashMap::new();
+        for a in (0..64).filter(|&a| is_placeable(&pieces[0], a)) {
+            for b in (0..64).filter(|&b| b != a) {
+                for c in (0..64).filter(|&c| c != a && c != b) {
+                    let mut board = Board::empty();
+                    for (piece, square) in pieces.iter().zip([a, b, c]) {
+                        board.set_piece(&Position::from_square(square), Some(*piece));
+                    }
+                    let state = BoardState::new(board, Color::White, CastlingRights::none(), None);
+                    let (index, _, file) = table.get_index(&state, false).unwrap().unwrap();
+                    assert!(index < table.get(0, file).get_table_size());
+                    let placement = symmetries
+                        .iter()
+                        .map(|symmetry| [a, b, c].map(symmetry))
+                        .min()
+                        .unwrap();
+                    let other = placements_by_index
+                        .entry((index, file))
+                        .or_insert(placement);
+                    assert_eq!(
+                        *other,
+                        placement,
+                        "{:?} has the index of {other:?}",
+                        [a, b, c]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_unique_pieces_index() {
+        let dihedral: [fn(usize) -> usize; 8] = [
+            |s| s,
+            flip_file,
+            flip_rank,
+            |s| flip_file(flip_rank(s)),
+            flip_diagonal,
+            |s| flip_diagonal(flip_file(s)),
+            |s| flip_diagonal(flip_rank(s)),
+            |s| flip_diagonal(flip_file(flip_rank(s))),
+        ];
+        check_indices(
+            "KRvK",
+            [
+                Piece::King(Color::White),
+                Piece::Rook(Color::White),
+                Piece::King(Color::Black),
+            ],
+            &dihedral,
+        );
+    }
+
+    #[test]
+    fn test_pawn_index() {
+        check_indices(
+            "KPvK",
+            [
+                Piece::Pawn(Color::White),
+                Piece::King(Color::White),
+                Piece::King(Color::Black),
+            ],
+            &[|s| s, flip_file],
+        );
+    }
+
+    #[test]
+    fn test_decompress() {
+        let dir = get_test_dir("decompress");
+        let pieces = [
+            Piece::King(Color::White),
+            Piece::Rook(Color::White),
+            Piece::King(Color::Black),
+        ];
+        // long runs of 4s make the blocks hold different numbers of symbols
+        let value = |side: usize, index: u64| {
+            if index % 7 < 3 {
+                4
+            } else {
+                ((index / 7 + side as u64) % 5) as u8
+            }
+        };
+        write_table(&dir, "KRvK", TableKind::Wdl, &pieces, value);
+        let table = Table::open(&dir.join("KRvK.rtbw"), "KRvK", TableKind::Wdl).unwrap();
+        for side in 0..2 {
+            let data = table.get(side, 0);
+            for index in (0..data.get_table_size()).step_by(13) {
+                assert_eq!(
+                    table.decompress(data, index).unwrap(),
+                    value(side, index) as u64
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_corrupt_table() {
+        let dir = get_test_dir("corrupt");
+        let path = dir.join("KQvK.rtbw");
+        File::create(&path).unwrap().write_all(&DTZ_MAGIC).unwrap();
+        assert!(matches!(
+            Table::open(&path, "KQvK", TableKind::Wdl),
+            Err(TablebaseError::CorruptTable(name)) if name == "KQvK.rtbw"
+        ));
+    }
+}
@@ -0,0 +1,573 @@
+use std::{
+    collections::HashMap,
+    fs,
+    ops::Neg,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::{
+    board_state::BoardState,
+    color::Color,
+    game::Game,
+    movegen,
+    moves::Move,
+    result::TablebaseError,
+    syzygy::{self, Table, TableKind, MAX_PIECES},
+    variant::Variant,
+};
+
+// Tables by name and kind, with `None` for those that aren't in the directory
+type TableCache = HashMap<(String, TableKind), Option<Arc<Table>>>;
+
+// The fifty-move rule counts plies
+const FIFTY_MOVES: i32 = 100;
+
+/// The result of a position with perfect play, from the point of view of the
+/// player to move. Cursed wins and blessed losses take longer than the
+/// fifty-move rule allows, so they are draws once it is claimed.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    fn from_value(value: i32) -> Self {
+        match value {
+            ..=-2 => Self::Loss,
+            -1 => Self::BlessedLoss,
+            0 => Self::Draw,
+            1 => Self::CursedWin,
+            _ => Self::Win,
+        }
+    }
+
+    // The distance to zero just before a capture or pawn move with this result
+    fn get_dtz_before_zeroing(self) -> i32 {
+        match self {
+            Self::Loss => -1,
+            Self::BlessedLoss => -(FIFTY_MOVES + 1),
+            Self::Draw => 0,
+            Self::CursedWin => FIFTY_MOVES + 1,
+            Self::Win => 1,
+        }
+    }
+}
+
+impl Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::from_value(-(self as i32))
+    }
+}
+
+/// Syzygy endgame tablebases in a directory, which give the result of every
+/// standard position with few enough pieces and no castling rights. WDL files
+/// (`.rtbw`) tell whether the position is won, drawn or lost, and DTZ files
+/// (`.rtbz`) how far it is to the next capture or pawn move with perfect
+/// play, which is enough to win without running into the fifty-move rule.
+/// Tables are opened the first time a position needs them.
+pub struct Tablebase {
+    dir: PathBuf,
+    max_pieces: usize,
+    tables: Mutex<TableCache>,
+}
+
+impl Tablebase {
+    /// Finds the tables in `dir`, which may hold any set of them.
+    pub fn open(dir: impl AsRef<Path>) -> Result<Self, TablebaseError> {
+        let dir = dir.as_ref().to_path_buf();
+        let mut max_pieces = 0;
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            if path
+                .extension()
+                .is_some_and(|extension| extension == "rtbw")
+            {
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                let pieces = name.chars().filter(|&c| c != 'v').count();
+                max_pieces = max_pieces.max(pieces.min(MAX_PIECES));
+            }
+        }
+        Ok(Self {
+            dir,
+            max_pieces,
+            tables: Mutex::default(),
+        })
+    }
+
+    /// The most pieces of any WDL table in the directory.
+    pub fn get_max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    /// Whether the position being looked at could be in the tablebase, though
+    /// the tables for it may still be missing.
+    pub fn can_probe(&self, game: &Game) -> bool {
+        self.check_state(game.get_current_state()).is_ok()
+    }
+
+    /// The result of the position being looked at.
+    pub fn probe_wdl(&self, game: &Game) -> Result<Wdl, TablebaseError> {
+        let state = game.get_current_state();
+        self.check_state(state)?;
+        Ok(self.search(state, false)?.0)
+    }
+
+    /// The number of plies to the next capture or pawn move with perfect play,
+    /// which is positive when the player to move wins and negative when they
+    /// lose, and 0 in a draw. The count can be one ply more than the shortest
+    /// way, since the tables store some results to the nearest move.
+    pub fn probe_dtz(&self, game: &Game) -> Result<i32, TablebaseError> {
+        let state = game.get_current_state();
+        self.check_state(state)?;
+        self.get_dtz(state)
+    }
+
+    /// The move that keeps the best result, taking the fifty-move rule into
+    /// account: the fastest way to make progress when winning, and the longest
+    /// resistance when losing. `None` if there are no legal moves.
+    pub fn get_best_move(&self, game: &Game) -> Result<Option<Move>, TablebaseError> {
+        let state = game.get_current_state();
+        self.check_state(state)?;
+        let halfmove_clock = game.get_halfmove_clock() as i32;
+        let repetition_keys = game.get_repetition_keys();
+        let mut best = None;
+        for mv in movegen::get_legal_moves(state) {
+            let mut next = *state;
+            next.move_piece(&mv);
+            let mut dtz = if Self::is_zeroing(state, &mv) {
+                (-self.search(&next, false)?.0).get_dtz_before_zeroing()
+            } else if repetition_keys.contains(&next.get_zobrist_key()) {
+                0
+            } else {
+                let dtz = -self.get_dtz(&next)?;
+                dtz + dtz.signum()
+            };
+            // a mate is a single ply from zeroing the clock
+            if dtz == 2 && Self::is_mate(&next) {
+                dtz = 1;
+            }
+            let rank = Self::get_rank(dtz, halfmove_clock);
+            if best.is_none_or(|(best_rank, _)| rank > best_rank) {
+                best = Some((rank, mv));
+            }
+        }
+        Ok(best.map(|(_, mv)| mv))
+    }
+
+    // Wins that beat the fifty-move rule come first, the fastest first, and
+    // losses last, the slowest first
+    fn get_rank(dtz: i32, halfmove_clock: i32) -> i32 {
+        const MAX_RANK: i32 = 1000;
+        match dtz {
+            1.. if dtz + halfmove_clock < FIFTY_MOVES => 2 * MAX_RANK - dtz,
+            1.. => MAX_RANK - dtz,
+            0 => 0,
+            _ if halfmove_clock - dtz > FIFTY_MOVES => -MAX_RANK - dtz,
+            _ => -2 * MAX_RANK - dtz,
+        }
+    }
+
+    fn check_state(&self, state: &BoardState) -> Result<(), TablebaseError> {
+        let pieces = state.board.get_occupied().len() as usize;
+        if state.variant != Variant::Standard
+            || !state.castling_rights.is_empty()
+            || pieces > self.max_pieces.max(2)
+        {
+            return Err(TablebaseError::UnsupportedPosition);
+        }
+        Ok(())
+    }
+
+    fn is_zeroing(state: &BoardState, mv: &Move) -> bool {
+        movegen::get_captured_piece(state, mv).is_some()
+            || state
+                .get_piece(&mv.from)
+                .is_some_and(|piece| piece.is_pawn())
+    }
+
+    fn is_mate(state: &BoardState) -> bool {
+        movegen::is_king_under_attack(state) && movegen::get_legal_moves(state).is_empty()
+    }
+
+    // The table for the pieces of `state` and whether its colors are swapped,
+    // or `None` if only the kings are left
+    fn get_table(
+        &self,
+        state: &BoardState,
+        kind: TableKind,
+    ) -> Result<Option<(Arc<Table>, bool)>, TablebaseError> {
+        let white = syzygy::get_material_name(state, Color::White);
+        let black = syzygy::get_material_name(state, Color::Black);
+        if white == "K" && black == "K" {
+            return Ok(None);
+        }
+        for (name, flipped) in [
+            (format!("{white}v{black}"), false),
+            (format!("{black}v{white}"), true),
+        ] {
+            if let Some(table) = self.load_table(&name, kind)? {
+                return Ok(Some((table, flipped)));
+            }
+        }
+        Err(TablebaseError::MissingTable(format!(
+            "{white}v{black}.{}",
+            kind.get_extension()
+        )))
+    }
+
+    fn load_table(
+        &self,
+        name: &str,
+        kind: TableKind,
+    ) -> Result<Option<Arc<Table>>, TablebaseError> {
+        let mut tables = self.tables.lock().unwrap();
+        let key = (name.to_string(), kind);
+        if let Some(table) = tables.get(&key) {
+            return Ok(table.clone());
+        }
+        let path = self.dir.join(format!("{name}.{}", kind.get_extension()));
+        let table = if path.is_file() {
+            Some(Arc::new(Table::open(&path, name, kind)?))
+        } else {
+            None
+        };
+        tables.insert(key, table.clone());
+        Ok(table)
+    }
+
+    fn probe_wdl_table(&self, state: &BoardState) -> Result<Wdl, TablebaseError> {
+        match self.get_table(state, TableKind::Wdl)? {
+            Some((table, flipped)) => Ok(Wdl::from_value(table.probe_wdl(state, flipped)?)),
+            None => Ok(Wdl::Draw),
+        }
+    }
+
+    // The tables don't have to store the right result where the best move is
+    // a capture, or a pawn move for DTZ tables, so those moves are searched.
+    // Also returns whether such a move is the best one.
+    fn search(
+        &self,
+        state: &BoardState,
+        check_pawn_moves: bool,
+    ) -> Result<(Wdl, bool), TablebaseError> {
+        let moves = movegen::get_legal_moves(state);
+        let mut best = Wdl::Loss;
+        let mut searched = 0;
+        for mv in &moves {
+            let is_capture = movegen::get_captured_piece(state, mv).is_some();
+            let is_pawn_move = state
+                .get_piece(&mv.from)
+                .is_some_and(|piece| piece.is_pawn());
+            let is_zeroing = is_capture || (check_pawn_moves && is_pawn_move);
+            if !is_zeroing {
+                continue;
+            }
+            searched += 1;
+            let mut next = *state;
+            next.move_piece(mv);
+            let wdl = -self.search(&next, false)?.0;
+            if wdl > best {
+                best = wdl;
+                if wdl == Wdl::Win {
+                    return Ok((wdl, true));
+                }
+            }
+        }
+
+        // With every move searched the tables aren't needed, and couldn't be
+        // trusted anyway since they ignore en passant
+        let searched_all = searched > 0 && searched == moves.len();
+        let wdl = if searched_all {
+            best
+        } else {
+            self.probe_wdl_table(state)?
+        };
+        if best >= wdl {
+            return Ok((best, best > Wdl::Draw || searched_all));
+        }
+        Ok((wdl, false))
+    }
+
+    fn get_dtz(&self, state: &BoardState) -> Result<i32, TablebaseError> {
+        let (wdl, is_zeroing_best) = self.search(state, true)?;
+        if wdl == Wdl::Draw {
+            return Ok(0);
+        }
+        if is_zeroing_best {
+            return Ok(wdl.get_dtz_before_zeroing());
+        }
+        let (table, flipped) = self
+            .get_table(state, TableKind::Dtz)?
+            .ok_or(TablebaseError::UnsupportedPosition)?;
+        if let Some(dtz) = table.probe_dtz(state, flipped, wdl as i32)? {
+            let is_cursed = matches!(wdl, Wdl::CursedWin | Wdl::BlessedLoss);
+            return Ok((dtz + FIFTY_MOVES * is_cursed as i32) * (wdl as i32).signum());
+        }
+
+        // The table only has the other player to move, so their best reply
+        // gives the distance
+        let mut best = None;
+        for mv in movegen::get_legal_moves(state) {
+            let mut next = *state;
+            next.move_piece(&mv);
+            let dtz = if Self::is_zeroing(state, &mv) {
+                -(-self.search(&next, false)?.0).get_dtz_before_zeroing()
+            } else {
+                let dtz = -self.get_dtz(&next)?;
+                if dtz == 1 && Self::is_mate(&next) {
+                    best = Some(1);
+                }
+                dtz + dtz.signum()
+            };
+            if dtz.signum() == (wdl as i32).signum() && best.is_none_or(|best| dtz < best) {
+                best = Some(dtz);
+            }
+        }
+        // without legal moves the player to move is mated
+        Ok(best.unwrap_or(-1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        piece::Piece,
+        position::Position,
+        syzygy::tests::{get_test_dir, write_single_value_dtz, write_table},
+    };
+
+    const KRVK: [Piece; 3] = [
+        Piece::King(Color::White),
+        Piece::Rook(Color::White),
+        Piece::King(Color::Black),
+    ];
+
+    // The rook wins for White to move, and for Black to move unless it can be
+    // taken; all wins are 5 moves from zeroing
+    fn get_krvk_tablebase(name: &str) -> Tablebase {
+        let dir = get_test_dir(name);
+        write_table(&dir, "KRvK", TableKind::Wdl, &KRVK, |side, _| {
+            if side == 0 {
+                4
+            } else {
+                0
+            }
+        });
+        write_single_value_dtz(&dir, "KRvK", &KRVK, 5);
+        Tablebase::open(dir).unwrap()
+    }
+
+    fn game(fen: &str) -> Game {
+        Game::from_fen(fen).unwrap()
+    }
+
+    #[test]
+    fn test_probe_wdl() {
+        let tablebase = get_krvk_tablebase("wdl");
+        assert_eq!(tablebase.get_max_pieces(), 3);
+        assert_eq!(
+            tablebase
+                .probe_wdl(&game("8/8/8/8/8/2k5/8/KR6 w - - 0 1"))
+                .unwrap(),
+            Wdl::Win
+        );
+        assert_eq!(
+            tablebase
+                .probe_wdl(&game("8/8/8/8/8/2k5/8/KR6 b - - 0 1"))
+                .unwrap(),
+            Wdl::Loss
+        );
+        // the rook can be taken
+        assert_eq!(
+            tablebase
+                .probe_wdl(&game("8/8/8/8/8/8/1kR5/7K b - - 0 1"))
+                .unwrap(),
+            Wdl::Draw
+        );
+        // with the colors swapped
+        assert_eq!(
+            tablebase
+                .probe_wdl(&game("kr6/8/2K5/8/8/8/8/8 b - - 0 1"))
+                .unwrap(),
+            Wdl::Win
+        );
+        assert_eq!(
+            tablebase
+                .probe_wdl(&game("8/8/8/8/8/2k5/8/K7 w - - 0 1"))
+                .unwrap(),
+            Wdl::Draw
+        );
+    }
+
+    #[test]
+    fn test_probe_dtz() {
+        let tablebase = get_krvk_tablebase("dtz");
+        // the table stores moves, so the distance is rounded up to plies
+        assert_eq!(
+            tablebase
+                .probe_dtz(&game("8/8/8/8/8/2k5/8/KR6 w - - 0 1"))
+                .unwrap(),
+            11
+        );
+        // Black to move isn't stored, so it is a ply further from White's
+        assert_eq!(
+            tablebase
+                .probe_dtz(&game("8/8/8/8/8/2k5/8/KR6 b - - 0 1"))
+                .unwrap(),
+            -12
+        );
+        assert_eq!(
+            tablebase
+                .probe_dtz(&game("8/8/8/8/8/8/1kR5/7K b - - 0 1"))
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_best_move() {
+        let tablebase = get_krvk_tablebase("best-move");
+        let best_move = tablebase
+            .get_best_move(&game("8/8/8/8/8/8/1kR5/7K b - - 0 1"))
+            .unwrap();
+        assert_eq!(
+            best_move,
+            Some(Move::new(Position::new(1, 1), Position::new(2, 1)))
+        );
+
+        // White saves the rook
+        let mut game = game("8/8/8/8/8/8/1k6/2R4K w - - 0 1");
+        let best_move = tablebase.get_best_move(&game).unwrap().unwrap();
+        game.make_move(best_move).unwrap();
+        assert_eq!(tablebase.probe_wdl(&game).unwrap(), Wdl::Loss);
+    }
+
+    // The KRvK tables of the published Syzygy set, which CI downloads into
+    // testdata/syzygy. KRvK has no cursed results, and every win is a mate
+    // within 16 moves since the rook never has to be given up.
+    #[test]
+    #[ignore = "needs the KRvK tables in testdata/syzygy"]
+    fn test_real_tables() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("testdata/syzygy");
+        let tablebase = Tablebase::open(&dir).unwrap();
+        let probe = |fen| tablebase.probe_wdl(&game(fen)).unwrap();
+        // mate in one
+        assert_eq!(probe("4k3/8/4K3/8/8/8/8/R7 w - - 0 1"), Wdl::Win);
+        assert_eq!(probe("R3k3/8/4K3/8/8/8/8/8 b - - 0 1"), Wdl::Loss);
+        // stalemate, and a rook that can be taken
+        assert_eq!(probe("k7/1R6/1K6/8/8/8/8/8 b - - 0 1"), Wdl::Draw);
+        assert_eq!(probe("8/8/8/8/8/8/1kR5/7K b - - 0 1"), Wdl::Draw);
+        // with the colors swapped
+        assert_eq!(probe("r7/8/8/8/8/4k3/8/4K3 b - - 0 1"), Wdl::Win);
+
+        // Every result has to follow from the results after each move. Only
+        // the positions with the white king on c3 are checked, since all of
+        // them would take too long.
+        let white_king = Position::parse("c3").unwrap().get_square();
+        let mut dtz_cache = HashMap::new();
+        for rook in (0..64).filter(|&rook| rook != white_king) {
+            for black_king in (0..64).filter(|&king| king != white_king && king != rook) {
+                let mut squares = ['1'; 64];
+                squares[white_king] = 'K';
+                squares[rook] = 'R';
+                squares[black_king] = 'k';
+                let placement = squares
+                    .chunks(8)
+                    .rev()
+                    .map(|rank| rank.iter().collect::<String>())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                for player in ["w", "b"] {
+                    let fen = format!("{placement} {player} - - 0 1");
+                    // illegal positions, like the player not to move in check
+                    if let Ok(game) = Game::from_fen(&fen) {
+                        check_real_position(
+                            &tablebase,
+                            game.get_current_state(),
+                            &fen,
+                            &mut dtz_cache,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    // The distances are cached by the key of the position, as each one is probed
+    // from every position leading to it
+    fn check_real_position(
+        tablebase: &Tablebase,
+        state: &BoardState,
+        fen: &str,
+        dtz_cache: &mut HashMap<u64, i32>,
+    ) {
+        let mut get_dtz = |state: &BoardState| {
+            *dtz_cache
+                .entry(state.get_zobrist_key())
+                .or_insert_with(|| tablebase.get_dtz(state).unwrap())
+        };
+        let wdl = tablebase.search(state, false).unwrap().0;
+        let mut best_wdl = Wdl::Loss;
+        let mut best_dtz = None;
+        for mv in movegen::get_legal_moves(state) {
+            let mut next = *state;
+            next.move_piece(&mv);
+            if Tablebase::is_zeroing(state, &mv) {
+                // only the rook can be taken
+                best_wdl = best_wdl.max(Wdl::Draw);
+                continue;
+            }
+            let next_wdl = -tablebase.search(&next, false).unwrap().0;
+            best_wdl = best_wdl.max(next_wdl);
+            if next_wdl == Wdl::Win {
+                let next_dtz = if Tablebase::is_mate(&next) {
+                    0
+                } else {
+                    -get_dtz(&next)
+                };
+                best_dtz = best_dtz.into_iter().chain([next_dtz + 1]).min();
+            }
+        }
+        if movegen::get_legal_moves(state).is_empty() {
+            best_wdl = if movegen::is_king_under_attack(state) {
+                Wdl::Loss
+            } else {
+                Wdl::Draw
+            };
+        }
+        assert_eq!(wdl, best_wdl, "{fen}");
+        let dtz = get_dtz(state);
+        match wdl {
+            Wdl::Win => {
+                // the table may round the distance up by a ply
+                let best_dtz = best_dtz.unwrap();
+                assert!(dtz == best_dtz || dtz == best_dtz + 1, "{fen}: {dtz}");
+                assert!(dtz <= 33, "{fen}: {dtz}");
+            }
+            Wdl::Loss => assert!((-34..0).contains(&dtz), "{fen}: {dtz}"),
+            _ => assert_eq!(dtz, 0, "{fen}"),
+        }
+    }
+
+    #[test]
+    fn test_unsupported_positions() {
+        let tablebase = get_krvk_tablebase("unsupported");
+        assert!(!tablebase.can_probe(&Game::new()));
+        assert!(matches!(
+            tablebase.probe_wdl(&Game::new()),
+            Err(TablebaseError::UnsupportedPosition)
+        ));
+        assert!(matches!(
+            tablebase.probe_wdl(&game("8/8/8/8/8/2k5/8/KQ6 w - - 0 1")),
+            Err(TablebaseError::MissingTable(name)) if name == "KQvK.rtbw"
+        ));
+    }
+}
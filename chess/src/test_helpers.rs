@@ -0,0 +1,8 @@
+use crate::{game::Game, moves::Move};
+
+/// Plays moves given in the long algebraic notation, such as "e2e4".
+pub(crate) fn play(game: &mut Game, moves: &[&str]) {
+    for lan in moves {
+        game.make_move(Move::from_lan(lan).unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,333 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use web_time::Duration;
+
+use crate::result::SaveError;
+
+const DEFAULT_START_TIME: Duration = Duration::from_secs(3600);
+
+/// What the clock gives back for each move.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum TimeBonus {
+    #[default]
+    None,
+    /// Added to the player's clock after every move.
+    Fischer(Duration),
+    /// The time spent on a move is given back, up to the delay.
+    Bronstein(Duration),
+    /// The clock waits for the delay before it starts running on each move.
+    SimpleDelay(Duration),
+}
+
+impl TimeBonus {
+    /// How much of `elapsed` a move costs while the clock is running. Only a
+    /// simple delay holds the clock back; Bronstein refunds once the move is made.
+    pub(super) fn get_running_cost(&self, elapsed: Duration) -> Duration {
+        match self {
+            Self::SimpleDelay(delay) => elapsed.saturating_sub(*delay),
+            _ => elapsed,
+        }
+    }
+
+    /// The time left after a move that took `elapsed`, starting from `time`.
+    /// A player whose time ran out gets nothing back.
+    pub(super) fn apply(&self, time: Duration, elapsed: Duration) -> Duration {
+        let remaining = time.saturating_sub(self.get_running_cost(elapsed));
+        if remaining.is_zero() {
+            return remaining;
+        }
+        match self {
+            Self::Fischer(increment) => remaining + *increment,
+            Self::Bronstein(delay) => remaining + elapsed.min(*delay),
+            Self::None | Self::SimpleDelay(_) => remaining,
+        }
+    }
+}
+
+/// One stage of a time control: `time` is added to the clock when the period
+/// starts and has to last for `moves` moves, or for the rest of the game.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub struct TimePeriod {
+    pub moves: Option<usize>,
+    pub time: Duration,
+    pub bonus: TimeBonus,
+}
+
+impl TimePeriod {
+    /// A period that lasts for the rest of the game.
+    pub const fn sudden_death(time: Duration, bonus: TimeBonus) -> Self {
+        Self {
+            moves: None,
+            time,
+            bonus,
+        }
+    }
+
+    pub const fn moves(moves: usize, time: Duration, bonus: TimeBonus) -> Self {
+        Self {
+            moves: Some(moves),
+            time,
+            bonus,
+        }
+    }
+}
+
+impl fmt::Display for TimePeriod {
+    /// Minutes and bonus seconds, like 3+2 for Fischer increment, with the kind
+    /// of delay spelled out and the number of moves in front.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(moves) = self.moves {
+            write!(f, "{moves}/")?;
+        }
+        let time = self.time.as_secs();
+        if time.is_multiple_of(60) {
+            write!(f, "{}", time / 60)?;
+        } else {
+            write!(f, "{:.1}", time as f64 / 60.0)?;
+        }
+        match self.bonus {
+            TimeBonus::None => write!(f, "+0"),
+            TimeBonus::Fischer(increment) => write!(f, "+{}", increment.as_secs()),
+            TimeBonus::Bronstein(delay) => write!(f, "+{} Bronstein", delay.as_secs()),
+            TimeBonus::SimpleDelay(delay) => write!(f, " d{}", delay.as_secs()),
+        }
+    }
+}
+
+/// The periods a game is played in. When a player has made the moves of one
+/// period, the time of the next one is added to their clock. If the last period
+/// has a move count, it repeats.
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(try_from = "SavedTimeControl")]
+pub struct TimeControl {
+    periods: Vec<TimePeriod>,
+}
+
+// A time control as read, before it's checked to have a period
+#[derive(Deserialize)]
+struct SavedTimeControl {
+    periods: Vec<TimePeriod>,
+}
+
+impl TryFrom<SavedTimeControl> for TimeControl {
+    type Error = SaveError;
+
+    fn try_from(saved: SavedTimeControl) -> Result<Self, Self::Error> {
+        if saved.periods.is_empty() {
+            return Err(SaveError::NoTimePeriods);
+        }
+        Ok(Self {
+            periods: saved.periods,
+        })
+    }
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        DEFAULT_START_TIME.into()
+    }
+}
+
+impl From<Duration> for TimeControl {
+    fn from(start_time: Duration) -> Self {
+        TimePeriod::sudden_death(start_time, TimeBonus::None).into()
+    }
+}
+
+impl From<TimePeriod> for TimeControl {
+    fn from(period: TimePeriod) -> Self {
+        Self {
+            periods: vec![period],
+        }
+    }
+}
+
+impl TimeControl {
+    /// The usual "minutes + seconds" blitz control, such as 3+2.
+    pub fn fischer(start_time: Duration, increment: Duration) -> Self {
+        TimePeriod::sudden_death(start_time, TimeBonus::Fischer(increment)).into()
+    }
+
+    pub fn bronstein(start_time: Duration, delay: Duration) -> Self {
+        TimePeriod::sudden_death(start_time, TimeBonus::Bronstein(delay)).into()
+    }
+
+    pub fn simple_delay(start_time: Duration, delay: Duration) -> Self {
+        TimePeriod::sudden_death(start_time, TimeBonus::SimpleDelay(delay)).into()
+    }
+
+    /// Adds a period that starts once the moves of the previous one are made.
+    pub fn then(mut self, period: TimePeriod) -> Self {
+        self.periods.push(period);
+        self
+    }
+
+    pub fn get_periods(&self) -> &[TimePeriod] {
+        &self.periods
+    }
+
+    pub fn get_start_time(&self) -> Duration {
+        self.periods[0].time
+    }
+
+    pub(super) fn get_period(&self, index: usize) -> &TimePeriod {
+        &self.periods[index.min(self.periods.len() - 1)]
+    }
+
+    /// The move number after which the period with the given index ends.
+    pub(super) fn get_period_end(&self, index: usize) -> Option<usize> {
+        (0..=index).try_fold(0, |end, i| Some(end + self.get_period(i).moves?))
+    }
+
+    /// The value of a PGN TimeControl tag, such as "40/5400+30:1800+30". PGN has
+    /// no notation for delays, so controls that use one can't be written.
+    pub(super) fn to_pgn(&self) -> Option<String> {
+        let periods = self.periods.iter().map(|period| {
+            let mut value = String::new();
+            if let Some(moves) = period.moves {
+                value.push_str(&format!("{moves}/"));
+            }
+            value.push_str(&period.time.as_secs().to_string());
+            match period.bonus {
+                TimeBonus::None => (),
+                TimeBonus::Fischer(increment) => {
+                    value.push_str(&format!("+{}", increment.as_secs()))
+                }
+                TimeBonus::Bronstein(_) | TimeBonus::SimpleDelay(_) => return None,
+            }
+            Some(value)
+        });
+        Some(periods.collect::<Option<Vec<_>>>()?.join(":"))
+    }
+
+    /// Reads the value of a PGN TimeControl tag. Unknown ("?") and untimed ("-")
+    /// games give `None`, as does a control this clock can't play.
+    pub(super) fn from_pgn(value: &str) -> Option<Self> {
+        let periods = value
+            .split(':')
+            .map(|period| {
+                let (moves, period) = match period.split_once('/') {
+                    Some((moves, period)) => (Some(moves.parse().ok()?), period),
+                    None => (None, period),
+                };
+                let (time, bonus) = match period.split_once('+') {
+                    Some((time, increment)) => (
+                        time,
+                        TimeBonus::Fischer(Duration::from_secs(increment.parse().ok()?)),
+                    ),
+                    None => (period, TimeBonus::None),
+                };
+                let time = Duration::from_secs(time.parse().ok()?);
+                Some(TimePeriod { moves, time, bonus })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self { periods })
+    }
+}
+
+impl fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, period) in self.periods.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", then ")?;
+            }
+            write!(f, "{period}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+    const SECOND: Duration = Duration::from_secs(1);
+
+    fn classical() -> TimeControl {
+        TimeControl::from(TimePeriod::moves(
+            40,
+            90 * MINUTE,
+            TimeBonus::Fischer(30 * SECOND),
+        ))
+        .then(TimePeriod::sudden_death(
+            30 * MINUTE,
+            TimeBonus::Fischer(30 * SECOND),
+        ))
+    }
+
+    #[test]
+    fn test_apply() {
+        let time = MINUTE;
+
+        let fischer = TimeBonus::Fischer(2 * SECOND);
+        assert_eq!(fischer.apply(time, 5 * SECOND), 57 * SECOND);
+
+        let bronstein = TimeBonus::Bronstein(3 * SECOND);
+        assert_eq!(bronstein.apply(time, 2 * SECOND), time);
+        assert_eq!(bronstein.apply(time, 5 * SECOND), 58 * SECOND);
+        assert_eq!(bronstein.get_running_cost(2 * SECOND), 2 * SECOND);
+
+        let delay = TimeBonus::SimpleDelay(3 * SECOND);
+        assert_eq!(delay.apply(time, 2 * SECOND), time);
+        assert_eq!(delay.apply(time, 5 * SECOND), 58 * SECOND);
+        assert_eq!(delay.get_running_cost(2 * SECOND), Duration::ZERO);
+
+        // running out of time isn't undone by the bonus
+        assert_eq!(fischer.apply(SECOND, 2 * SECOND), Duration::ZERO);
+        assert_eq!(bronstein.apply(SECOND, 2 * SECOND), Duration::ZERO);
+        // but a simple delay can save a player with little time left
+        assert_eq!(delay.apply(SECOND, 2 * SECOND), SECOND);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(TimeControl::default().to_string(), "60+0");
+        assert_eq!(
+            TimeControl::fischer(3 * MINUTE, 2 * SECOND).to_string(),
+            "3+2"
+        );
+        assert_eq!(
+            TimeControl::bronstein(5 * MINUTE, 3 * SECOND).to_string(),
+            "5+3 Bronstein"
+        );
+        assert_eq!(
+            TimeControl::simple_delay(MINUTE / 2, 5 * SECOND).to_string(),
+            "0.5 d5"
+        );
+        assert_eq!(classical().to_string(), "40/90+30, then 30+30");
+    }
+
+    #[test]
+    fn test_periods() {
+        let classical = classical();
+        assert_eq!(classical.get_start_time(), 90 * MINUTE);
+        assert_eq!(classical.get_period_end(0), Some(40));
+        assert_eq!(classical.get_period_end(1), None);
+
+        // a last period with a move count repeats
+        let repeating = TimeControl::from(TimePeriod::moves(40, 120 * MINUTE, TimeBonus::None))
+            .then(TimePeriod::moves(20, 60 * MINUTE, TimeBonus::None));
+        assert_eq!(repeating.get_period_end(1), Some(60));
+        assert_eq!(repeating.get_period_end(2), Some(80));
+        assert_eq!(repeating.get_period(2).time, 60 * MINUTE);
+    }
+
+    #[test]
+    fn test_pgn() {
+        assert_eq!(classical().to_pgn().unwrap(), "40/5400+30:1800+30");
+        assert_eq!(TimeControl::default().to_pgn().unwrap(), "3600");
+        assert_eq!(TimeControl::bronstein(MINUTE, SECOND).to_pgn(), None);
+
+        for value in ["40/5400+30:1800+30", "300+3", "40/7200:3600"] {
+            assert_eq!(
+                TimeControl::from_pgn(value).unwrap().to_pgn().unwrap(),
+                value
+            );
+        }
+        for value in ["?", "-", "*180", "40/", "300+"] {
+            assert_eq!(TimeControl::from_pgn(value), None);
+        }
+    }
+}
@@ -1,73 +1,154 @@
-use web_time::{Duration, Instant};
+use std::sync::Arc;
 
-use crate::color::Color;
+use web_time::Duration;
 
-pub(super) const DEFAULT_DURATION: Duration = Duration::from_secs(3600);
+use crate::{
+    clock::{Clock, RealClock},
+    color::Color,
+    save::SavedTimer,
+    time_control::{TimeBonus, TimeControl},
+};
 
 #[derive(Clone)]
 pub(super) struct Timer {
     white_time: Duration,
     black_time: Duration,
-    time_started: Option<Instant>,
+    time_controls: [TimeControl; 2],
+    // the period each player is in
+    periods: [usize; 2],
+    clock: Arc<dyn Clock>,
+    // when the current player's clock was started, as read from `clock`
+    time_started: Option<Duration>,
     current_player: Color,
 }
 
 impl Default for Timer {
     fn default() -> Self {
-        Self {
-            white_time: DEFAULT_DURATION,
-            black_time: DEFAULT_DURATION,
-            time_started: None,
-            current_player: Color::White,
-        }
+        Self::with_time_control(TimeControl::default(), Color::White)
     }
 }
 
 impl Timer {
-    pub(super) fn with_duration(start_time: Duration) -> Self {
+    pub(super) fn with_time_control(time_control: TimeControl, first_player: Color) -> Self {
+        Self::with_time_controls([time_control.clone(), time_control], first_player)
+    }
+
+    /// A timer where each player has their own time control, indexed by color.
+    pub(super) fn with_time_controls(time_controls: [TimeControl; 2], first_player: Color) -> Self {
         Self {
-            white_time: start_time,
-            black_time: start_time,
+            white_time: time_controls[Color::White as usize].get_start_time(),
+            black_time: time_controls[Color::Black as usize].get_start_time(),
+            time_controls,
+            periods: [0; 2],
+            clock: Arc::new(RealClock::default()),
             time_started: None,
-            current_player: Color::White,
+            current_player: first_player,
         }
     }
 
+    /// A running clock is saved with the time it has left, and starts again
+    /// from there when it's loaded.
+    pub(super) fn to_saved(&self) -> SavedTimer {
+        SavedTimer {
+            times: [self.get_time(Color::White), self.get_time(Color::Black)],
+            time_controls: self.time_controls.clone(),
+            periods: self.periods,
+            current_player: self.current_player,
+            running: self.is_active(),
+        }
+    }
+
+    pub(super) fn from_saved(saved: SavedTimer) -> Self {
+        let [white_time, black_time] = saved.times;
+        let mut timer = Self {
+            white_time,
+            black_time,
+            periods: saved.periods,
+            current_player: saved.current_player,
+            ..Self::with_time_controls(saved.time_controls, saved.current_player)
+        };
+        if saved.running {
+            timer.start();
+        }
+        timer
+    }
+
+    pub(super) fn get_time_control(&self, player: Color) -> &TimeControl {
+        &self.time_controls[player as usize]
+    }
+
+    pub(super) fn get_clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    /// Replaces the clock the time is read from. A running timer keeps the time
+    /// it has used so far.
+    pub(super) fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        if let Some(time_started) = &mut self.time_started {
+            let elapsed = self.clock.now().saturating_sub(*time_started);
+            *time_started = clock.now().saturating_sub(elapsed);
+        }
+        self.clock = clock;
+    }
+
+    fn get_elapsed(&self, time_started: Duration) -> Duration {
+        self.clock.now().saturating_sub(time_started)
+    }
+
+    fn get_bonus(&self, player: Color) -> TimeBonus {
+        self.get_time_control(player)
+            .get_period(self.periods[player as usize])
+            .bonus
+    }
+
     pub(super) fn start(&mut self) {
-        self.time_started = Some(Instant::now());
+        self.time_started = Some(self.clock.now());
     }
 
+    // A game can end before its first move starts the clock, by resigning or
+    // from a finished position, which leaves the timer as it is
     pub(super) fn stop(&mut self) {
-        self.pause_active_time();
-        self.time_started = None;
+        if self.is_active() {
+            let elapsed = self.take_elapsed();
+            let cost = self
+                .get_bonus(self.current_player)
+                .get_running_cost(elapsed);
+            let time = self.get_time_mut(self.current_player);
+            *time = time.saturating_sub(cost);
+        }
     }
 
-    fn pause_active_time(&mut self) {
-        let elapsed = self
+    fn take_elapsed(&mut self) -> Duration {
+        let time_started = self
             .time_started
             .take()
-            .expect("call `timer.start()` first")
-            .elapsed();
-
-        match self.current_player {
-            Color::White => {
-                self.white_time = self
-                    .white_time
-                    .checked_sub(elapsed)
-                    .unwrap_or(Duration::from_secs(0));
-            }
-            Color::Black => {
-                self.black_time = self
-                    .black_time
-                    .checked_sub(elapsed)
-                    .unwrap_or(Duration::from_secs(0));
-            }
-        };
+            .expect("call `timer.start()` first");
+        self.get_elapsed(time_started)
     }
 
-    pub(super) fn next_player(&mut self) {
-        self.pause_active_time();
-        self.current_player = !self.current_player;
+    fn get_time_mut(&mut self, player: Color) -> &mut Duration {
+        match player {
+            Color::White => &mut self.white_time,
+            Color::Black => &mut self.black_time,
+        }
+    }
+
+    /// Ends the current player's move, giving them their bonus and the time of
+    /// the next period if `move_number` finishes the current one, and starts
+    /// the other player's clock.
+    pub(super) fn next_player(&mut self, move_number: usize) {
+        let player = self.current_player;
+        let elapsed = self.take_elapsed();
+        let mut time = self.get_bonus(player).apply(self.get_time(player), elapsed);
+
+        let time_control = &self.time_controls[player as usize];
+        let period = &mut self.periods[player as usize];
+        if !time.is_zero() && time_control.get_period_end(*period) == Some(move_number) {
+            *period += 1;
+            time += time_control.get_period(*period).time;
+        }
+        *self.get_time_mut(player) = time;
+        self.current_player = !player;
         self.start();
     }
 
@@ -77,12 +158,12 @@ impl Timer {
             Color::Black => self.black_time,
         };
 
-        if self.time_started.is_some() && player == self.current_player {
-            current_time
-                .checked_sub(self.time_started.unwrap().elapsed())
-                .unwrap_or(Duration::from_secs(0))
-        } else {
-            current_time
+        match self.time_started {
+            Some(time_started) if player == self.current_player => current_time.saturating_sub(
+                self.get_bonus(player)
+                    .get_running_cost(self.get_elapsed(time_started)),
+            ),
+            _ => current_time,
         }
     }
 
@@ -90,16 +171,6 @@ impl Timer {
         self.get_time(self.current_player)
     }
 
-    pub(super) fn print(&self) {
-        for &player in &[Color::White, Color::Black] {
-            let total_seconds = self.get_time(player).as_secs();
-            let minutes = total_seconds / 60;
-            let seconds = total_seconds % 60;
-
-            log::info!("{:?}: {:02}:{:02}", player, minutes, seconds);
-        }
-    }
-
     pub(super) fn is_active(&self) -> bool {
         self.time_started.is_some()
     }
@@ -0,0 +1,110 @@
+use crate::moves::Move;
+
+const DEFAULT_SIZE: usize = 1 << 16;
+
+/// How a stored score relates to the true score of the position.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(super) enum Bound {
+    Exact,
+    /// The search failed high, so the true score is at least this.
+    Lower,
+    /// The search failed low, so the true score is at most this.
+    Upper,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub(super) struct Entry {
+    key: u64,
+    pub(super) depth: u8,
+    pub(super) score: i32,
+    pub(super) bound: Bound,
+    pub(super) best_move: Option<Move>,
+}
+
+/// Search results by Zobrist key, so that positions reached by different move
+/// orders are only searched once. Keys share slots, and the last position
+/// stored takes its slot over, except that a shallower search of the same
+/// position keeps the deeper result.
+#[derive(Clone)]
+pub(super) struct TranspositionTable {
+    entries: Vec<Option<Entry>>,
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        Self::with_size(DEFAULT_SIZE)
+    }
+}
+
+impl TranspositionTable {
+    /// A table with room for `size` entries, rounded up to a power of two.
+    pub(super) fn with_size(size: usize) -> Self {
+        Self {
+            entries: vec![None; size.next_power_of_two()],
+        }
+    }
+
+    fn get_index(&self, key: u64) -> usize {
+        key as usize & (self.entries.len() - 1)
+    }
+
+    pub(super) fn get(&self, key: u64) -> Option<&Entry> {
+        self.entries[self.get_index(key)]
+            .as_ref()
+            .filter(|entry| entry.key == key)
+    }
+
+    pub(super) fn insert(
+        &mut self,
+        key: u64,
+        depth: u8,
+        score: i32,
+        bound: Bound,
+        best_move: Option<Move>,
+    ) {
+        let index = self.get_index(key);
+        let slot = &mut self.entries[index];
+        if slot.is_some_and(|entry| entry.key == key && entry.depth > depth) {
+            return;
+        }
+        *slot = Some(Entry {
+            key,
+            depth,
+            score,
+            bound,
+            best_move,
+        });
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.entries.fill(None);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::position::Position;
+
+    #[test]
+    fn test_replacement() {
+        let mut table = TranspositionTable::with_size(3);
+        let mv = Move::new(Position::new(4, 1), Position::new(4, 3));
+        table.insert(5, 3, 20, Bound::Exact, Some(mv));
+        assert_eq!(table.get(5).unwrap().best_move, Some(mv));
+        // a key sharing the slot isn't mistaken for the stored one
+        assert!(table.get(1).is_none());
+
+        // a shallower search of the same position keeps the deeper result
+        table.insert(5, 1, -40, Bound::Upper, None);
+        assert_eq!(table.get(5).unwrap().score, 20);
+
+        // but another position takes the slot over
+        table.insert(1, 1, -40, Bound::Lower, None);
+        assert!(table.get(5).is_none());
+        assert_eq!(table.get(1).unwrap().bound, Bound::Lower);
+
+        table.clear();
+        assert!(table.get(1).is_none());
+    }
+}
@@ -1,41 +1,60 @@
 use std::fmt;
 
-use crate::{board_state::BoardState, game_status::GameStatus, moves::Move};
+use crate::{
+    annotation::Annotation,
+    board_state::BoardState,
+    game_status::GameStatus,
+    moves::Move,
+    san::{Notation, San},
+};
 
-#[derive(Clone, Copy, Default)]
+#[derive(Clone)]
 pub struct Turn {
     pub(super) board_state: BoardState,
     pub(super) mv: Move,
-    pub(super) piece_captured: bool,
+    pub(super) san: San,
     pub(crate) status: GameStatus,
+    // the halfmove clock of the fifty-move rule after the move
+    pub(super) fifty_move_count: u8,
+    pub(super) annotation: Annotation,
 }
 
 impl Turn {
-    pub fn new(board_state: BoardState, mv: Move, piece_captured: bool) -> Self {
+    pub(super) fn new(board_state: BoardState, mv: Move, san: San, fifty_move_count: u8) -> Self {
         Self {
             board_state,
             mv,
-            piece_captured,
-            ..Default::default()
+            san,
+            status: GameStatus::default(),
+            fifty_move_count,
+            annotation: Annotation::default(),
         }
     }
+
+    // A check that ends the game some other way, like the third check in
+    // Three-check, is still a check
+    fn get_check_suffix(&self) -> &'static str {
+        let state = &self.board_state;
+        if matches!(self.status, GameStatus::Checkmate(..)) {
+            "#"
+        } else if state.variant.is_king_under_attack(state) {
+            "+"
+        } else {
+            ""
+        }
+    }
+
+    pub(super) fn get_san_str(&self, notation: Notation) -> String {
+        format!(
+            "{}{}",
+            self.san.to_notation_string(notation),
+            self.get_check_suffix()
+        )
+    }
 }
 
 impl fmt::Display for Turn {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "{}{}{}{}",
-            self.board_state.get_piece(&self.mv.to).unwrap(),
-            if self.piece_captured { "x" } else { "" },
-            self.mv.to,
-            if matches!(self.status, GameStatus::Check(..)) {
-                "+"
-            } else if matches!(self.status, GameStatus::Checkmate(..)) {
-                "#"
-            } else {
-                ""
-            }
-        )
+        write!(f, "{}", self.get_san_str(Notation::Letter))
     }
 }
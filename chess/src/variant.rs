@@ -0,0 +1,221 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bitboard::Bitboard, board::Board, board_state::BoardState, castling_rights::CastlingRights,
+    color::Color, fen::Fen, movegen, piece::Piece,
+};
+
+const HORDE_FEN: &str =
+    "rnbqkbnr/pppppppp/8/1PP2PP1/PPPPPPPP/PPPPPPPP/PPPPPPPP/PPPPPPPP w kq - 0 1";
+// d4, e4, d5 and e5
+const HILL: Bitboard = Bitboard(0x0000_0018_1800_0000);
+pub(super) const THREE_CHECK_CHECKS: u8 = 3;
+
+/// The rules a game is played by. Each variant decides its start position,
+/// which moves are legal and how the game can end; Chess960 is a choice of
+/// start position rather than a variant, so it can be combined with any of them.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum Variant {
+    #[default]
+    Standard,
+    /// Bringing your king to one of the four center squares wins.
+    KingOfTheHill,
+    /// Checking the opponent three times wins.
+    ThreeCheck,
+    /// Captures are compulsory, the king is an ordinary piece and the first
+    /// player to lose all their pieces, or to be stalemated, wins.
+    Antichess,
+    /// Captures explode, taking every piece but pawns next to the captured
+    /// piece with them. Exploding the opponent's king wins.
+    Atomic,
+    /// White has 36 pawns and no king, and loses once they are all captured.
+    Horde,
+}
+
+impl Variant {
+    pub const ALL: [Self; 6] = [
+        Self::Standard,
+        Self::KingOfTheHill,
+        Self::ThreeCheck,
+        Self::Antichess,
+        Self::Atomic,
+        Self::Horde,
+    ];
+
+    pub(super) fn get_start_state(self) -> BoardState {
+        match self {
+            Self::Horde => Fen::parse(HORDE_FEN, self).unwrap().state,
+            Self::Antichess => {
+                BoardState::new(Board::default(), Color::White, CastlingRights::none(), None)
+                    .with_variant(self)
+            }
+            _ => BoardState::default().with_variant(self),
+        }
+    }
+
+    /// Whether the kings on the board are what the variant expects.
+    pub(super) fn has_valid_kings(self, board: &Board) -> bool {
+        let count = |player| board.get_bitboard(Piece::King(player)).len();
+        match self {
+            Self::Antichess => true,
+            Self::Horde => count(Color::White) == 0 && count(Color::Black) == 1,
+            _ => count(Color::White) == 1 && count(Color::Black) == 1,
+        }
+    }
+
+    // Horde's pawns start on White's first rank
+    pub(super) fn allows_pawn_on(self, piece: Piece, y: usize) -> bool {
+        match (self, piece.get_player(), y) {
+            (Self::Horde, Color::White, 0) => true,
+            (_, _, y) => y != 0 && y != 7,
+        }
+    }
+
+    /// Whether the king of the player to move is in check. Antichess has no
+    /// check, and in Atomic a king can't be attacked by a king next to it,
+    /// since capturing it would blow up the capturing king too.
+    pub(super) fn is_king_under_attack(self, state: &BoardState) -> bool {
+        match self {
+            Self::Antichess => false,
+            Self::Atomic => movegen::is_atomic_king_under_attack(state),
+            _ => movegen::is_king_under_attack(state),
+        }
+    }
+
+    pub(super) fn get_promotion_options(self, player: Color) -> Vec<Piece> {
+        let mut options = Piece::get_promotion_options(player).to_vec();
+        if self == Self::Antichess {
+            options.push(Piece::King(player));
+        }
+        options
+    }
+
+    /// The player who has lost by the variant's own rules, if the game is
+    /// already decided by the position alone.
+    pub(super) fn get_loser(self, state: &BoardState) -> Option<Color> {
+        let board = &state.board;
+        let [white, black] = [Color::White, Color::Black];
+        match self {
+            Self::Standard => None,
+            Self::KingOfTheHill => [white, black]
+                .into_iter()
+                .find(|&player| !(board.get_bitboard(Piece::King(player)) & HILL).is_empty())
+                .map(|player| !player),
+            Self::ThreeCheck => [white, black]
+                .into_iter()
+                .find(|&player| state.remaining_checks[player as usize] == 0)
+                .map(|player| !player),
+            // losing every piece wins
+            Self::Antichess => [white, black]
+                .into_iter()
+                .find(|&player| board.get_color_bitboard(player).is_empty())
+                .map(|player| !player),
+            Self::Atomic => [white, black]
+                .into_iter()
+                .find(|&player| board.get_bitboard(Piece::King(player)).is_empty()),
+            Self::Horde => board.get_color_bitboard(white).is_empty().then_some(white),
+        }
+    }
+
+    /// Whether a player with no legal moves and not in check has lost rather than drawn.
+    pub(super) fn stalemate_loses(self) -> bool {
+        // the stalemated player wins Antichess, so their opponent loses
+        self == Self::Antichess
+    }
+
+    pub(super) fn has_insufficient_material(self, state: &BoardState) -> bool {
+        let board = &state.board;
+        let only_kings = board.get_occupied()
+            == board.get_bitboard(Piece::King(Color::White))
+                | board.get_bitboard(Piece::King(Color::Black));
+        match self {
+            Self::Standard => state.has_insufficient_material(),
+            // any piece can still give check or explode a king
+            Self::ThreeCheck | Self::Atomic => only_kings,
+            // a lone king can still walk to the hill or be given away
+            Self::KingOfTheHill | Self::Antichess | Self::Horde => false,
+        }
+    }
+
+    /// Whether `player` has no way left to win, so that running out of time
+    /// against them is a draw.
+    pub(super) fn has_insufficient_material_for(self, state: &BoardState, player: Color) -> bool {
+        let board = &state.board;
+        let only_king = board.get_color_bitboard(player) == board.get_bitboard(Piece::King(player));
+        match self {
+            Self::Standard => state.has_insufficient_material_for(player),
+            Self::ThreeCheck | Self::Atomic => only_king,
+            Self::KingOfTheHill | Self::Antichess | Self::Horde => false,
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    /// The names used by the PGN Variant tag.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Self::Standard => "Standard",
+            Self::KingOfTheHill => "King of the Hill",
+            Self::ThreeCheck => "Three-check",
+            Self::Antichess => "Antichess",
+            Self::Atomic => "Atomic",
+            Self::Horde => "Horde",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for Variant {
+    type Err = ();
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name: String = name
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        match name.as_str() {
+            "standard" | "chess" | "chess960" => Ok(Self::Standard),
+            "kingofthehill" | "koth" => Ok(Self::KingOfTheHill),
+            "threecheck" | "3check" => Ok(Self::ThreeCheck),
+            "antichess" | "giveaway" => Ok(Self::Antichess),
+            "atomic" => Ok(Self::Atomic),
+            "horde" => Ok(Self::Horde),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_variant_names() {
+        for variant in Variant::ALL {
+            assert_eq!(variant.to_string().parse(), Ok(variant));
+        }
+        assert_eq!("Chess960".parse(), Ok(Variant::Standard));
+        assert_eq!("3check".parse(), Ok(Variant::ThreeCheck));
+        assert_eq!("Crazyhouse".parse::<Variant>(), Err(()));
+    }
+
+    #[test]
+    fn test_start_states() {
+        for variant in Variant::ALL {
+            let state = variant.get_start_state();
+            assert!(variant.has_valid_kings(&state.board));
+            assert_eq!(variant.get_loser(&state), None);
+        }
+        assert_eq!(
+            Variant::Horde
+                .get_start_state()
+                .board
+                .get_bitboard(Piece::Pawn(Color::White))
+                .len(),
+            36
+        );
+    }
+}
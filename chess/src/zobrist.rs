@@ -0,0 +1,118 @@
+use crate::{color::Color, piece::Piece, variant::THREE_CHECK_CHECKS};
+
+static KEYS: ZobristKeys = ZobristKeys::new(0x5EED_C0DE_0BAD_F00D);
+
+/// Random keys for each feature of a position. A position's key is the XOR of
+/// the keys of its features, so making a move only has to toggle the keys of
+/// the features it changes.
+struct ZobristKeys {
+    pieces: [[u64; 64]; 12],
+    castling_rights: [u64; 4],
+    en_passant_files: [u64; 8],
+    black_to_move: u64,
+    // Three-check's remaining checks, from none to all but one left
+    remaining_checks: [[u64; THREE_CHECK_CHECKS as usize]; 2],
+}
+
+impl ZobristKeys {
+    const fn new(seed: u64) -> Self {
+        let mut state = seed;
+        let mut keys = Self {
+            pieces: [[0; 64]; 12],
+            castling_rights: [0; 4],
+            en_passant_files: [0; 8],
+            black_to_move: 0,
+            remaining_checks: [[0; THREE_CHECK_CHECKS as usize]; 2],
+        };
+
+        let mut piece = 0;
+        while piece < 12 {
+            let mut square = 0;
+            while square < 64 {
+                (state, keys.pieces[piece][square]) = split_mix(state);
+                square += 1;
+            }
+            piece += 1;
+        }
+        let mut i = 0;
+        while i < 4 {
+            (state, keys.castling_rights[i]) = split_mix(state);
+            i += 1;
+        }
+        let mut file = 0;
+        while file < 8 {
+            (state, keys.en_passant_files[file]) = split_mix(state);
+            file += 1;
+        }
+        (state, keys.black_to_move) = split_mix(state);
+        let mut player = 0;
+        while player < 2 {
+            let mut checks = 0;
+            while checks < THREE_CHECK_CHECKS as usize {
+                (state, keys.remaining_checks[player][checks]) = split_mix(state);
+                checks += 1;
+            }
+            player += 1;
+        }
+        keys
+    }
+}
+
+// SplitMix64, returning the next state and its output
+const fn split_mix(state: u64) -> (u64, u64) {
+    let state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut z = state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    (state, z ^ (z >> 31))
+}
+
+pub(super) fn get_piece_key(piece: Piece, square: usize) -> u64 {
+    KEYS.pieces[piece.get_index() * 2 + piece.get_player() as usize][square]
+}
+
+pub(super) fn get_castling_right_key(right: usize) -> u64 {
+    KEYS.castling_rights[right]
+}
+
+pub(super) fn get_en_passant_key(file: usize) -> u64 {
+    KEYS.en_passant_files[file]
+}
+
+pub(super) fn get_player_key(player: Color) -> u64 {
+    match player {
+        Color::White => 0,
+        Color::Black => KEYS.black_to_move,
+    }
+}
+
+// Nothing is added while every check is left, so other variants' keys are unaffected
+pub(super) fn get_remaining_checks_key(player: Color, remaining_checks: u8) -> u64 {
+    KEYS.remaining_checks[player as usize]
+        .get(remaining_checks as usize)
+        .copied()
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_keys_are_distinct() {
+        let keys: HashSet<u64> = KEYS
+            .pieces
+            .iter()
+            .flatten()
+            .chain(&KEYS.castling_rights)
+            .chain(&KEYS.en_passant_files)
+            .chain([&KEYS.black_to_move])
+            .chain(KEYS.remaining_checks.iter().flatten())
+            .copied()
+            .collect();
+        assert_eq!(keys.len(), 12 * 64 + 4 + 8 + 1 + 2 * 3);
+        assert!(!keys.contains(&0));
+    }
+}